@@ -17,7 +17,23 @@ create table tournament(
   demand_yintercept bigint not null,
   incentive_multiplier bigint not null,
   incentive_start_year bigint not null,
-  max_years bigint not null
+  max_years bigint not null,
+  -- how long each year lasts before it advances on its own, null if only the creator advances
+  year_duration_ms bigint,
+  -- LateJoinPolicyKind
+  late_join_policy bigint not null,
+  -- ExitPolicyKind
+  exit_policy bigint not null,
+  -- VisibilityKind
+  visibility bigint not null,
+  -- how many players are needed to start, null if any number will do
+  min_players bigint,
+  -- how many players may join, null if there is no limit
+  max_players bigint,
+  -- start on its own once min_players have joined
+  auto_start bool not null,
+  -- one student against bots, left out of tournament listings unless asked for
+  practice bool not null
 );
 
 -- invariant: tournament_id is valid
@@ -42,6 +58,25 @@ create view recent_tournament_data as
   ) maxids
   on maxids.id = td.tournament_data_id;
 
+drop table if exists tournament_state cascade;
+create table tournament_state(
+  tournament_state_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  creator_user_id bigint not null,
+  tournament_id bigint not null references tournament(tournament_id),
+  -- TournamentStateKind
+  state bigint not null
+);
+
+create view recent_tournament_state as
+  select td.* from tournament_state td
+  inner join (
+   select max(tournament_state_id) id 
+   from tournament_state 
+   group by tournament_id
+  ) maxids
+  on maxids.id = td.tournament_state_id;
+
 drop table if exists tournament_year cascade;
 create table tournament_year(
   tournament_year_id bigserial primary key,
@@ -49,7 +84,9 @@ create table tournament_year(
   creator_user_id bigint not null,
   tournament_id bigint not null references tournament(tournament_id),
   -- tournament year
-  current_year bigint not null
+  current_year bigint not null,
+  -- when this year advances on its own, null if it doesn't
+  deadline bigint
 );
 
 create view recent_tournament_year as
@@ -61,19 +98,65 @@ create view recent_tournament_year as
   ) maxids
   on maxids.id = td.tournament_year_id;
 
+-- written when a paused tournament is resumed, finalized or archived
+drop table if exists tournament_pause cascade;
+create table tournament_pause(
+  tournament_pause_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  creator_user_id bigint not null,
+  tournament_id bigint not null references tournament(tournament_id),
+  -- when the tournament was paused
+  start_time bigint not null,
+  -- when the tournament stopped being paused
+  end_time bigint not null
+);
+
+-- several users playing as one firm
+drop table if exists tournament_team cascade;
+create table tournament_team(
+  tournament_team_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  creator_user_id bigint not null,
+  tournament_id bigint not null references tournament(tournament_id),
+  name text not null
+);
+
+-- a computer player an organizer added, it plays as a firm of its own
+drop table if exists tournament_bot cascade;
+create table tournament_bot(
+  tournament_bot_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  creator_user_id bigint not null,
+  tournament_id bigint not null references tournament(tournament_id),
+  -- BotStrategyKind
+  strategy bigint not null,
+  -- what a FIXED_QUANTITY bot always submits, null for every other strategy
+  quantity bigint,
+  -- shown in place of a pseudonym
+  name text not null
+);
 
 drop table if exists tournament_year_demand cascade;
 create table tournament_year_demand(
   tournament_year_demand_id bigserial primary key,
   creation_time bigint not null default extract(epoch from now()) * 1000,
-  -- which user this is for
-  user_id bigint not null,
+  -- which user this is for, null for teams and bots
+  user_id bigint,
+  -- which team this is for, null for players on their own
+  tournament_team_id bigint references tournament_team(tournament_team_id),
+  -- which bot this is for
+  tournament_bot_id bigint references tournament_bot(tournament_bot_id),
   -- id of tournament
   tournament_id bigint not null references tournament(tournament_id),
   -- tournament year
   year bigint not null,
   -- your personal demand per year (actual)
-  demand bigint not null
+  demand bigint not null,
+  -- one demand per firm and year, otherwise incentives would count twice
+  unique (tournament_id, user_id, year),
+  unique (tournament_id, tournament_team_id, year),
+  unique (tournament_id, tournament_bot_id, year),
+  check (num_nonnulls(user_id, tournament_team_id, tournament_bot_id) = 1)
 );
 
 drop table if exists tournament_membership cascade;
@@ -82,7 +165,21 @@ create table tournament_membership(
   creation_time bigint not null default extract(epoch from now()) * 1000,
   creator_user_id bigint not null,
   tournament_id bigint not null references tournament(tournament_id),
-  active bool not null
+  active bool not null,
+  -- joined late, and may watch but not play
+  observer bool not null,
+  -- ApprovalKind, only players who were approved take part
+  approval bigint not null,
+  -- taken out by an organizer, and left out of the results
+  removed bool not null,
+  -- removed, and may not rejoin
+  banned bool not null,
+  -- who made this change, the member or an organizer
+  actor_user_id bigint not null,
+  -- why an organizer made this change
+  reason text,
+  -- the team this member plays for, null if they play on their own
+  tournament_team_id bigint references tournament_team(tournament_team_id)
 );
 
 create view recent_tournament_membership as
@@ -94,14 +191,133 @@ create view recent_tournament_membership as
   ) maxids
   on maxids.id = td.tournament_membership_id;
 
+-- the code players need to join an invite only tournament, the most recent one is valid
+drop table if exists tournament_invite_code cascade;
+create table tournament_invite_code(
+  tournament_invite_code_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  creator_user_id bigint not null,
+  tournament_id bigint not null references tournament(tournament_id),
+  invite_code text not null
+);
+
+create view recent_tournament_invite_code as
+  select td.* from tournament_invite_code td
+  inner join (
+   select max(tournament_invite_code_id) id 
+   from tournament_invite_code 
+   group by tournament_id
+  ) maxids
+  on maxids.id = td.tournament_invite_code_id;
+
+-- the name a member goes by in a tournament, so players don't learn who they play against
+-- given out once, on the first join
+drop table if exists tournament_pseudonym cascade;
+create table tournament_pseudonym(
+  tournament_pseudonym_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  creator_user_id bigint not null,
+  tournament_id bigint not null references tournament(tournament_id),
+  -- who goes by this name
+  user_id bigint not null,
+  pseudonym text not null,
+  unique (tournament_id, user_id),
+  unique (tournament_id, pseudonym)
+);
+
+-- who may help run a tournament, the creator is its first owner
+drop table if exists tournament_role cascade;
+create table tournament_role(
+  tournament_role_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  -- who granted or revoked the role
+  creator_user_id bigint not null,
+  tournament_id bigint not null references tournament(tournament_id),
+  -- who holds the role
+  user_id bigint not null,
+  -- TournamentRoleKind
+  role bigint not null,
+  -- false once revoked
+  active bool not null
+);
+
+create view recent_tournament_role as
+  select td.* from tournament_role td
+  inner join (
+   select max(tournament_role_id) id 
+   from tournament_role 
+   group by tournament_id, user_id
+  ) maxids
+  on maxids.id = td.tournament_role_id;
+
 
 drop table if exists tournament_submission cascade;
 create table tournament_submission(
   tournament_submission_id bigserial primary key,
   creation_time bigint not null default extract(epoch from now()) * 1000,
+  -- who submitted, any member of a team may submit for it, bots submit as whoever added them
   creator_user_id bigint not null,
+  -- the team submitted for, null for players on their own
+  tournament_team_id bigint references tournament_team(tournament_team_id),
+  -- the bot submitted for
+  tournament_bot_id bigint references tournament_bot(tournament_bot_id),
   tournament_id bigint not null references tournament(tournament_id),
   year bigint not null,
   amount bigint not null,
   autogenerated bool not null 
 );
+
+-- team members overwrite each other's submissions, everyone else only their own
+create view recent_tournament_submission as
+  select td.* from tournament_submission td
+  inner join (
+   select max(tournament_submission_id) id 
+   from tournament_submission 
+   group by tournament_id, tournament_team_id, tournament_bot_id, case when tournament_team_id is null and tournament_bot_id is null then creator_user_id end, year
+  ) maxids
+  on maxids.id = td.tournament_submission_id;
+
+-- written once, when the tournament is finalized
+drop table if exists tournament_final_result cascade;
+create table tournament_final_result(
+  tournament_final_result_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  -- who finalized the tournament
+  creator_user_id bigint not null,
+  tournament_id bigint not null references tournament(tournament_id),
+  -- which user this is for, null for teams and bots
+  user_id bigint,
+  -- which team this is for, null for players on their own
+  tournament_team_id bigint references tournament_team(tournament_team_id),
+  -- which bot this is for
+  tournament_bot_id bigint references tournament_bot(tournament_bot_id),
+  -- 1 is best, tied balances share a rank
+  rank bigint not null,
+  -- total profit over every year
+  balance double precision not null,
+  unique (tournament_id, user_id),
+  unique (tournament_id, tournament_team_id),
+  unique (tournament_id, tournament_bot_id),
+  check (num_nonnulls(user_id, tournament_team_id, tournament_bot_id) = 1)
+);
+
+-- lets clients retry mutating requests without doing them twice
+drop table if exists idempotency_key cascade;
+create table idempotency_key(
+  idempotency_key_id bigserial primary key,
+  creation_time bigint not null default extract(epoch from now()) * 1000,
+  -- who made the request, keys are only unique per user
+  creator_user_id bigint not null,
+  -- which endpoint was called, like tournament_submission/new
+  endpoint text not null,
+  -- chosen by the client
+  idempotency_key text not null,
+  -- fingerprint of the request, a key can't be reused for a different one
+  request_hash text not null,
+  -- the json we responded with, returned again on replays
+  response text not null
+);
+
+-- rows older than the idempotency window are pruned by the scheduler
+create unique index idempotency_key_lookup on idempotency_key(creator_user_id, endpoint, idempotency_key);
+create index idempotency_key_creation_time on idempotency_key(creation_time);
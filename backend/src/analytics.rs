@@ -0,0 +1,227 @@
+// Numbers for the debrief after a game: how concentrated the market was and how firms played.
+// Everything is derived from the most recent submission per firm and year, and the demands.
+use super::db_types::*;
+use super::market;
+use std::collections::BTreeMap;
+
+// one firm in one year
+pub struct FirmYear {
+    pub firm: Firm,
+    pub quantity: i64,
+    // of the year's total quantity, between 0 and 1
+    pub share: f64,
+    // what would have earned the most, knowing what everyone else did
+    pub best_response: f64,
+}
+
+pub struct Year {
+    pub year: i64,
+    pub total_quantity: i64,
+    pub price: f64,
+    pub nash_price: f64,
+    // what all firms together would produce at the Nash equilibrium, and as a cartel
+    pub nash_quantity: f64,
+    pub monopoly_quantity: f64,
+    // Herfindahl-Hirschman index from 0 to 10000, unknown if nobody produced anything
+    pub hhi: Option<f64>,
+    pub firms: Vec<FirmYear>,
+}
+
+// how one firm played over the whole game
+pub struct FirmSummary {
+    pub firm: Firm,
+    pub mean_quantity: f64,
+    // low when the firm stuck to the same quantity every year
+    pub quantity_std_dev: f64,
+    // how far the firm was from its best response, on average
+    pub mean_deviation: f64,
+}
+
+// Every year up to and including last_year.
+// A year's firms are everyone handed a demand for it and everyone who sold in it,
+// so the benchmarks are worked out for the same firms the shares are.
+// Years nobody has a demand for yet are left out, their incentives aren't known.
+pub fn years(
+    tournament: &Tournament,
+    submissions: &[TournamentSubmission],
+    demands: &[TournamentYearDemand],
+    last_year: i64,
+) -> Vec<Year> {
+    let mut quantities: BTreeMap<i64, BTreeMap<Firm, i64>> = BTreeMap::new();
+    let mut incentives: BTreeMap<i64, i64> = BTreeMap::new();
+    for d in demands.iter().filter(|d| d.year <= last_year) {
+        *incentives.entry(d.year).or_insert(0) += d.demand;
+        // a firm that hasn't sold anything yet is still in the market
+        quantities
+            .entry(d.year)
+            .or_default()
+            .entry(d.firm())
+            .or_insert(0);
+    }
+    for s in submissions.iter().filter(|s| s.year <= last_year) {
+        *quantities
+            .entry(s.year)
+            .or_default()
+            .entry(s.firm())
+            .or_insert(0) += s.amount;
+    }
+
+    let mut years = vec![];
+    for (year, quantities) in quantities {
+        let total_incentive = match incentives.get(&year) {
+            Some(&total_incentive) => total_incentive,
+            None => continue,
+        };
+        let firms = quantities.len() as i64;
+        let total_quantity: i64 = quantities.values().sum();
+
+        let firms_in_year: Vec<FirmYear> = quantities
+            .iter()
+            .map(|(&firm, &quantity)| FirmYear {
+                firm,
+                quantity,
+                share: match total_quantity {
+                    0 => 0.0,
+                    total => quantity as f64 / total as f64,
+                },
+                best_response: market::best_response(
+                    tournament,
+                    total_incentive,
+                    (total_quantity - quantity) as f64,
+                ),
+            })
+            .collect();
+
+        let nash = market::cournot(tournament, total_incentive, firms);
+        years.push(Year {
+            year,
+            total_quantity,
+            price: market::price(tournament, total_incentive, total_quantity),
+            nash_price: nash.price,
+            nash_quantity: nash.total_quantity,
+            monopoly_quantity: market::monopoly(tournament, total_incentive, firms).total_quantity,
+            hhi: match total_quantity {
+                0 => None,
+                _ => Some(
+                    firms_in_year
+                        .iter()
+                        .map(|f| (f.share * 100.0).powi(2))
+                        .sum(),
+                ),
+            },
+            firms: firms_in_year,
+        });
+    }
+    years
+}
+
+pub fn summaries(years: &[Year]) -> Vec<FirmSummary> {
+    let mut by_firm: BTreeMap<Firm, Vec<&FirmYear>> = BTreeMap::new();
+    for f in years.iter().flat_map(|y| y.firms.iter()) {
+        by_firm.entry(f.firm).or_default().push(f);
+    }
+
+    by_firm
+        .into_iter()
+        .map(|(firm, played)| {
+            let n = played.len() as f64;
+            let mean_quantity = played.iter().map(|f| f.quantity as f64).sum::<f64>() / n;
+            let variance = played
+                .iter()
+                .map(|f| (f.quantity as f64 - mean_quantity).powi(2))
+                .sum::<f64>()
+                / n;
+            FirmSummary {
+                firm,
+                mean_quantity,
+                quantity_std_dev: variance.sqrt(),
+                mean_deviation: played
+                    .iter()
+                    .map(|f| (f.quantity as f64 - f.best_response).abs())
+                    .sum::<f64>()
+                    / n,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{demand, submission, tournament};
+
+    #[test]
+    fn shares_and_concentration() {
+        let submissions = [
+            submission(2, 0, 30),
+            submission(3, 0, 10),
+            submission(2, 1, 20),
+            submission(3, 1, 20),
+            // still being played
+            submission(2, 2, 50),
+        ];
+        let demands = [
+            demand(2, 0, 0),
+            demand(3, 0, 0),
+            demand(2, 1, 0),
+            demand(3, 1, 0),
+        ];
+        let years = years(&tournament(), &submissions, &demands, 1);
+        assert_eq!(years.len(), 2);
+
+        // price is 100 - Q, so 60 in year 0 against 40 at the Nash equilibrium
+        assert_eq!(years[0].price, 60.0);
+        assert_eq!(years[0].nash_price, 40.0);
+        assert_eq!(years[0].nash_quantity, 60.0);
+        assert_eq!(years[0].monopoly_quantity, 45.0);
+        assert_eq!(years[0].hhi, Some(75.0 * 75.0 + 25.0 * 25.0));
+        assert_eq!(years[0].firms[0].share, 0.75);
+        // with 10 from the other firm, 40 would have been best
+        assert_eq!(years[0].firms[0].best_response, 40.0);
+        assert_eq!(years[1].hhi, Some(5000.0));
+
+        let summaries = summaries(&years);
+        assert_eq!(summaries[0].firm, Firm::User(2));
+        assert_eq!(summaries[0].mean_quantity, 25.0);
+        assert_eq!(summaries[0].quantity_std_dev, 5.0);
+        // 10 short in year 0, 15 short in year 1
+        assert_eq!(summaries[0].mean_deviation, 12.5);
+    }
+
+    #[test]
+    fn empty_years_have_no_concentration() {
+        let submissions = [submission(2, 0, 0), submission(3, 0, 0)];
+        let demands = [demand(2, 0, 0), demand(3, 0, 0)];
+        let years = years(&tournament(), &submissions, &demands, 0);
+        assert_eq!(years[0].hhi, None);
+        assert_eq!(years[0].firms[0].share, 0.0);
+    }
+
+    #[test]
+    fn open_years_count_every_firm_in_the_market() {
+        // player 3 hasn't submitted for year 1 yet, and year 2 hasn't been handed out
+        let submissions = [
+            submission(2, 0, 30),
+            submission(3, 0, 10),
+            submission(2, 1, 20),
+            submission(2, 2, 20),
+        ];
+        let demands = [
+            demand(2, 0, 0),
+            demand(3, 0, 0),
+            demand(2, 1, 6),
+            demand(3, 1, 0),
+        ];
+        let years = years(&tournament(), &submissions, &demands, 2);
+        assert_eq!(years.len(), 2);
+
+        // the benchmarks are for the same two firms the shares are
+        let year = &years[1];
+        assert_eq!(year.firms.len(), 2);
+        assert_eq!(year.firms[1].firm, Firm::User(3));
+        assert_eq!((year.firms[1].quantity, year.firms[1].share), (0, 0.0));
+        // price is 106 - Q with the incentive, so 32 each at the Nash equilibrium
+        assert_eq!(year.nash_quantity, 64.0);
+        assert_eq!(year.monopoly_quantity, 48.0);
+    }
+}
@@ -1,15 +1,28 @@
 use super::handlers;
+use super::metrics;
 use super::response;
 use super::response::AppError;
 use super::utils;
+use super::Auth;
 use super::Config;
 use super::Db;
 use super::SERVICE_NAME;
-use auth_service_api::client::AuthService;
 use std::convert::Infallible;
 use std::future::Future;
+use std::pin::Pin;
+use std::time::Instant;
+use tracing::Instrument;
+use warp::http::HeaderValue;
 use warp::http::StatusCode;
 use warp::Filter;
+use warp::Reply;
+
+// accepted from clients, and returned on every response
+static REQUEST_ID_HEADER: &str = "x-request-id";
+
+// What a route extracts once it has matched the request and decoded its body.
+// It's only run once we know the request id, so everything it logs can be traced back.
+type Pending = Pin<Box<dyn Future<Output = Result<warp::reply::Response, AppError>> + Send>>;
 
 /// Helper to combine the multiple filters together with Filter::or, possibly boxing the types in
 /// the process. This greatly helps the build times for `ipfs-http`.
@@ -17,7 +30,7 @@ use warp::Filter;
 macro_rules! combine {
   ($x:expr, $($y:expr),+) => {{
       let filter = ($x).boxed();
-      $( let filter = (filter.or($y)).boxed(); )+
+      $( let filter = (filter.or($y)).unify().boxed(); )+
       filter
   }}
 }
@@ -26,11 +39,14 @@ macro_rules! combine {
 pub fn api(
     config: Config,
     db: Db,
-    auth_service: AuthService,
+    auth_service: Auth,
 ) -> impl Filter<Extract = impl warp::Reply, Error = Infallible> + Clone {
     // public API
-    combine!(
+    let routes = combine!(
         api_info(),
+        api_health_live(),
+        api_health_ready(db.clone(), auth_service.clone()),
+        api_metrics(db.clone()),
         adapter(
             config.clone(),
             db.clone(),
@@ -45,6 +61,13 @@ pub fn api(
             warp::path!("public" / "tournament_data" / "new"),
             handlers::tournament_data_new,
         ),
+        adapter(
+            config.clone(),
+            db.clone(),
+            auth_service.clone(),
+            warp::path!("public" / "tournament_state" / "new"),
+            handlers::tournament_state_new,
+        ),
         adapter(
             config.clone(),
             db.clone(),
@@ -59,6 +82,48 @@ pub fn api(
             warp::path!("public" / "tournament_membership" / "new"),
             handlers::tournament_membership_new,
         ),
+        adapter(
+            config.clone(),
+            db.clone(),
+            auth_service.clone(),
+            warp::path!("public" / "tournament_role" / "new"),
+            handlers::tournament_role_new,
+        ),
+        adapter(
+            config.clone(),
+            db.clone(),
+            auth_service.clone(),
+            warp::path!("public" / "tournament_membership_approval" / "new"),
+            handlers::tournament_membership_approval_new,
+        ),
+        adapter(
+            config.clone(),
+            db.clone(),
+            auth_service.clone(),
+            warp::path!("public" / "tournament_membership_action" / "new"),
+            handlers::tournament_membership_action_new,
+        ),
+        adapter(
+            config.clone(),
+            db.clone(),
+            auth_service.clone(),
+            warp::path!("public" / "tournament_invite_code" / "new"),
+            handlers::tournament_invite_code_new,
+        ),
+        adapter(
+            config.clone(),
+            db.clone(),
+            auth_service.clone(),
+            warp::path!("public" / "tournament_team" / "new"),
+            handlers::tournament_team_new,
+        ),
+        adapter(
+            config.clone(),
+            db.clone(),
+            auth_service.clone(),
+            warp::path!("public" / "tournament_bot" / "new"),
+            handlers::tournament_bot_new,
+        ),
         adapter(
             config.clone(),
             db.clone(),
@@ -66,6 +131,13 @@ pub fn api(
             warp::path!("public" / "tournament_submission" / "new"),
             handlers::tournament_submission_new,
         ),
+        adapter(
+            config.clone(),
+            db.clone(),
+            auth_service.clone(),
+            warp::path!("public" / "tournament_final_result" / "new"),
+            handlers::tournament_final_result_new,
+        ),
         adapter(
             config.clone(),
             db.clone(),
@@ -94,25 +166,188 @@ pub fn api(
             warp::path!("public" / "tournament_membership" / "view"),
             handlers::tournament_membership_view,
         ),
+        adapter(
+            config.clone(),
+            db.clone(),
+            auth_service.clone(),
+            warp::path!("public" / "tournament_role" / "view"),
+            handlers::tournament_role_view,
+        ),
+        adapter(
+            config.clone(),
+            db.clone(),
+            auth_service.clone(),
+            warp::path!("public" / "tournament_invite_code" / "view"),
+            handlers::tournament_invite_code_view,
+        ),
+        adapter(
+            config.clone(),
+            db.clone(),
+            auth_service.clone(),
+            warp::path!("public" / "tournament_team" / "view"),
+            handlers::tournament_team_view,
+        ),
+        adapter(
+            config.clone(),
+            db.clone(),
+            auth_service.clone(),
+            warp::path!("public" / "tournament_bot" / "view"),
+            handlers::tournament_bot_view,
+        ),
+        adapter(
+            config.clone(),
+            db.clone(),
+            auth_service.clone(),
+            warp::path!("public" / "tournament_pseudonym" / "view"),
+            handlers::tournament_pseudonym_view,
+        ),
         adapter(
             config.clone(),
             db.clone(),
             auth_service.clone(),
             warp::path!("public" / "tournament_submission" / "view"),
             handlers::tournament_submission_view,
+        ),
+        adapter(
+            config.clone(),
+            db.clone(),
+            auth_service.clone(),
+            warp::path!("public" / "tournament_final_result" / "view"),
+            handlers::tournament_final_result_view,
+        ),
+        adapter(
+            config.clone(),
+            db.clone(),
+            auth_service.clone(),
+            warp::path!("public" / "tournament_benchmarks" / "view"),
+            handlers::tournament_benchmarks_view,
+        ),
+        adapter(
+            config.clone(),
+            db.clone(),
+            auth_service.clone(),
+            warp::path!("public" / "tournament_analytics" / "view"),
+            handlers::tournament_analytics_view,
+        ),
+        adapter(
+            config.clone(),
+            db.clone(),
+            auth_service.clone(),
+            warp::path!("public" / "tournament_collusion" / "view"),
+            handlers::tournament_collusion_view,
+        ),
+        adapter(
+            config.clone(),
+            db.clone(),
+            auth_service.clone(),
+            warp::path!("public" / "tournament_pause" / "view"),
+            handlers::tournament_pause_view,
+        )
+    );
+
+    // the request id is picked here, outside of every route, so that even requests
+    // that never reach a handler get one
+    request_id()
+        .and(warp::path::full())
+        .and(
+            routes
+                .map(Ok)
+                .or_else(|err| async move { Ok::<_, Infallible>((Err(err),)) }),
         )
-    )
-    .recover(handle_rejection)
+        .then(
+            |request_id: String,
+             path: warp::path::FullPath,
+             pending: Result<Pending, warp::Rejection>| async move {
+                let span = tracing::info_span!("request", %request_id, route = path.as_str());
+                let result = match pending {
+                    Ok(pending) => pending.instrument(span).await.map_err(|e| {
+                        metrics::record_error(&e);
+                        (app_error_status(&e), e)
+                    }),
+                    Err(err) => Err(handle_rejection(err)),
+                };
+
+                let mut resp = match result {
+                    Ok(resp) => resp,
+                    // lets support find the logs for a failed request
+                    Err((code, error)) => warp::reply::with_status(
+                        warp::reply::json(&response::Error {
+                            error,
+                            request_id: request_id.clone(),
+                        }),
+                        code,
+                    )
+                    .into_response(),
+                };
+                if let Ok(id) = HeaderValue::from_str(&request_id) {
+                    resp.headers_mut().insert(REQUEST_ID_HEADER, id);
+                }
+                resp
+            },
+        )
+}
+
+// for routes that already have their reply at hand
+fn ready(reply: impl warp::Reply + 'static) -> Pending {
+    let resp = reply.into_response();
+    Box::pin(async move { Ok(resp) })
 }
 
-fn api_info() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+fn api_info() -> impl Filter<Extract = (Pending,), Error = warp::Rejection> + Clone {
     let info = response::Info {
         service: SERVICE_NAME.to_owned(),
         version_major: 1,
         version_minor: 0,
         version_rev: 0,
     };
-    warp::path!("public" / "info").map(move || warp::reply::json(&info))
+    warp::path!("public" / "info").map(move || ready(warp::reply::json(&info)))
+}
+
+// the process is up and serving requests
+fn api_health_live() -> impl Filter<Extract = (Pending,), Error = warp::Rejection> + Clone {
+    warp::path!("health" / "live").map(|| ready(warp::reply::json(&"OK")))
+}
+
+// we can talk to everything we depend on
+fn api_health_ready(
+    db: Db,
+    auth_service: Auth,
+) -> impl Filter<Extract = (Pending,), Error = warp::Rejection> + Clone {
+    warp::path!("health" / "ready").map(move || {
+        let db = db.clone();
+        let auth_service = auth_service.clone();
+        let pending: Pending = Box::pin(async move {
+            let readiness = response::Readiness {
+                database: db.ping().await,
+                auth_service: auth_service.is_reachable().await,
+            };
+            let code = if readiness.database && readiness.auth_service {
+                StatusCode::OK
+            } else {
+                StatusCode::SERVICE_UNAVAILABLE
+            };
+            Ok(warp::reply::with_status(warp::reply::json(&readiness), code).into_response())
+        });
+        pending
+    })
+}
+
+// prometheus scrape endpoint
+fn api_metrics(db: Db) -> impl Filter<Extract = (Pending,), Error = warp::Rejection> + Clone {
+    warp::path!("metrics").map(move || {
+        let db = db.clone();
+        let pending: Pending = Box::pin(async move {
+            // don't fail the whole scrape just because the database is down
+            let active_tournaments = handlers::active_tournament_count(&db).await.unwrap_or(-1);
+            Ok(warp::reply::with_header(
+                metrics::render(active_tournaments),
+                "Content-Type",
+                "text/plain; version=0.0.4",
+            )
+            .into_response())
+        });
+        pending
+    })
 }
 
 // this function adapts a handler function to a warp filter
@@ -120,14 +355,14 @@ fn api_info() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Reject
 fn adapter<PropsType, ResponseType, F>(
     config: Config,
     db: Db,
-    auth_service: AuthService,
+    auth_service: Auth,
     filter: impl Filter<Extract = (), Error = warp::Rejection> + Clone,
-    handler: fn(Config, Db, AuthService, PropsType) -> F,
-) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+    handler: fn(Config, Db, Auth, PropsType) -> F,
+) -> impl Filter<Extract = (Pending,), Error = warp::Rejection> + Clone
 where
-    F: Future<Output = Result<ResponseType, AppError>> + Send,
-    PropsType: Send + serde::de::DeserializeOwned,
-    ResponseType: Send + serde::ser::Serialize,
+    F: Future<Output = Result<ResponseType, AppError>> + Send + 'static,
+    PropsType: Send + serde::de::DeserializeOwned + 'static,
+    ResponseType: Send + serde::ser::Serialize + 'static,
 {
     // lets you pass in an arbitrary parameter
     fn with<T: Clone + Send>(t: T) -> impl Filter<Extract = (T,), Error = Infallible> + Clone {
@@ -135,21 +370,50 @@ where
     }
 
     filter
+        .and(warp::path::full())
         .and(with(config))
         .and(with(db))
         .and(with(auth_service))
         .and(warp::body::json())
-        .and_then(move |config, db, auth_service, props| async move {
-            handler(config, db, auth_service, props)
-                .await
-                .map_err(app_error)
-        })
-        .map(|x| warp::reply::json(&x))
+        .map(
+            move |path: warp::path::FullPath, config, db, auth_service, props| {
+                let pending: Pending = Box::pin(async move {
+                    let start = Instant::now();
+                    let result = handler(config, db, auth_service, props).await;
+                    let status = match &result {
+                        Ok(_) => StatusCode::OK,
+                        Err(e) => app_error_status(e),
+                    };
+                    metrics::record_request(path.as_str(), status.as_u16(), start.elapsed());
+                    match result {
+                        Ok(x) => Ok(warp::reply::json(&x).into_response()),
+                        Err(e) => {
+                            tracing::warn!(error = e.as_ref(), "request failed");
+                            Err(e)
+                        }
+                    }
+                });
+                pending
+            },
+        )
 }
 
-// This function receives a `Rejection` and tries to return a custom
-// value, otherwise simply passes the rejection along.
-async fn handle_rejection(err: warp::Rejection) -> Result<impl warp::Reply, Infallible> {
+// uses the caller's request id if it sent a sensible one, otherwise makes one up
+fn request_id() -> impl Filter<Extract = (String,), Error = Infallible> + Clone {
+    warp::header::headers_cloned().map(|headers: warp::http::HeaderMap| {
+        headers
+            .get(REQUEST_ID_HEADER)
+            .and_then(|id| id.to_str().ok())
+            .filter(|id| {
+                !id.is_empty() && id.len() <= 128 && id.chars().all(|c| c.is_ascii_graphic())
+            })
+            .map(|id| id.to_owned())
+            .unwrap_or_else(utils::random_string)
+    })
+}
+
+// Turns a request that never made it to a handler into an error for the client.
+fn handle_rejection(err: warp::Rejection) -> (StatusCode, AppError) {
     let code;
     let message;
 
@@ -165,9 +429,6 @@ async fn handle_rejection(err: warp::Rejection) -> Result<impl warp::Reply, Infa
     } else if err.find::<warp::reject::MethodNotAllowed>().is_some() {
         code = StatusCode::METHOD_NOT_ALLOWED;
         message = AppError::MethodNotAllowed;
-    } else if let Some(AppErrorRejection(app_error)) = err.find() {
-        code = StatusCode::BAD_REQUEST;
-        message = app_error.clone();
     } else {
         // We should have expected this... Just log and say its a 500
         utils::log(utils::Event {
@@ -179,18 +440,18 @@ async fn handle_rejection(err: warp::Rejection) -> Result<impl warp::Reply, Infa
         message = AppError::Unknown;
     }
 
-    Ok(warp::reply::with_status(
-        warp::reply::json(&message),
-        code,
-    ))
-}
+    // unmatched routes are mostly scanners and typos, not something we did wrong
+    if !err.is_not_found() {
+        metrics::record_error(&message);
+    }
 
-// This type represents errors that we can generate
-// These will be automatically converted to a proper string later
-#[derive(Debug)]
-pub struct AppErrorRejection(pub AppError);
-impl warp::reject::Reject for AppErrorRejection {}
+    (code, message)
+}
 
-fn app_error(app_error: AppError) -> warp::reject::Rejection {
-    warp::reject::custom(AppErrorRejection(app_error))
+fn app_error_status(app_error: &AppError) -> StatusCode {
+    match app_error {
+        AppError::AuthServiceUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+        AppError::DatabaseUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+        _ => StatusCode::BAD_REQUEST,
+    }
 }
@@ -0,0 +1,390 @@
+use super::auth_provider::AuthProvider;
+use super::response::AppError;
+use super::utils;
+use async_trait::async_trait;
+use auth_service_api::client::AuthService;
+use auth_service_api::response::AuthError;
+use auth_service_api::response::User;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+#[derive(Clone, Debug)]
+pub struct AuthClientConfig {
+    // how long a single call to the auth service may take
+    pub timeout: Duration,
+    // how many times a call that failed with a network error is retried
+    pub max_retries: u32,
+    // delay before the first retry, doubled on every subsequent retry
+    pub retry_backoff: Duration,
+    // consecutive failed calls before the circuit opens
+    pub failure_threshold: u32,
+    // how long the circuit stays open before a trial call is let through
+    pub reset_timeout: Duration,
+}
+
+#[derive(Debug)]
+enum CircuitState {
+    // calls go through, counting consecutive failures
+    Closed { failures: u32 },
+    // calls fail fast until the deadline
+    Open { until: Instant },
+    // a single trial call is in flight, everyone else fails fast
+    // if the trial never finishes, e.g. because its request was dropped,
+    // another one is let through once the reset timeout has passed again
+    HalfOpen { since: Instant },
+}
+
+// wraps the auth service client with timeouts, retries and a circuit breaker
+// so that a slow or dead auth service doesn't hang every request
+#[derive(Clone)]
+pub struct AuthClient {
+    auth_service: AuthService,
+    config: AuthClientConfig,
+    circuit: Arc<Mutex<CircuitState>>,
+}
+
+fn report_auth_err(e: AuthError) -> AppError {
+    match e {
+        AuthError::ApiKeyNonexistent => AppError::Unauthorized,
+        AuthError::ApiKeyUnauthorized => AppError::Unauthorized,
+        c => {
+            let ae = match c {
+                AuthError::InternalServerError => AppError::InternalServerError,
+                AuthError::MethodNotAllowed => AppError::InternalServerError,
+                AuthError::BadRequest => AppError::InternalServerError,
+                AuthError::Network => AppError::AuthServiceUnavailable,
+                _ => AppError::Unknown,
+            };
+
+            utils::log(utils::Event {
+                msg: ae.as_ref().to_owned(),
+                source: Some(format!("auth service: {}", c.as_ref())),
+                severity: utils::SeverityKind::Error,
+            });
+
+            ae
+        }
+    }
+}
+
+impl AuthClient {
+    pub fn new(auth_service: AuthService, config: AuthClientConfig) -> AuthClient {
+        AuthClient {
+            auth_service,
+            config,
+            circuit: Arc::new(Mutex::new(CircuitState::Closed { failures: 0 })),
+        }
+    }
+
+    #[tracing::instrument(name = "auth_service", skip_all)]
+    pub async fn get_user_by_api_key_if_valid(&self, api_key: String) -> Result<User, AppError> {
+        self.acquire()?;
+
+        let mut backoff = self.config.retry_backoff;
+        let mut attempt = 0;
+        let result = loop {
+            match tokio::time::timeout(
+                self.config.timeout,
+                self.auth_service
+                    .get_user_by_api_key_if_valid(api_key.clone()),
+            )
+            .await
+            {
+                // only network errors are worth retrying
+                Ok(Err(AuthError::Network)) if attempt < self.config.max_retries => {
+                    attempt += 1;
+                    tracing::warn!(attempt, "auth service unreachable, retrying");
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                }
+                Ok(v) => break Some(v),
+                // a timed out call is not retried, since it already took the full timeout
+                Err(_) => break None,
+            }
+        };
+
+        match result {
+            Some(Err(AuthError::Network)) | None => {
+                self.record_failure();
+                utils::log(utils::Event {
+                    msg: AppError::AuthServiceUnavailable.as_ref().to_owned(),
+                    source: Some(format!(
+                        "auth service: no response after {} attempts",
+                        attempt + 1
+                    )),
+                    severity: utils::SeverityKind::Error,
+                });
+                Err(AppError::AuthServiceUnavailable)
+            }
+            // the auth service answered, even if it was an error
+            Some(v) => {
+                self.record_success();
+                v.map_err(report_auth_err)
+            }
+        }
+    }
+
+    // the auth service is reachable if it gives us any answer about a made up api key
+    // asked once and around the circuit, so health checks neither trip it nor close it
+    pub async fn is_reachable(&self) -> bool {
+        let result = tokio::time::timeout(
+            self.config.timeout,
+            self.auth_service
+                .get_user_by_api_key_if_valid(utils::random_string()),
+        )
+        .await;
+        matches!(
+            result,
+            Ok(Ok(_) | Err(AuthError::ApiKeyNonexistent | AuthError::ApiKeyUnauthorized))
+        )
+    }
+
+    // checks whether we're allowed to make a call right now
+    fn acquire(&self) -> Result<(), AppError> {
+        let mut circuit = self.circuit.lock().unwrap();
+        match *circuit {
+            CircuitState::Closed { .. } => Ok(()),
+            CircuitState::Open { until } if Instant::now() >= until => {
+                *circuit = CircuitState::HalfOpen {
+                    since: Instant::now(),
+                };
+                Ok(())
+            }
+            CircuitState::HalfOpen { since }
+                if Instant::now() >= since + self.config.reset_timeout =>
+            {
+                *circuit = CircuitState::HalfOpen {
+                    since: Instant::now(),
+                };
+                Ok(())
+            }
+            _ => Err(AppError::AuthServiceUnavailable),
+        }
+    }
+
+    fn record_success(&self) {
+        *self.circuit.lock().unwrap() = CircuitState::Closed { failures: 0 };
+    }
+
+    fn record_failure(&self) {
+        let mut circuit = self.circuit.lock().unwrap();
+        let failures = match *circuit {
+            CircuitState::Closed { failures } => failures + 1,
+            // the trial call failed, so reopen immediately
+            _ => self.config.failure_threshold,
+        };
+
+        *circuit = if failures >= self.config.failure_threshold {
+            utils::log(utils::Event {
+                msg: "auth service circuit opened".to_owned(),
+                source: Some(format!("{} consecutive failures", failures)),
+                severity: utils::SeverityKind::Warning,
+            });
+            CircuitState::Open {
+                until: Instant::now() + self.config.reset_timeout,
+            }
+        } else {
+            CircuitState::Closed { failures }
+        };
+    }
+}
+
+#[async_trait]
+impl AuthProvider for AuthClient {
+    async fn get_user_by_api_key_if_valid(&self, api_key: String) -> Result<User, AppError> {
+        AuthClient::get_user_by_api_key_if_valid(self, api_key).await
+    }
+
+    async fn is_reachable(&self) -> bool {
+        AuthClient::is_reachable(self).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{api_key, auth_client_for, stub_auth_service};
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // a port that accepts connections and immediately drops them, counting attempts
+    async fn refusing_service() -> (SocketAddr, Arc<AtomicUsize>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                counter.fetch_add(1, Ordering::SeqCst);
+                drop(socket);
+            }
+        });
+        (addr, hits)
+    }
+
+    #[tokio::test]
+    async fn valid_and_invalid_keys() {
+        let stub = stub_auth_service().await;
+        let client = auth_client_for(stub.addr).await;
+
+        let user = client
+            .get_user_by_api_key_if_valid(api_key(1))
+            .await
+            .unwrap();
+        assert_eq!(user.user_id, 1);
+
+        // rejected keys are answers, not failures, so they never open the circuit
+        for _ in 0..5 {
+            let err = client
+                .get_user_by_api_key_if_valid("bogus".to_owned())
+                .await
+                .unwrap_err();
+            assert!(matches!(err, AppError::Unauthorized));
+        }
+        assert_eq!(stub.hits.load(Ordering::SeqCst), 6);
+    }
+
+    #[tokio::test]
+    async fn network_errors_are_retried_then_circuit_opens() {
+        let (addr, hits) = refusing_service().await;
+        let client = auth_client_for(addr).await;
+
+        for _ in 0..2 {
+            let err = client
+                .get_user_by_api_key_if_valid(api_key(1))
+                .await
+                .unwrap_err();
+            assert!(matches!(err, AppError::AuthServiceUnavailable));
+        }
+        // one initial attempt plus two retries for each call
+        assert_eq!(hits.load(Ordering::SeqCst), 6);
+
+        // circuit is now open, so we fail without touching the network
+        let err = client
+            .get_user_by_api_key_if_valid(api_key(1))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AppError::AuthServiceUnavailable));
+        assert_eq!(hits.load(Ordering::SeqCst), 6);
+    }
+
+    #[tokio::test]
+    async fn readiness_probes_leave_the_circuit_alone() {
+        // probing a dead service doesn't open the circuit for real calls
+        let (addr, hits) = refusing_service().await;
+        let client = auth_client_for(addr).await;
+        for _ in 0..3 {
+            assert!(!client.is_reachable().await);
+        }
+        // one attempt each, without retries
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+        let err = client
+            .get_user_by_api_key_if_valid(api_key(1))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AppError::AuthServiceUnavailable));
+        assert_eq!(hits.load(Ordering::SeqCst), 6);
+
+        // and probing a live one doesn't close an open circuit
+        let stub = stub_auth_service().await;
+        let client = auth_client_for(stub.addr).await;
+        stub.slow.store(true, Ordering::SeqCst);
+        for _ in 0..2 {
+            assert!(client
+                .get_user_by_api_key_if_valid(api_key(1))
+                .await
+                .is_err());
+        }
+        stub.slow.store(false, Ordering::SeqCst);
+        assert!(client.is_reachable().await);
+        assert!(client
+            .get_user_by_api_key_if_valid(api_key(1))
+            .await
+            .is_err());
+        assert_eq!(stub.hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn slow_service_times_out_and_recovers() {
+        let stub = stub_auth_service().await;
+        let client = auth_client_for(stub.addr).await;
+
+        stub.slow.store(true, Ordering::SeqCst);
+        for _ in 0..2 {
+            let start = Instant::now();
+            let err = client
+                .get_user_by_api_key_if_valid(api_key(1))
+                .await
+                .unwrap_err();
+            assert!(matches!(err, AppError::AuthServiceUnavailable));
+            assert!(start.elapsed() < Duration::from_secs(2));
+        }
+        assert_eq!(stub.hits.load(Ordering::SeqCst), 2);
+
+        // open: fail fast
+        stub.slow.store(false, Ordering::SeqCst);
+        assert!(client
+            .get_user_by_api_key_if_valid(api_key(1))
+            .await
+            .is_err());
+        assert_eq!(stub.hits.load(Ordering::SeqCst), 2);
+
+        // after the reset timeout the trial call goes through and closes the circuit
+        tokio::time::sleep(Duration::from_millis(350)).await;
+        assert!(client
+            .get_user_by_api_key_if_valid(api_key(1))
+            .await
+            .is_ok());
+        assert!(client
+            .get_user_by_api_key_if_valid(api_key(1))
+            .await
+            .is_ok());
+        assert_eq!(stub.hits.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn abandoned_trial_call_does_not_wedge_the_circuit() {
+        let stub = stub_auth_service().await;
+        let client = auth_client_for(stub.addr).await;
+
+        // open the circuit
+        stub.slow.store(true, Ordering::SeqCst);
+        for _ in 0..2 {
+            assert!(client
+                .get_user_by_api_key_if_valid(api_key(1))
+                .await
+                .is_err());
+        }
+
+        // the trial call is cancelled before the auth service answers
+        tokio::time::sleep(Duration::from_millis(350)).await;
+        assert!(tokio::time::timeout(
+            Duration::from_millis(50),
+            client.get_user_by_api_key_if_valid(api_key(1)),
+        )
+        .await
+        .is_err());
+
+        // while it would still be in flight, everyone else fails fast
+        let hits = stub.hits.load(Ordering::SeqCst);
+        assert!(client
+            .get_user_by_api_key_if_valid(api_key(1))
+            .await
+            .is_err());
+        assert_eq!(stub.hits.load(Ordering::SeqCst), hits);
+
+        // but after another reset timeout a new trial gets through and closes the circuit
+        stub.slow.store(false, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(350)).await;
+        assert!(client
+            .get_user_by_api_key_if_valid(api_key(1))
+            .await
+            .is_ok());
+        assert!(client
+            .get_user_by_api_key_if_valid(api_key(1))
+            .await
+            .is_ok());
+    }
+}
@@ -0,0 +1,14 @@
+use super::response::AppError;
+use async_trait::async_trait;
+use auth_service_api::response::User;
+
+// Something that can tell us which user an api key belongs to.
+// In production this is the auth service, but for local development
+// and CI we can use a fixed set of keys instead.
+#[async_trait]
+pub trait AuthProvider: Send + Sync {
+    async fn get_user_by_api_key_if_valid(&self, api_key: String) -> Result<User, AppError>;
+
+    // whether we can currently authenticate anyone at all
+    async fn is_reachable(&self) -> bool;
+}
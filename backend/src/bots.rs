@@ -0,0 +1,140 @@
+// How computer players decide what to produce.
+// Bots only know what a student could find out: the demand curve, this year's incentives,
+// how many firms sell and what everyone produced in earlier years.
+use super::db_types::*;
+use super::market;
+use super::utils;
+
+// the market a bot sells into this year
+pub struct Market<'a> {
+    tournament: &'a Tournament,
+    total_incentive: i64,
+    // every firm selling this year, the bot included
+    firms: i64,
+}
+
+impl<'a> Market<'a> {
+    pub fn new(tournament: &'a Tournament, total_incentive: i64, firms: usize) -> Market<'a> {
+        Market {
+            tournament,
+            total_incentive,
+            firms: firms.max(1) as i64,
+        }
+    }
+
+    fn best_response(&self, others: f64) -> f64 {
+        market::best_response(self.tournament, self.total_incentive, others)
+    }
+
+    fn nash(&self) -> f64 {
+        market::cournot(self.tournament, self.total_incentive, self.firms).quantity
+    }
+
+    // each firm's share of what a monopolist would make, the most a cartel can earn together
+    fn cartel(&self) -> f64 {
+        market::monopoly(self.tournament, self.total_incentive, self.firms).quantity
+    }
+}
+
+// how much a bot submits this year
+// others holds what all other firms produced together in each earlier year, oldest first
+pub fn decide(
+    strategy: BotStrategyKind,
+    quantity: Option<i64>,
+    market: &Market,
+    others: &[f64],
+) -> i64 {
+    let amount = match strategy {
+        // anything between nothing and twice the equilibrium, so it's wrong in both directions
+        BotStrategyKind::Random => {
+            let max = (2.0 * market.nash()).round() as i64;
+            utils::random_number(0, max + 1) as f64
+        }
+        BotStrategyKind::FixedQuantity => quantity.unwrap_or(0) as f64,
+        // assumes everyone does what they did last year
+        BotStrategyKind::BestResponse => match others.last() {
+            Some(&last) => market.best_response(last),
+            None => market.nash(),
+        },
+        BotStrategyKind::Nash => market.nash(),
+        // keeps to its cartel share as long as everyone else did last year, punishes otherwise
+        BotStrategyKind::TitForTat => {
+            let cartel = market.cartel();
+            let allowed = cartel.ceil() * (market.firms - 1) as f64;
+            match others.last() {
+                Some(&last) if last > allowed => market.nash(),
+                _ => cartel,
+            }
+        }
+        // assumes everyone does what they did on average so far
+        BotStrategyKind::Adaptive => match others.len() {
+            0 => market.nash(),
+            n => market.best_response(others.iter().sum::<f64>() / n as f64),
+        },
+    };
+    amount.round().max(0.0) as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::tournament;
+
+    #[test]
+    fn strategies_follow_the_textbook() {
+        // price is 100 - Q, cost 10, two firms
+        let t = tournament();
+        let market = Market::new(&t, 0, 2);
+        assert_eq!(decide(BotStrategyKind::Nash, None, &market, &[]), 30);
+        assert_eq!(decide(BotStrategyKind::TitForTat, None, &market, &[]), 23);
+        assert_eq!(
+            decide(BotStrategyKind::FixedQuantity, Some(7), &market, &[]),
+            7
+        );
+        assert_eq!(
+            decide(BotStrategyKind::BestResponse, None, &market, &[10.0, 50.0]),
+            20
+        );
+        assert_eq!(
+            decide(BotStrategyKind::Adaptive, None, &market, &[10.0, 50.0]),
+            30
+        );
+        let random = decide(BotStrategyKind::Random, None, &market, &[]);
+        assert!((0..=60).contains(&random));
+
+        // incentives shift the curve up
+        let market = Market::new(&t, 30, 2);
+        assert_eq!(decide(BotStrategyKind::Nash, None, &market, &[]), 40);
+    }
+
+    #[test]
+    fn tit_for_tat_punishes_defectors() {
+        let t = tournament();
+        let market = Market::new(&t, 0, 2);
+        assert_eq!(
+            decide(BotStrategyKind::TitForTat, None, &market, &[23.0]),
+            23
+        );
+        assert_eq!(
+            decide(BotStrategyKind::TitForTat, None, &market, &[40.0]),
+            30
+        );
+        // and forgives once they fall back in line
+        assert_eq!(
+            decide(BotStrategyKind::TitForTat, None, &market, &[40.0, 22.0]),
+            23
+        );
+    }
+
+    #[test]
+    fn flat_curves_produce_nothing() {
+        let mut t = tournament();
+        t.demand_xintercept = 0;
+        let market = Market::new(&t, 0, 2);
+        assert_eq!(decide(BotStrategyKind::Nash, None, &market, &[]), 0);
+        assert_eq!(
+            decide(BotStrategyKind::BestResponse, None, &market, &[5.0]),
+            0
+        );
+    }
+}
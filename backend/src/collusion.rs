@@ -0,0 +1,270 @@
+// Signs that firms quietly agreed not to compete, for discussing cartels after a game.
+// None of this proves anything, students can land on the same numbers by accident,
+// but sustained output near the monopoly level, moving in step and punishing
+// whoever breaks ranks is what a cartel looks like from the outside.
+use super::analytics::{FirmYear, Year};
+use super::db_types::*;
+use std::collections::BTreeMap;
+
+// how close to the monopoly output a year has to be to count as collusive
+const NEAR_MONOPOLY: f64 = 0.75;
+// how many years in a row make it more than a coincidence
+const SUSTAINED_YEARS: i64 = 3;
+// how often two firms have to move in the same direction to count as moving in step
+const PARALLEL_SHARE: f64 = 0.8;
+const MIN_MOVES: i64 = 3;
+
+// a firm's quantity in one year, and whether it kept to its share of the monopoly output
+type Play = (i64, bool);
+
+pub struct YearFinding {
+    pub year: i64,
+    pub total_quantity: i64,
+    pub nash_quantity: f64,
+    pub monopoly_quantity: f64,
+    // 0 at the Nash equilibrium, 1 at the monopoly output, unknown if the two are the same
+    pub collusion_index: Option<f64>,
+    pub near_monopoly: bool,
+}
+
+// how two firms played against each other
+pub struct PairFinding {
+    pub firms: (Firm, Firm),
+    pub years_together: i64,
+    // years where both changed their quantity in the same or opposite direction
+    pub parallel_moves: i64,
+    pub opposite_moves: i64,
+    pub parallel: bool,
+    // one left the cartel while the other kept to its share
+    pub defections: i64,
+    // the other left it too the year after
+    pub punishments: i64,
+    pub punishing: bool,
+    // most years in a row both kept to their cartel share
+    pub longest_cooperation: i64,
+    pub sustained_cooperation: bool,
+}
+
+pub struct Findings {
+    pub years: Vec<YearFinding>,
+    // most years in a row near the monopoly output
+    pub longest_near_monopoly: i64,
+    pub sustained_near_monopoly: bool,
+    pub pairs: Vec<PairFinding>,
+}
+
+fn collusion_index(year: &Year) -> Option<f64> {
+    let room = year.nash_quantity - year.monopoly_quantity;
+    if room <= 0.0 {
+        return None;
+    }
+    Some((year.nash_quantity - year.total_quantity as f64) / room)
+}
+
+// whether a firm produced no more than its share of the monopoly output
+fn cooperating(year: &Year, firm: &FirmYear) -> bool {
+    let cartel_share = year.monopoly_quantity / year.firms.len() as f64;
+    firm.quantity as f64 <= cartel_share.ceil()
+}
+
+// longest run of consecutive years for which the condition holds
+fn longest_run(years: impl Iterator<Item = (i64, bool)>) -> i64 {
+    let mut longest = 0;
+    let mut current = 0;
+    let mut previous_year = None;
+    for (year, holds) in years {
+        if !holds || previous_year.is_some_and(|p| p + 1 != year) {
+            current = 0;
+        }
+        if holds {
+            current += 1;
+            longest = longest.max(current);
+        }
+        previous_year = Some(year);
+    }
+    longest
+}
+
+fn pair(firms: (Firm, Firm), played: &BTreeMap<i64, BTreeMap<Firm, Play>>) -> PairFinding {
+    // quantity and whether they cooperated, for the years both sold
+    let together: BTreeMap<i64, (Play, Play)> = played
+        .iter()
+        .filter_map(|(&year, firms_in_year)| {
+            Some((
+                year,
+                (*firms_in_year.get(&firms.0)?, *firms_in_year.get(&firms.1)?),
+            ))
+        })
+        .collect();
+
+    let mut parallel_moves = 0;
+    let mut opposite_moves = 0;
+    let mut defections = 0;
+    let mut punishments = 0;
+    for (&year, &((a, a_coop), (b, b_coop))) in &together {
+        let ((prev_a, prev_a_coop), (prev_b, prev_b_coop)) = match together.get(&(year - 1)) {
+            Some(&previous) => previous,
+            None => continue,
+        };
+
+        match ((a - prev_a).signum(), (b - prev_b).signum()) {
+            (0, _) | (_, 0) => {}
+            (da, db) if da == db => parallel_moves += 1,
+            _ => opposite_moves += 1,
+        }
+
+        // a defection only counts if there was a cartel to leave
+        if !(prev_a_coop && prev_b_coop) || a_coop == b_coop {
+            continue;
+        }
+        defections += 1;
+        let punished = together.get(&(year + 1)).is_some_and(|&((_, a), (_, b))| {
+            // whoever stayed in the cartel leaves it
+            if a_coop {
+                !a
+            } else {
+                !b
+            }
+        });
+        if punished {
+            punishments += 1;
+        }
+    }
+
+    let moves = parallel_moves + opposite_moves;
+    let longest_cooperation = longest_run(
+        together
+            .iter()
+            .map(|(&year, &((_, a), (_, b)))| (year, a && b)),
+    );
+    PairFinding {
+        firms,
+        years_together: together.len() as i64,
+        parallel_moves,
+        opposite_moves,
+        parallel: moves >= MIN_MOVES && parallel_moves as f64 >= PARALLEL_SHARE * moves as f64,
+        defections,
+        punishments,
+        punishing: punishments > 0 && 2 * punishments >= defections,
+        longest_cooperation,
+        sustained_cooperation: longest_cooperation >= SUSTAINED_YEARS,
+    }
+}
+
+pub fn find(years: &[Year]) -> Findings {
+    let year_findings: Vec<YearFinding> = years
+        .iter()
+        .map(|y| {
+            let collusion_index = collusion_index(y);
+            YearFinding {
+                year: y.year,
+                total_quantity: y.total_quantity,
+                nash_quantity: y.nash_quantity,
+                monopoly_quantity: y.monopoly_quantity,
+                collusion_index,
+                near_monopoly: collusion_index.is_some_and(|i| i >= NEAR_MONOPOLY),
+            }
+        })
+        .collect();
+    let longest_near_monopoly =
+        longest_run(year_findings.iter().map(|y| (y.year, y.near_monopoly)));
+
+    let played: BTreeMap<i64, BTreeMap<Firm, Play>> = years
+        .iter()
+        .map(|y| {
+            (
+                y.year,
+                y.firms
+                    .iter()
+                    .map(|f| (f.firm, (f.quantity, cooperating(y, f))))
+                    .collect(),
+            )
+        })
+        .collect();
+    let mut firms: Vec<Firm> = played.values().flat_map(|f| f.keys().copied()).collect();
+    firms.sort();
+    firms.dedup();
+
+    let mut pairs = vec![];
+    for (i, &a) in firms.iter().enumerate() {
+        for &b in &firms[i + 1..] {
+            pairs.push(pair((a, b), &played));
+        }
+    }
+
+    Findings {
+        years: year_findings,
+        longest_near_monopoly,
+        sustained_near_monopoly: longest_near_monopoly >= SUSTAINED_YEARS,
+        pairs,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::analytics;
+    use super::*;
+    use crate::test_utils::{demand, submission, tournament};
+
+    // two players, quantities for each year starting at 0
+    fn play(t: &Tournament, quantities: &[(i64, i64)]) -> Vec<Year> {
+        let mut submissions = vec![];
+        let mut demands = vec![];
+        for (year, &(a, b)) in quantities.iter().enumerate() {
+            for (user_id, amount) in [(2, a), (3, b)] {
+                submissions.push(submission(user_id, year as i64, amount));
+                demands.push(demand(user_id, year as i64, 0));
+            }
+        }
+        analytics::years(t, &submissions, &demands, quantities.len() as i64 - 1)
+    }
+
+    #[test]
+    fn cartels_that_hold_and_punish() {
+        // price is 100 - Q, cost 10: 60 at the Nash equilibrium, 45 for a cartel, 23 each
+        let findings = find(&play(
+            &tournament(),
+            &[(22, 22), (23, 22), (22, 23), (35, 22), (30, 30), (25, 25)],
+        ));
+        assert_eq!(findings.years[0].collusion_index, Some(16.0 / 15.0));
+        assert!(findings.years[2].near_monopoly);
+        assert!(!findings.years[3].near_monopoly);
+        assert_eq!(findings.longest_near_monopoly, 3);
+        assert!(findings.sustained_near_monopoly);
+
+        let pair = &findings.pairs[0];
+        assert_eq!(pair.firms, (Firm::User(2), Firm::User(3)));
+        assert_eq!(pair.years_together, 6);
+        assert_eq!(pair.longest_cooperation, 3);
+        assert!(pair.sustained_cooperation);
+        // player 2 broke away in year 3 and player 3 answered in year 4
+        assert_eq!(pair.defections, 1);
+        assert_eq!(pair.punishments, 1);
+        assert!(pair.punishing);
+        assert_eq!((pair.parallel_moves, pair.opposite_moves), (1, 3));
+        assert!(!pair.parallel);
+    }
+
+    #[test]
+    fn firms_moving_in_step() {
+        let findings = find(&play(
+            &tournament(),
+            &[(30, 30), (35, 34), (40, 38), (35, 36), (30, 30)],
+        ));
+        assert_eq!(findings.longest_near_monopoly, 0);
+        let pair = &findings.pairs[0];
+        assert_eq!((pair.parallel_moves, pair.opposite_moves), (4, 0));
+        assert!(pair.parallel);
+        assert_eq!(pair.defections, 0);
+        assert!(!pair.punishing);
+    }
+
+    #[test]
+    fn flat_curves_have_no_index() {
+        let mut t = tournament();
+        t.demand_xintercept = 0;
+        let findings = find(&play(&t, &[(0, 0)]));
+        assert_eq!(findings.years[0].collusion_index, None);
+        assert!(!findings.years[0].near_monopoly);
+    }
+}
@@ -0,0 +1,258 @@
+use super::metrics;
+use super::response::AppError;
+use super::utils;
+use std::error::Error;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{watch, Mutex, OwnedMutexGuard, OwnedSemaphorePermit, Semaphore};
+use tokio::task::JoinHandle;
+use tokio_postgres::tls::NoTlsStream;
+use tokio_postgres::{Client, Connection, NoTls, Socket};
+
+const MIN_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+// one connection in the pool
+struct Slot {
+    client: Arc<Mutex<Client>>,
+    healthy: AtomicBool,
+}
+
+// Owns a fixed number of connections to postgres, each used by one transaction at a time.
+// A background task per connection watches it and swaps in a fresh client whenever it dies,
+// so a postgres restart doesn't leave us with permanently broken clients.
+pub struct Database {
+    database_url: String,
+    slots: Vec<Slot>,
+    // indexes of the slots nobody is using
+    idle: Arc<std::sync::Mutex<Vec<usize>>>,
+    // one permit per idle slot, so waiting for a connection doesn't spin
+    permits: Arc<Semaphore>,
+}
+
+// A connection borrowed from the pool, given back when dropped.
+pub struct PooledClient {
+    idle: Arc<std::sync::Mutex<Vec<usize>>>,
+    slot: usize,
+    client: OwnedMutexGuard<Client>,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl Deref for PooledClient {
+    type Target = Client;
+    fn deref(&self) -> &Client {
+        &self.client
+    }
+}
+
+impl DerefMut for PooledClient {
+    fn deref_mut(&mut self) -> &mut Client {
+        &mut self.client
+    }
+}
+
+impl Drop for PooledClient {
+    // the slot goes back before the permit does, so whoever gets the permit finds it
+    fn drop(&mut self) {
+        self.idle.lock().unwrap().push(self.slot);
+    }
+}
+
+impl Database {
+    // waits until postgres is reachable, then starts supervising every connection
+    // the supervisors stop once shutdown is signalled
+    pub async fn connect(
+        database_url: String,
+        pool_size: usize,
+        shutdown: watch::Receiver<bool>,
+    ) -> (Arc<Database>, JoinHandle<()>) {
+        let pool_size = pool_size.max(1);
+        let mut slots = vec![];
+        let mut connections = vec![];
+        for _ in 0..pool_size {
+            let (client, connection) = connect_with_backoff(&database_url).await;
+            slots.push(Slot {
+                client: Arc::new(Mutex::new(client)),
+                healthy: AtomicBool::new(true),
+            });
+            connections.push(connection);
+        }
+
+        let db = Arc::new(Database {
+            database_url,
+            slots,
+            idle: Arc::new(std::sync::Mutex::new((0..pool_size).rev().collect())),
+            permits: Arc::new(Semaphore::new(pool_size)),
+        });
+
+        let supervisors: Vec<JoinHandle<()>> = connections
+            .into_iter()
+            .enumerate()
+            .map(|(slot, connection)| {
+                tokio::spawn(db.clone().supervise(slot, connection, shutdown.clone()))
+            })
+            .collect();
+        let supervisor = tokio::spawn(async move {
+            for s in supervisors {
+                s.await.ok();
+            }
+        });
+
+        (db, supervisor)
+    }
+
+    // borrows a healthy connection, or returns an error if every one is currently disconnected
+    pub async fn lock(&self) -> Result<PooledClient, AppError> {
+        let start = Instant::now();
+        let permit = self
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("pool semaphore is never closed");
+        // a slot can still look healthy for a moment after its connection closed,
+        // so each one gets at most one try per borrow
+        for _ in 0..self.slots.len() {
+            let slot = {
+                let mut idle = self.idle.lock().unwrap();
+                match idle
+                    .iter()
+                    .rposition(|&s| self.slots[s].healthy.load(Ordering::SeqCst))
+                {
+                    Some(i) => idle.remove(i),
+                    None => break,
+                }
+            };
+            // only contended while the supervisor swaps in a new client
+            let client = self.slots[slot].client.clone().lock_owned().await;
+            if client.is_closed() {
+                // dead slots go to the bottom, so the next borrower tries the others first
+                drop(client);
+                self.idle.lock().unwrap().insert(0, slot);
+                continue;
+            }
+            metrics::record_db_pool_wait(start.elapsed());
+            return Ok(PooledClient {
+                idle: self.idle.clone(),
+                slot,
+                client,
+                _permit: permit,
+            });
+        }
+        metrics::record_db_pool_wait(start.elapsed());
+        Err(AppError::DatabaseUnavailable)
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.slots.iter().any(|s| s.healthy.load(Ordering::SeqCst))
+    }
+
+    // checks that we can actually run a query, not just that we think we're connected
+    pub async fn ping(&self) -> bool {
+        match self.lock().await {
+            Ok(client) => client.simple_query("SELECT 1").await.is_ok(),
+            Err(_) => false,
+        }
+    }
+
+    async fn supervise(
+        self: Arc<Self>,
+        slot: usize,
+        mut connection: Connection<Socket, NoTlsStream>,
+        mut shutdown: watch::Receiver<bool>,
+    ) {
+        let healthy = &self.slots[slot].healthy;
+        loop {
+            // The connection object performs the actual communication with the database,
+            // and only resolves once the connection is gone
+            let result = tokio::select! {
+                result = &mut connection => result,
+                _ = shutdown.changed() => break,
+            };
+            let msg = match result {
+                Ok(()) => "database connection closed".to_owned(),
+                Err(e) => e.to_string(),
+            };
+            healthy.store(false, Ordering::SeqCst);
+            utils::log(utils::Event {
+                msg,
+                source: Some("database connection lost, reconnecting".to_owned()),
+                severity: utils::SeverityKind::Error,
+            });
+
+            let (client, new_connection) = tokio::select! {
+                v = connect_with_backoff(&self.database_url) => v,
+                _ = shutdown.changed() => break,
+            };
+            *self.slots[slot].client.lock().await = client;
+            connection = new_connection;
+
+            healthy.store(true, Ordering::SeqCst);
+            utils::log(utils::Event {
+                msg: "database connection reestablished".to_owned(),
+                source: None::<String>,
+                severity: utils::SeverityKind::Info,
+            });
+        }
+
+        healthy.store(false, Ordering::SeqCst);
+        utils::log(utils::Event {
+            msg: "database supervisor stopped".to_owned(),
+            source: None::<String>,
+            severity: utils::SeverityKind::Info,
+        });
+    }
+}
+
+async fn connect_with_backoff(database_url: &str) -> (Client, Connection<Socket, NoTlsStream>) {
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        match tokio_postgres::connect(database_url, NoTls).await {
+            Ok(v) => return v,
+            Err(e) => utils::log(utils::Event {
+                msg: e.to_string(),
+                source: e.source().map(|x| x.to_string()),
+                severity: utils::SeverityKind::Error,
+            }),
+        }
+
+        tokio::time::sleep(backoff).await;
+        backoff = std::cmp::min(backoff * 2, MAX_BACKOFF);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn unhealthy_slots_are_skipped() {
+        // needs a real postgres, like the integration tests
+        let database_url = match std::env::var("TEST_DATABASE_URL") {
+            Ok(v) => v,
+            Err(_) => return,
+        };
+        let (_shutdown_tx, shutdown_rx) = watch::channel(false);
+        let (db, _) = Database::connect(database_url, 2, shutdown_rx).await;
+
+        // slot 0 is on top of the idle stack, so it's the one that would keep coming back
+        db.slots[0].healthy.store(false, Ordering::SeqCst);
+        for _ in 0..4 {
+            let client = db.lock().await.unwrap();
+            assert_eq!(client.slot, 1);
+            assert!(client.simple_query("SELECT 1").await.is_ok());
+        }
+        assert!(db.is_healthy());
+        assert_eq!(db.idle.lock().unwrap().len(), 2);
+
+        db.slots[1].healthy.store(false, Ordering::SeqCst);
+        assert!(matches!(
+            db.lock().await,
+            Err(AppError::DatabaseUnavailable)
+        ));
+        assert!(!db.is_healthy());
+        assert_eq!(db.idle.lock().unwrap().len(), 2);
+    }
+}
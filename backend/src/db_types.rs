@@ -1,3 +1,6 @@
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
 #[derive(Clone, Debug)]
 pub struct Tournament {
     pub tournament_id: i64,
@@ -9,6 +12,95 @@ pub struct Tournament {
     pub incentive_multiplier: i64,
     pub incentive_start_year: i64,
     pub max_years: i64,
+    pub year_duration_ms: Option<i64>,
+    pub late_join_policy: LateJoinPolicyKind,
+    pub exit_policy: ExitPolicyKind,
+    pub visibility: VisibilityKind,
+    pub min_players: Option<i64>,
+    pub max_players: Option<i64>,
+    pub auto_start: bool,
+    // one student against bots
+    pub practice: bool,
+}
+
+// what happens to players who join after the game started
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum LateJoinPolicyKind {
+    // they can't
+    Forbidden,
+    // they play from the current year on
+    CatchUp,
+    // they may watch, but not play
+    Observer,
+}
+
+impl TryFrom<i64> for LateJoinPolicyKind {
+    type Error = i64;
+    fn try_from(val: i64) -> Result<LateJoinPolicyKind, i64> {
+        match val {
+            x if x == LateJoinPolicyKind::Forbidden as i64 => Ok(LateJoinPolicyKind::Forbidden),
+            x if x == LateJoinPolicyKind::CatchUp as i64 => Ok(LateJoinPolicyKind::CatchUp),
+            x if x == LateJoinPolicyKind::Observer as i64 => Ok(LateJoinPolicyKind::Observer),
+            x => Err(x),
+        }
+    }
+}
+
+// what happens to the balance of players who leave after the game started
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ExitPolicyKind {
+    // they're left out of the final results
+    Forfeit,
+    // they're ranked with whatever they earned before leaving
+    KeepBalance,
+}
+
+impl TryFrom<i64> for ExitPolicyKind {
+    type Error = i64;
+    fn try_from(val: i64) -> Result<ExitPolicyKind, i64> {
+        match val {
+            x if x == ExitPolicyKind::Forfeit as i64 => Ok(ExitPolicyKind::Forfeit),
+            x if x == ExitPolicyKind::KeepBalance as i64 => Ok(ExitPolicyKind::KeepBalance),
+            x => Err(x),
+        }
+    }
+}
+
+// who may join
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum VisibilityKind {
+    // anyone who knows the tournament
+    Public,
+    // anyone who knows the current invite code
+    InviteCode,
+    // anyone an organizer lets in, before the game starts
+    ApprovalRequired,
+}
+
+impl TryFrom<i64> for VisibilityKind {
+    type Error = i64;
+    fn try_from(val: i64) -> Result<VisibilityKind, i64> {
+        match val {
+            x if x == VisibilityKind::Public as i64 => Ok(VisibilityKind::Public),
+            x if x == VisibilityKind::InviteCode as i64 => Ok(VisibilityKind::InviteCode),
+            x if x == VisibilityKind::ApprovalRequired as i64 => {
+                Ok(VisibilityKind::ApprovalRequired)
+            }
+            x => Err(x),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TournamentInviteCode {
+    pub tournament_invite_code_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub tournament_id: i64,
+    pub invite_code: String,
 }
 
 #[derive(Clone, Debug)]
@@ -28,6 +120,55 @@ pub struct TournamentYear {
     pub creator_user_id: i64,
     pub tournament_id: i64,
     pub current_year: i64,
+    pub deadline: Option<i64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct TournamentPause {
+    pub tournament_pause_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub tournament_id: i64,
+    pub start_time: i64,
+    pub end_time: i64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TournamentStateKind {
+    // players may join, nobody may submit yet
+    Lobby,
+    // players submit, and the creator advances the years
+    Running,
+    // like running, but nobody may submit or advance
+    Paused,
+    // the game is over, results are final
+    Finished,
+    // hidden, nothing may change anymore
+    Archived,
+}
+
+impl TryFrom<i64> for TournamentStateKind {
+    type Error = i64;
+    fn try_from(val: i64) -> Result<TournamentStateKind, i64> {
+        match val {
+            x if x == TournamentStateKind::Lobby as i64 => Ok(TournamentStateKind::Lobby),
+            x if x == TournamentStateKind::Running as i64 => Ok(TournamentStateKind::Running),
+            x if x == TournamentStateKind::Paused as i64 => Ok(TournamentStateKind::Paused),
+            x if x == TournamentStateKind::Finished as i64 => Ok(TournamentStateKind::Finished),
+            x if x == TournamentStateKind::Archived as i64 => Ok(TournamentStateKind::Archived),
+            x => Err(x),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TournamentState {
+    pub tournament_state_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub tournament_id: i64,
+    pub state: TournamentStateKind,
 }
 
 #[derive(Clone, Debug)]
@@ -35,11 +176,135 @@ pub struct TournamentYearDemand {
     pub tournament_year_demand_id: i64,
     pub creation_time: i64,
     pub tournament_id: i64,
-    pub user_id: i64,
+    // exactly one of user_id, tournament_team_id and tournament_bot_id is set
+    pub user_id: Option<i64>,
+    pub tournament_team_id: Option<i64>,
+    pub tournament_bot_id: Option<i64>,
     pub year: i64,
     pub demand: i64,
 }
 
+impl TournamentYearDemand {
+    pub fn firm(&self) -> Firm {
+        Firm::of(
+            self.user_id,
+            self.tournament_team_id,
+            self.tournament_bot_id,
+        )
+    }
+}
+
+// who sells into the market, a player on their own, a team sharing one firm or a bot
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Firm {
+    User(i64),
+    Team(i64),
+    Bot(i64),
+}
+
+impl Firm {
+    // a team wins over the user, since team members act for the team
+    pub fn new(user_id: i64, tournament_team_id: Option<i64>) -> Firm {
+        Firm::of(Some(user_id), tournament_team_id, None)
+    }
+
+    // bots and teams win over the user, who only acts for them
+    fn of(
+        user_id: Option<i64>,
+        tournament_team_id: Option<i64>,
+        tournament_bot_id: Option<i64>,
+    ) -> Firm {
+        match (user_id, tournament_team_id, tournament_bot_id) {
+            (_, _, Some(tournament_bot_id)) => Firm::Bot(tournament_bot_id),
+            (_, Some(tournament_team_id), None) => Firm::Team(tournament_team_id),
+            (Some(user_id), None, None) => Firm::User(user_id),
+            (None, None, None) => unreachable!("rows always belong to a user, a team or a bot"),
+        }
+    }
+
+    pub fn user_id(self) -> Option<i64> {
+        match self {
+            Firm::User(user_id) => Some(user_id),
+            _ => None,
+        }
+    }
+
+    pub fn tournament_team_id(self) -> Option<i64> {
+        match self {
+            Firm::Team(tournament_team_id) => Some(tournament_team_id),
+            _ => None,
+        }
+    }
+
+    pub fn tournament_bot_id(self) -> Option<i64> {
+        match self {
+            Firm::Bot(tournament_bot_id) => Some(tournament_bot_id),
+            _ => None,
+        }
+    }
+}
+
+// the name a member goes by in a tournament, only organizers see who is behind it
+#[derive(Clone, Debug)]
+pub struct TournamentPseudonym {
+    pub tournament_pseudonym_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub tournament_id: i64,
+    pub user_id: i64,
+    pub pseudonym: String,
+}
+
+// several users playing as one firm
+#[derive(Clone, Debug)]
+pub struct TournamentTeam {
+    pub tournament_team_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub tournament_id: i64,
+    pub name: String,
+}
+
+// how a bot decides what to produce, see bots.rs
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum BotStrategyKind {
+    Random,
+    FixedQuantity,
+    BestResponse,
+    Nash,
+    TitForTat,
+    Adaptive,
+}
+
+impl TryFrom<i64> for BotStrategyKind {
+    type Error = i64;
+    fn try_from(val: i64) -> Result<BotStrategyKind, i64> {
+        match val {
+            x if x == BotStrategyKind::Random as i64 => Ok(BotStrategyKind::Random),
+            x if x == BotStrategyKind::FixedQuantity as i64 => Ok(BotStrategyKind::FixedQuantity),
+            x if x == BotStrategyKind::BestResponse as i64 => Ok(BotStrategyKind::BestResponse),
+            x if x == BotStrategyKind::Nash as i64 => Ok(BotStrategyKind::Nash),
+            x if x == BotStrategyKind::TitForTat as i64 => Ok(BotStrategyKind::TitForTat),
+            x if x == BotStrategyKind::Adaptive as i64 => Ok(BotStrategyKind::Adaptive),
+            x => Err(x),
+        }
+    }
+}
+
+// a computer player, the service submits for it when the year closes
+#[derive(Clone, Debug)]
+pub struct TournamentBot {
+    pub tournament_bot_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub tournament_id: i64,
+    pub strategy: BotStrategyKind,
+    // only for fixed quantity bots
+    pub quantity: Option<i64>,
+    pub name: String,
+}
+
 #[derive(Clone, Debug)]
 pub struct TournamentMembership {
     pub tournament_membership_id: i64,
@@ -47,16 +312,138 @@ pub struct TournamentMembership {
     pub creator_user_id: i64,
     pub tournament_id: i64,
     pub active: bool,
+    // watches the game without playing
+    pub observer: bool,
+    pub approval: ApprovalKind,
+    // taken out by an organizer, and left out of the results
+    pub removed: bool,
+    // removed, and may not rejoin
+    pub banned: bool,
+    // who made this change, the member or an organizer
+    pub actor_user_id: i64,
+    pub reason: Option<String>,
+    // the team this member plays for, if any
+    pub tournament_team_id: Option<i64>,
+}
+
+impl TournamentMembership {
+    pub fn firm(&self) -> Firm {
+        Firm::new(self.creator_user_id, self.tournament_team_id)
+    }
+}
+
+// whether a member was let in, always approved unless the tournament requires approval
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ApprovalKind {
+    // waiting for an organizer
+    Pending,
+    Approved,
+    Rejected,
+}
+
+impl TryFrom<i64> for ApprovalKind {
+    type Error = i64;
+    fn try_from(val: i64) -> Result<ApprovalKind, i64> {
+        match val {
+            x if x == ApprovalKind::Pending as i64 => Ok(ApprovalKind::Pending),
+            x if x == ApprovalKind::Approved as i64 => Ok(ApprovalKind::Approved),
+            x if x == ApprovalKind::Rejected as i64 => Ok(ApprovalKind::Rejected),
+            x => Err(x),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TournamentRoleKind {
+    // runs the game, and decides who else may
+    Owner,
+    // runs the game: edits it, starts, pauses and advances years, finalizes
+    Organizer,
+    // sees everything players and organizers see, but changes nothing
+    Observer,
+}
+
+impl TryFrom<i64> for TournamentRoleKind {
+    type Error = i64;
+    fn try_from(val: i64) -> Result<TournamentRoleKind, i64> {
+        match val {
+            x if x == TournamentRoleKind::Owner as i64 => Ok(TournamentRoleKind::Owner),
+            x if x == TournamentRoleKind::Organizer as i64 => Ok(TournamentRoleKind::Organizer),
+            x if x == TournamentRoleKind::Observer as i64 => Ok(TournamentRoleKind::Observer),
+            x => Err(x),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TournamentRole {
+    pub tournament_role_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub tournament_id: i64,
+    pub user_id: i64,
+    pub role: TournamentRoleKind,
+    pub active: bool,
 }
 
 #[derive(Clone, Debug)]
 pub struct TournamentSubmission {
     pub tournament_submission_id: i64,
     pub creation_time: i64,
+    // who acted, for teams any member may submit
     pub creator_user_id: i64,
+    pub tournament_team_id: Option<i64>,
+    pub tournament_bot_id: Option<i64>,
     pub tournament_id: i64,
     pub year: i64,
     pub amount: i64,
     pub autogenerated: bool,
 }
 
+impl TournamentSubmission {
+    pub fn firm(&self) -> Firm {
+        Firm::of(
+            Some(self.creator_user_id),
+            self.tournament_team_id,
+            self.tournament_bot_id,
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TournamentFinalResult {
+    pub tournament_final_result_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub tournament_id: i64,
+    // exactly one of user_id, tournament_team_id and tournament_bot_id is set
+    pub user_id: Option<i64>,
+    pub tournament_team_id: Option<i64>,
+    pub tournament_bot_id: Option<i64>,
+    pub rank: i64,
+    pub balance: f64,
+}
+
+impl TournamentFinalResult {
+    pub fn firm(&self) -> Firm {
+        Firm::of(
+            self.user_id,
+            self.tournament_team_id,
+            self.tournament_bot_id,
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct IdempotencyKey {
+    pub idempotency_key_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub endpoint: String,
+    pub idempotency_key: String,
+    pub request_hash: String,
+    // serialized json
+    pub response: String,
+}
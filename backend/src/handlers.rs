@@ -1,64 +1,32 @@
-use crate::tournament_year_demand_service;
-use crate::tournament_year_service;
-
+use super::store::StoreTransaction;
+use super::Auth;
 use super::Db;
-use auth_service_api::client::AuthService;
-use auth_service_api::response::AuthError;
 use auth_service_api::response::User;
 
 use super::request;
 use super::response;
 
+use super::analytics;
+use super::bots;
+use super::collusion;
 use super::db_types::*;
+use super::market;
+use super::metrics;
 use super::utils;
 
-use super::tournament_data_service;
-use super::tournament_membership_service;
-use super::tournament_service;
-use super::tournament_submission_service;
-
-use std::collections::HashSet;
-use std::error::Error;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Instant;
 
 use super::Config;
 
-pub fn report_postgres_err(e: tokio_postgres::Error) -> response::AppError {
-    utils::log(utils::Event {
-        msg: e.to_string(),
-        source: e.source().map(|e| e.to_string()),
-        severity: utils::SeverityKind::Error,
-    });
-    response::AppError::InternalServerError
-}
-
-fn report_auth_err(e: AuthError) -> response::AppError {
-    match e {
-        AuthError::ApiKeyNonexistent => response::AppError::Unauthorized,
-        AuthError::ApiKeyUnauthorized => response::AppError::Unauthorized,
-        c => {
-            let ae = match c {
-                AuthError::InternalServerError => response::AppError::InternalServerError,
-                AuthError::MethodNotAllowed => response::AppError::InternalServerError,
-                AuthError::BadRequest => response::AppError::InternalServerError,
-                AuthError::Network => response::AppError::InternalServerError,
-                _ => response::AppError::Unknown,
-            };
-
-            utils::log(utils::Event {
-                msg: ae.as_ref().to_owned(),
-                source: Some(format!("auth service: {}", c.as_ref())),
-                severity: utils::SeverityKind::Error,
-            });
-
-            ae
-        }
-    }
-}
-
 async fn fill_tournament(
-    _con: &mut tokio_postgres::Client,
+    sp: &mut dyn StoreTransaction,
     tournament: Tournament,
 ) -> Result<response::Tournament, response::AppError> {
+    let state = get_tournament_state(sp, tournament.tournament_id).await?;
+
     Ok(response::Tournament {
         tournament_id: tournament.tournament_id,
         creation_time: tournament.creation_time,
@@ -69,598 +37,4883 @@ async fn fill_tournament(
         incentive_multiplier: tournament.incentive_multiplier,
         incentive_start_year: tournament.incentive_start_year,
         max_years: tournament.max_years,
+        year_duration_ms: tournament.year_duration_ms,
+        late_join_policy: tournament.late_join_policy,
+        exit_policy: tournament.exit_policy,
+        visibility: tournament.visibility,
+        min_players: tournament.min_players,
+        max_players: tournament.max_players,
+        auto_start: tournament.auto_start,
+        practice: tournament.practice,
+        state,
     })
 }
 
 async fn fill_tournament_data(
-    con: &mut tokio_postgres::Client,
+    sp: &mut dyn StoreTransaction,
     tournament_data: TournamentData,
 ) -> Result<response::TournamentData, response::AppError> {
-    let tournament = tournament_service::get_by_tournament_id(con, tournament_data.tournament_id)
-        .await
-        .map_err(report_postgres_err)?
+    let tournament = sp
+        .tournament_get(tournament_data.tournament_id)
+        .await?
         .ok_or(response::AppError::TournamentNonexistent)?;
 
     Ok(response::TournamentData {
         tournament_data_id: tournament_data.tournament_data_id,
         creation_time: tournament_data.creation_time,
         creator_user_id: tournament_data.creator_user_id,
-        tournament: fill_tournament(con, tournament).await?,
+        tournament: fill_tournament(sp, tournament).await?,
         title: tournament_data.title,
         active: tournament_data.active,
     })
 }
 
+async fn fill_tournament_state(
+    sp: &mut dyn StoreTransaction,
+    tournament_state: TournamentState,
+) -> Result<response::TournamentState, response::AppError> {
+    let tournament = sp
+        .tournament_get(tournament_state.tournament_id)
+        .await?
+        .ok_or(response::AppError::TournamentNonexistent)?;
+
+    Ok(response::TournamentState {
+        tournament_state_id: tournament_state.tournament_state_id,
+        creation_time: tournament_state.creation_time,
+        creator_user_id: tournament_state.creator_user_id,
+        tournament: fill_tournament(sp, tournament).await?,
+        state: tournament_state.state,
+    })
+}
+
 async fn fill_tournament_year(
-    con: &mut tokio_postgres::Client,
+    sp: &mut dyn StoreTransaction,
     tournament_year: TournamentYear,
 ) -> Result<response::TournamentYear, response::AppError> {
-    let tournament = tournament_service::get_by_tournament_id(con, tournament_year.tournament_id)
-        .await
-        .map_err(report_postgres_err)?
+    let tournament = sp
+        .tournament_get(tournament_year.tournament_id)
+        .await?
         .ok_or(response::AppError::TournamentNonexistent)?;
 
     Ok(response::TournamentYear {
         tournament_year_id: tournament_year.tournament_year_id,
         creation_time: tournament_year.creation_time,
         creator_user_id: tournament_year.creator_user_id,
-        tournament: fill_tournament(con, tournament).await?,
+        tournament: fill_tournament(sp, tournament).await?,
         current_year: tournament_year.current_year,
+        deadline: tournament_year.deadline,
+    })
+}
+
+async fn fill_tournament_pause(
+    sp: &mut dyn StoreTransaction,
+    tournament_pause: TournamentPause,
+) -> Result<response::TournamentPause, response::AppError> {
+    let tournament = sp
+        .tournament_get(tournament_pause.tournament_id)
+        .await?
+        .ok_or(response::AppError::TournamentNonexistent)?;
+
+    Ok(response::TournamentPause {
+        tournament_pause_id: tournament_pause.tournament_pause_id,
+        creation_time: tournament_pause.creation_time,
+        creator_user_id: tournament_pause.creator_user_id,
+        tournament: fill_tournament(sp, tournament).await?,
+        start_time: tournament_pause.start_time,
+        end_time: tournament_pause.end_time,
     })
 }
 
 async fn fill_tournament_year_demand(
-    con: &mut tokio_postgres::Client,
+    sp: &mut dyn StoreTransaction,
     tournament_year_demand: TournamentYearDemand,
+    viewer_user_id: i64,
 ) -> Result<response::TournamentYearDemand, response::AppError> {
-    let tournament =
-        tournament_service::get_by_tournament_id(con, tournament_year_demand.tournament_id)
-            .await
-            .map_err(report_postgres_err)?
-            .ok_or(response::AppError::TournamentNonexistent)?;
+    let tournament = sp
+        .tournament_get(tournament_year_demand.tournament_id)
+        .await?
+        .ok_or(response::AppError::TournamentNonexistent)?;
+    let reveal = identity_filter(sp, tournament.tournament_id, viewer_user_id).await?;
 
     Ok(response::TournamentYearDemand {
         tournament_year_demand_id: tournament_year_demand.tournament_year_demand_id,
         creation_time: tournament_year_demand.creation_time,
-        user_id: tournament_year_demand.user_id,
-        tournament: fill_tournament(con, tournament).await?,
+        user_id: tournament_year_demand.user_id.and_then(reveal),
+        tournament_team_id: tournament_year_demand.tournament_team_id,
+        tournament_bot_id: tournament_year_demand.tournament_bot_id,
+        tournament: fill_tournament(sp, tournament).await?,
         year: tournament_year_demand.year,
         demand: tournament_year_demand.demand,
     })
 }
 async fn fill_tournament_membership(
-    con: &mut tokio_postgres::Client,
+    sp: &mut dyn StoreTransaction,
     tournament_membership: TournamentMembership,
+    viewer_user_id: i64,
 ) -> Result<response::TournamentMembership, response::AppError> {
-    let tournament =
-        tournament_service::get_by_tournament_id(con, tournament_membership.tournament_id)
-            .await
-            .map_err(report_postgres_err)?
-            .ok_or(response::AppError::TournamentNonexistent)?;
+    let tournament = sp
+        .tournament_get(tournament_membership.tournament_id)
+        .await?
+        .ok_or(response::AppError::TournamentNonexistent)?;
+    let reveal = identity_filter(sp, tournament.tournament_id, viewer_user_id).await?;
 
     Ok(response::TournamentMembership {
         tournament_membership_id: tournament_membership.tournament_membership_id,
         creation_time: tournament_membership.creation_time,
-        creator_user_id: tournament_membership.creator_user_id,
-        tournament: fill_tournament(con, tournament).await?,
+        creator_user_id: reveal(tournament_membership.creator_user_id),
+        tournament: fill_tournament(sp, tournament).await?,
         active: tournament_membership.active,
+        observer: tournament_membership.observer,
+        approval: tournament_membership.approval,
+        removed: tournament_membership.removed,
+        banned: tournament_membership.banned,
+        actor_user_id: reveal(tournament_membership.actor_user_id),
+        pseudonym: get_pseudonym(
+            sp,
+            tournament_membership.tournament_id,
+            tournament_membership.creator_user_id,
+        )
+        .await?,
+        reason: tournament_membership.reason,
+        tournament_team_id: tournament_membership.tournament_team_id,
+    })
+}
+
+async fn fill_tournament_invite_code(
+    sp: &mut dyn StoreTransaction,
+    tournament_invite_code: TournamentInviteCode,
+) -> Result<response::TournamentInviteCode, response::AppError> {
+    let tournament = sp
+        .tournament_get(tournament_invite_code.tournament_id)
+        .await?
+        .ok_or(response::AppError::TournamentNonexistent)?;
+
+    Ok(response::TournamentInviteCode {
+        tournament_invite_code_id: tournament_invite_code.tournament_invite_code_id,
+        creation_time: tournament_invite_code.creation_time,
+        creator_user_id: tournament_invite_code.creator_user_id,
+        tournament: fill_tournament(sp, tournament).await?,
+        invite_code: tournament_invite_code.invite_code,
+    })
+}
+
+async fn fill_tournament_pseudonym(
+    sp: &mut dyn StoreTransaction,
+    tournament_pseudonym: TournamentPseudonym,
+) -> Result<response::TournamentPseudonym, response::AppError> {
+    let tournament = sp
+        .tournament_get(tournament_pseudonym.tournament_id)
+        .await?
+        .ok_or(response::AppError::TournamentNonexistent)?;
+
+    Ok(response::TournamentPseudonym {
+        tournament_pseudonym_id: tournament_pseudonym.tournament_pseudonym_id,
+        creation_time: tournament_pseudonym.creation_time,
+        creator_user_id: tournament_pseudonym.creator_user_id,
+        tournament: fill_tournament(sp, tournament).await?,
+        user_id: tournament_pseudonym.user_id,
+        pseudonym: tournament_pseudonym.pseudonym,
+    })
+}
+
+async fn fill_tournament_bot(
+    sp: &mut dyn StoreTransaction,
+    tournament_bot: TournamentBot,
+) -> Result<response::TournamentBot, response::AppError> {
+    let tournament = sp
+        .tournament_get(tournament_bot.tournament_id)
+        .await?
+        .ok_or(response::AppError::TournamentNonexistent)?;
+
+    Ok(response::TournamentBot {
+        tournament_bot_id: tournament_bot.tournament_bot_id,
+        creation_time: tournament_bot.creation_time,
+        creator_user_id: tournament_bot.creator_user_id,
+        tournament: fill_tournament(sp, tournament).await?,
+        strategy: tournament_bot.strategy,
+        quantity: tournament_bot.quantity,
+        name: tournament_bot.name,
+    })
+}
+
+async fn fill_tournament_team(
+    sp: &mut dyn StoreTransaction,
+    tournament_team: TournamentTeam,
+) -> Result<response::TournamentTeam, response::AppError> {
+    let tournament = sp
+        .tournament_get(tournament_team.tournament_id)
+        .await?
+        .ok_or(response::AppError::TournamentNonexistent)?;
+
+    Ok(response::TournamentTeam {
+        tournament_team_id: tournament_team.tournament_team_id,
+        creation_time: tournament_team.creation_time,
+        creator_user_id: tournament_team.creator_user_id,
+        tournament: fill_tournament(sp, tournament).await?,
+        name: tournament_team.name,
+    })
+}
+
+async fn fill_tournament_role(
+    sp: &mut dyn StoreTransaction,
+    tournament_role: TournamentRole,
+) -> Result<response::TournamentRole, response::AppError> {
+    let tournament = sp
+        .tournament_get(tournament_role.tournament_id)
+        .await?
+        .ok_or(response::AppError::TournamentNonexistent)?;
+
+    Ok(response::TournamentRole {
+        tournament_role_id: tournament_role.tournament_role_id,
+        creation_time: tournament_role.creation_time,
+        creator_user_id: tournament_role.creator_user_id,
+        tournament: fill_tournament(sp, tournament).await?,
+        user_id: tournament_role.user_id,
+        role: tournament_role.role,
+        active: tournament_role.active,
     })
 }
 
 async fn fill_tournament_submission(
-    con: &mut tokio_postgres::Client,
+    sp: &mut dyn StoreTransaction,
     tournament_submission: TournamentSubmission,
+    viewer_user_id: i64,
 ) -> Result<response::TournamentSubmission, response::AppError> {
-    let tournament =
-        tournament_service::get_by_tournament_id(con, tournament_submission.tournament_id)
-            .await
-            .map_err(report_postgres_err)?
-            .ok_or(response::AppError::TournamentNonexistent)?;
+    let tournament = sp
+        .tournament_get(tournament_submission.tournament_id)
+        .await?
+        .ok_or(response::AppError::TournamentNonexistent)?;
+    let reveal = identity_filter(sp, tournament.tournament_id, viewer_user_id).await?;
+
+    // bots submit as whoever added them, but go by their own name
+    let pseudonym = match tournament_submission.tournament_bot_id {
+        Some(tournament_bot_id) => sp
+            .tournament_bot_get(tournament_bot_id)
+            .await?
+            .map(|b| b.name),
+        None => {
+            get_pseudonym(
+                sp,
+                tournament_submission.tournament_id,
+                tournament_submission.creator_user_id,
+            )
+            .await?
+        }
+    };
 
     Ok(response::TournamentSubmission {
         tournament_submission_id: tournament_submission.tournament_submission_id,
         creation_time: tournament_submission.creation_time,
-        creator_user_id: tournament_submission.creator_user_id,
-        tournament: fill_tournament(con, tournament).await?,
+        creator_user_id: reveal(tournament_submission.creator_user_id),
+        tournament_team_id: tournament_submission.tournament_team_id,
+        tournament_bot_id: tournament_submission.tournament_bot_id,
+        pseudonym,
+        tournament: fill_tournament(sp, tournament).await?,
         year: tournament_submission.year,
         amount: tournament_submission.amount,
         autogenerated: tournament_submission.autogenerated,
     })
 }
 
-pub async fn get_user_if_api_key_valid(
-    auth_service: &auth_service_api::client::AuthService,
-    api_key: String,
-) -> Result<User, response::AppError> {
-    auth_service
-        .get_user_by_api_key_if_valid(api_key)
-        .await
-        .map_err(report_auth_err)
-}
-
-// generate 2 random numbers between -|m| and |m|, pick the one farthest from zero
-fn gen_random_incentive(m: i64) -> i64 {
-    if m == 0 {
-        return 0;
+async fn fill_tournament_analytics(
+    sp: &mut dyn StoreTransaction,
+    tournament: Tournament,
+    years: Vec<analytics::Year>,
+    summaries: Vec<analytics::FirmSummary>,
+) -> Result<response::TournamentAnalytics, response::AppError> {
+    let mut resp_years = vec![];
+    for year in years {
+        let mut resp_firms = vec![];
+        for f in year.firms {
+            resp_firms.push(response::AnalyticsFirmYear {
+                user_id: f.firm.user_id(),
+                tournament_team_id: f.firm.tournament_team_id(),
+                tournament_bot_id: f.firm.tournament_bot_id(),
+                pseudonym: firm_name(sp, tournament.tournament_id, f.firm).await?,
+                quantity: f.quantity,
+                share: f.share,
+                best_response: f.best_response,
+                deviation: f.quantity as f64 - f.best_response,
+            });
+        }
+        resp_years.push(response::AnalyticsYear {
+            year: year.year,
+            total_quantity: year.total_quantity,
+            price: year.price,
+            nash_price: year.nash_price,
+            nash_price_gap: year.price - year.nash_price,
+            hhi: year.hhi,
+            firms: resp_firms,
+        });
     }
-    let absmul = m.abs();
-    let r1 = utils::random_number(-absmul, absmul);
-    let r2 = utils::random_number(-absmul, absmul);
-    if r1.abs() > r2.abs() {
-        return r1;
-    } else {
-        return r2;
+
+    let mut resp_firms = vec![];
+    for s in summaries {
+        resp_firms.push(response::AnalyticsFirm {
+            user_id: s.firm.user_id(),
+            tournament_team_id: s.firm.tournament_team_id(),
+            tournament_bot_id: s.firm.tournament_bot_id(),
+            pseudonym: firm_name(sp, tournament.tournament_id, s.firm).await?,
+            mean_quantity: s.mean_quantity,
+            quantity_std_dev: s.quantity_std_dev,
+            mean_deviation: s.mean_deviation,
+        });
     }
-}
 
-pub async fn tournament_new(
-    _config: Config,
-    db: Db,
-    auth_service: AuthService,
-    props: request::TournamentNewProps,
-) -> Result<response::TournamentData, response::AppError> {
-    // validate api key
-    let user = get_user_if_api_key_valid(&auth_service, props.api_key).await?;
+    Ok(response::TournamentAnalytics {
+        tournament: fill_tournament(sp, tournament).await?,
+        years: resp_years,
+        firms: resp_firms,
+    })
+}
 
-    let con = &mut *db.lock().await;
+async fn fill_collusion_firm(
+    sp: &mut dyn StoreTransaction,
+    tournament_id: i64,
+    firm: Firm,
+) -> Result<response::CollusionFirm, response::AppError> {
+    Ok(response::CollusionFirm {
+        user_id: firm.user_id(),
+        tournament_team_id: firm.tournament_team_id(),
+        tournament_bot_id: firm.tournament_bot_id(),
+        pseudonym: firm_name(sp, tournament_id, firm).await?,
+    })
+}
 
-    if props.max_years <= 1 {
-        return Err(response::AppError::TournamentMaxYearsInvalid);
-    }
+async fn fill_tournament_collusion(
+    sp: &mut dyn StoreTransaction,
+    tournament: Tournament,
+    findings: collusion::Findings,
+) -> Result<response::TournamentCollusion, response::AppError> {
+    let years = findings
+        .years
+        .into_iter()
+        .map(|y| response::CollusionYear {
+            year: y.year,
+            total_quantity: y.total_quantity,
+            nash_quantity: y.nash_quantity,
+            monopoly_quantity: y.monopoly_quantity,
+            collusion_index: y.collusion_index,
+            near_monopoly: y.near_monopoly,
+        })
+        .collect();
 
-    if props.incentive_start_year <= 1 {
-        return Err(response::AppError::TournamentIncentiveStartYearInvalid);
+    let mut pairs = vec![];
+    for p in findings.pairs {
+        let (a, b) = p.firms;
+        pairs.push(response::CollusionPair {
+            firms: vec![
+                fill_collusion_firm(sp, tournament.tournament_id, a).await?,
+                fill_collusion_firm(sp, tournament.tournament_id, b).await?,
+            ],
+            years_together: p.years_together,
+            parallel_moves: p.parallel_moves,
+            opposite_moves: p.opposite_moves,
+            parallel: p.parallel,
+            defections: p.defections,
+            punishments: p.punishments,
+            punishing: p.punishing,
+            longest_cooperation: p.longest_cooperation,
+            sustained_cooperation: p.sustained_cooperation,
+        });
     }
 
-    let mut sp = con.transaction().await.map_err(report_postgres_err)?;
-
-    // create tournament
-    let tournament = tournament_service::add(
-        &mut sp,
-        user.user_id,
-        props.cost_per_unit,
-        props.demand_xintercept,
-        props.demand_yintercept,
-        props.incentive_multiplier,
-        props.incentive_start_year,
-        props.max_years,
-    )
-    .await
-    .map_err(report_postgres_err)?;
+    Ok(response::TournamentCollusion {
+        tournament: fill_tournament(sp, tournament).await?,
+        years,
+        longest_near_monopoly: findings.longest_near_monopoly,
+        sustained_near_monopoly: findings.sustained_near_monopoly,
+        pairs,
+    })
+}
 
-    // create tournament data
-    let tournament_data = tournament_data_service::add(
-        &mut sp,
-        user.user_id,
-        tournament.tournament_id,
-        props.title,
-        true,
-    )
-    .await
-    .map_err(report_postgres_err)?;
+fn fill_benchmark_outcome(outcome: market::Outcome) -> response::BenchmarkOutcome {
+    response::BenchmarkOutcome {
+        quantity: outcome.quantity,
+        total_quantity: outcome.total_quantity,
+        price: outcome.price,
+        profit: outcome.profit,
+    }
+}
 
-    // create year
-    tournament_year_service::add(&mut sp, user.user_id, tournament.tournament_id, 0)
-        .await
-        .map_err(report_postgres_err)?;
+async fn fill_tournament_final_result(
+    sp: &mut dyn StoreTransaction,
+    tournament_final_result: TournamentFinalResult,
+    viewer_user_id: i64,
+) -> Result<response::TournamentFinalResult, response::AppError> {
+    let tournament = sp
+        .tournament_get(tournament_final_result.tournament_id)
+        .await?
+        .ok_or(response::AppError::TournamentNonexistent)?;
+    let reveal = identity_filter(sp, tournament.tournament_id, viewer_user_id).await?;
 
-    sp.commit().await.map_err(report_postgres_err)?;
+    let pseudonym = firm_name(sp, tournament.tournament_id, tournament_final_result.firm()).await?;
 
-    // return json
-    fill_tournament_data(con, tournament_data).await
+    Ok(response::TournamentFinalResult {
+        tournament_final_result_id: tournament_final_result.tournament_final_result_id,
+        creation_time: tournament_final_result.creation_time,
+        creator_user_id: tournament_final_result.creator_user_id,
+        tournament: fill_tournament(sp, tournament).await?,
+        user_id: tournament_final_result.user_id.and_then(reveal),
+        tournament_team_id: tournament_final_result.tournament_team_id,
+        tournament_bot_id: tournament_final_result.tournament_bot_id,
+        pseudonym,
+        rank: tournament_final_result.rank,
+        balance: tournament_final_result.balance,
+    })
 }
 
-pub async fn tournament_data_new(
-    _config: Config,
-    db: Db,
-    auth_service: AuthService,
-    props: request::TournamentDataNewProps,
-) -> Result<response::TournamentData, response::AppError> {
-    // validate api key
-    let user = get_user_if_api_key_valid(&auth_service, props.api_key).await?;
-
-    let con = &mut *db.lock().await;
-
-    let mut sp = con.transaction().await.map_err(report_postgres_err)?;
+pub async fn get_user_if_api_key_valid(
+    auth_service: &Auth,
+    api_key: String,
+) -> Result<User, response::AppError> {
+    let user = auth_service.get_user_by_api_key_if_valid(api_key).await?;
+    tracing::debug!(user_id = user.user_id, "authenticated");
+    Ok(user)
+}
 
-    // ensure that tournament exists and belongs to you
-    let tournament = tournament_service::get_by_tournament_id(&mut sp, props.tournament_id)
-        .await
-        .map_err(report_postgres_err)?
+async fn get_tournament_state(
+    sp: &mut dyn StoreTransaction,
+    tournament_id: i64,
+) -> Result<TournamentStateKind, response::AppError> {
+    let tournament_state = sp
+        .tournament_state_get_recent(tournament_id)
+        .await?
         .ok_or(response::AppError::TournamentNonexistent)?;
-    // validate tournament is owned by correct user
-    if tournament.creator_user_id != user.user_id {
-        return Err(response::AppError::TournamentNonexistent);
-    }
+    Ok(tournament_state.state)
+}
 
-    // create tournament data
-    let tournament_data = tournament_data_service::add(
-        &mut sp,
-        user.user_id,
-        tournament.tournament_id,
-        props.title,
-        props.active,
+// the transitions a creator may make, archiving is possible from anywhere and can't be undone
+// finishing happens through tournament_final_result_new, so that there are always results
+fn is_valid_transition(from: TournamentStateKind, to: TournamentStateKind) -> bool {
+    use TournamentStateKind::*;
+    matches!(
+        (from, to),
+        (Lobby, Running)
+            | (Running, Paused)
+            | (Paused, Running)
+            | (Lobby, Archived)
+            | (Running, Archived)
+            | (Paused, Archived)
+            | (Finished, Archived)
     )
-    .await
-    .map_err(report_postgres_err)?;
-
-    sp.commit().await.map_err(report_postgres_err)?;
-
-    // return json
-    fill_tournament_data(con, tournament_data).await
 }
 
-pub async fn tournament_year_new(
-    _config: Config,
-    db: Db,
-    auth_service: AuthService,
-    props: request::TournamentYearNewProps,
-) -> Result<response::TournamentYear, response::AppError> {
-    // validate api key
-    let user = get_user_if_api_key_valid(&auth_service, props.api_key).await?;
-
-    let con = &mut *db.lock().await;
+// submissions and new years are only accepted while the game is being played
+fn ensure_running(state: TournamentStateKind) -> Result<(), response::AppError> {
+    match state {
+        TournamentStateKind::Running => Ok(()),
+        TournamentStateKind::Finished => Err(response::AppError::TournamentFinished),
+        TournamentStateKind::Archived => Err(response::AppError::TournamentArchived),
+        TournamentStateKind::Lobby | TournamentStateKind::Paused => {
+            Err(response::AppError::TournamentNotRunning)
+        }
+    }
+}
 
-    let mut sp = con.transaction().await.map_err(report_postgres_err)?;
+// the role a user currently holds in the tournament, if any
+async fn get_tournament_role(
+    sp: &mut dyn StoreTransaction,
+    tournament_id: i64,
+    user_id: i64,
+) -> Result<Option<TournamentRoleKind>, response::AppError> {
+    Ok(sp
+        .tournament_role_get_recent_by_tournament_user(tournament_id, user_id)
+        .await?
+        .filter(|r| r.active)
+        .map(|r| r.role))
+}
 
-    // ensure that tournament exists and belongs to you
-    let tournament = tournament_service::get_by_tournament_id(&mut sp, props.tournament_id)
-        .await
-        .map_err(report_postgres_err)?
-        .ok_or(response::AppError::TournamentNonexistent)?;
+async fn is_organizer(
+    sp: &mut dyn StoreTransaction,
+    tournament_id: i64,
+    user_id: i64,
+) -> Result<bool, response::AppError> {
+    Ok(matches!(
+        get_tournament_role(sp, tournament_id, user_id).await?,
+        Some(TournamentRoleKind::Owner | TournamentRoleKind::Organizer)
+    ))
+}
 
-    // validate tournament is owned by correct user
-    if tournament.creator_user_id != user.user_id {
-        return Err(response::AppError::TournamentNonexistent);
+// owners and organizers run the game, everyone else can't tell it's there to be run
+async fn ensure_organizer(
+    sp: &mut dyn StoreTransaction,
+    tournament_id: i64,
+    user_id: i64,
+) -> Result<(), response::AppError> {
+    match is_organizer(sp, tournament_id, user_id).await? {
+        true => Ok(()),
+        false => Err(response::AppError::TournamentNonexistent),
     }
+}
 
-    // validate tournament is still active
-    let tournament_data =
-        tournament_data_service::get_recent_by_tournament_id(&mut sp, props.tournament_id)
-            .await
-            .map_err(report_postgres_err)?
-            .ok_or(response::AppError::TournamentNonexistent)?;
+// Players only know each other by pseudonym, so the user ids behind them are left out
+// for everyone except whoever helps run the tournament. Your own id is never hidden from you.
+async fn identity_filter(
+    sp: &mut dyn StoreTransaction,
+    tournament_id: i64,
+    viewer_user_id: i64,
+) -> Result<impl Fn(i64) -> Option<i64>, response::AppError> {
+    let organizer = is_organizer(sp, tournament_id, viewer_user_id).await?;
+    Ok(move |user_id: i64| (organizer || user_id == viewer_user_id).then_some(user_id))
+}
 
-    if !tournament_data.active {
-        return Err(response::AppError::TournamentArchived);
+// Public tournaments can be looked at by anyone, the rest only by whoever helps run or watch them
+// and the members who were let in and haven't been put out since.
+// A practice game is only its student's business.
+async fn can_view(
+    sp: &mut dyn StoreTransaction,
+    tournament: &Tournament,
+    user_id: i64,
+) -> Result<bool, response::AppError> {
+    if tournament.visibility == VisibilityKind::Public && !tournament.practice {
+        return Ok(true);
     }
-
-    // get old tournament year
-    let tournament_year =
-        tournament_year_service::get_recent_by_tournament_id(&mut sp, props.tournament_id)
-            .await
-            .map_err(report_postgres_err)?
-            .ok_or(response::AppError::TournamentNonexistent)?;
-
-    // if max years hit
-    if tournament_year.current_year >= tournament.max_years {
-        return Err(response::AppError::TournamentMaxYearsAchieved);
+    if get_tournament_role(sp, tournament.tournament_id, user_id)
+        .await?
+        .is_some()
+    {
+        return Ok(true);
     }
+    Ok(sp
+        .tournament_membership_get_recent_by_tournament_user(tournament.tournament_id, user_id)
+        .await?
+        .is_some_and(|m| {
+            m.active && m.approval == ApprovalKind::Approved && !m.removed && !m.banned
+        }))
+}
 
-    // TODO: add other tournament submissions if they're not created yet
+async fn ensure_can_view(
+    sp: &mut dyn StoreTransaction,
+    tournament: &Tournament,
+    user_id: i64,
+) -> Result<(), response::AppError> {
+    match can_view(sp, tournament, user_id).await? {
+        true => Ok(()),
+        false => Err(response::AppError::Unauthorized),
+    }
+}
 
-    let mut users_who_didnt_submit = HashSet::new();
+// Views that can span several tournaments leave out the rows of those the viewer can't see.
+// Each tournament is only looked up once.
+struct Viewable {
+    user_id: i64,
+    checked: BTreeMap<i64, bool>,
+}
 
-    // add all members to a hashmap marked false for now
-    let memberships = tournament_membership_service::get_recent_by_tournament(
-        &mut sp,
-        tournament_data.tournament_id,
-    )
-    .await
-    .map_err(report_postgres_err)?;
+impl Viewable {
+    fn new(user_id: i64) -> Viewable {
+        Viewable {
+            user_id,
+            checked: BTreeMap::new(),
+        }
+    }
 
-    for membership in &memberships {
-        users_who_didnt_submit.insert(membership.creator_user_id);
+    async fn contains(
+        &mut self,
+        sp: &mut dyn StoreTransaction,
+        tournament_id: i64,
+    ) -> Result<bool, response::AppError> {
+        if let Some(&viewable) = self.checked.get(&tournament_id) {
+            return Ok(viewable);
+        }
+        let viewable = match sp.tournament_get(tournament_id).await? {
+            Some(tournament) => can_view(sp, &tournament, self.user_id).await?,
+            None => false,
+        };
+        self.checked.insert(tournament_id, viewable);
+        Ok(viewable)
     }
-    for submission in tournament_submission_service::get_recent_by_tournament(
-        &mut sp,
-        tournament_data.tournament_id,
-    )
-    .await
-    .map_err(report_postgres_err)?
+}
+
+// the response to an earlier request with the same idempotency key, if it's within the window
+async fn replay<T: DeserializeOwned>(
+    sp: &mut dyn StoreTransaction,
+    config: &Config,
+    user_id: i64,
+    endpoint: &str,
+    props: &impl request::Idempotent,
+) -> Result<Option<T>, response::AppError> {
+    let idempotency_key = match props.idempotency_key() {
+        Some(idempotency_key) => idempotency_key,
+        None => return Ok(None),
+    };
+    let min_creation_time = utils::current_time_millis() - config.idempotency_window_ms;
+    match sp
+        .idempotency_key_get_recent(user_id, endpoint, idempotency_key, min_creation_time)
+        .await?
     {
-        if submission.year == tournament_year.current_year {
-            users_who_didnt_submit.remove(&submission.creator_user_id);
+        // the key was used for something else, so the client is confused about what it sent
+        Some(k) if k.request_hash != props.fingerprint() => {
+            Err(response::AppError::IdempotencyKeyReused)
         }
+        Some(k) => serde_json::from_str(&k.response)
+            .map(Some)
+            .map_err(|_| response::AppError::InternalServerError),
+        None => Ok(None),
     }
+}
 
-    // autogenerate a submission if not submitted
-    for user_id in users_who_didnt_submit {
-        // create tournament submission
-        tournament_submission_service::add(
-            &mut sp,
+// saves the response in the same transaction as the change, so retries can be replayed
+async fn remember<T: Serialize>(
+    sp: &mut dyn StoreTransaction,
+    config: &Config,
+    user_id: i64,
+    endpoint: &str,
+    props: &impl request::Idempotent,
+    resp: &T,
+) -> Result<(), response::AppError> {
+    if let Some(idempotency_key) = props.idempotency_key() {
+        let response =
+            serde_json::to_string(resp).map_err(|_| response::AppError::InternalServerError)?;
+        let min_creation_time = utils::current_time_millis() - config.idempotency_window_ms;
+        sp.idempotency_key_add(
             user_id,
-            tournament.tournament_id,
-            tournament_year.current_year,
-            0,
-            true,
+            endpoint.to_owned(),
+            idempotency_key.to_owned(),
+            props.fingerprint(),
+            response,
+            min_creation_time,
         )
-        .await
-        .map_err(report_postgres_err)?;
+        .await?
+        // someone else got there first with the same key, probably the same request sent twice
+        .ok_or(response::AppError::IdempotencyKeyReused)?;
     }
+    Ok(())
+}
 
-    // create tournament data
-    let tournament_year = tournament_year_service::add(
-        &mut sp,
-        user.user_id,
-        tournament.tournament_id,
-        tournament_year.current_year + 1,
-    )
-    .await
-    .map_err(report_postgres_err)?;
+// forgets responses that are too old to be replayed, returning how many there were
+pub async fn prune_idempotency_keys(
+    db: &Db,
+    config: &Config,
+    now: i64,
+) -> Result<u64, response::AppError> {
+    let mut sp = db.begin().await?;
+    let pruned = sp
+        .idempotency_key_delete_expired(now - config.idempotency_window_ms)
+        .await?;
+    sp.commit().await?;
+    Ok(pruned)
+}
 
-    // generate the new demands for all members
-    let do_incentives = tournament_year.current_year >= tournament.incentive_start_year;
-    for membership in memberships {
-        let incentive = if do_incentives {
-            gen_random_incentive(tournament.incentive_multiplier)
-        } else {
-            0
-        };
+// members who left, only watch, or weren't let in don't take part in the market
+fn is_player(membership: &TournamentMembership) -> bool {
+    membership.active && !membership.observer && membership.approval == ApprovalKind::Approved
+}
 
-        tournament_year_demand_service::add(
-            &mut sp,
-            membership.creator_user_id,
-            tournament.tournament_id,
-            tournament_year.current_year,
-            incentive,
-        )
-        .await
-        .map_err(report_postgres_err)?;
+// members who joined before pseudonyms were handed out don't have one
+async fn get_pseudonym(
+    sp: &mut dyn StoreTransaction,
+    tournament_id: i64,
+    user_id: i64,
+) -> Result<Option<String>, response::AppError> {
+    Ok(sp
+        .tournament_pseudonym_get_by_tournament_user(tournament_id, user_id)
+        .await?
+        .map(|p| p.pseudonym))
+}
+
+// the names members and bots already go by
+async fn taken_names(
+    sp: &mut dyn StoreTransaction,
+    tournament_id: i64,
+) -> Result<BTreeSet<String>, response::AppError> {
+    let mut taken: BTreeSet<String> = sp
+        .tournament_pseudonym_get_by_tournament(tournament_id)
+        .await?
+        .into_iter()
+        .map(|p| p.pseudonym)
+        .collect();
+    for bot in sp.tournament_bot_get_by_tournament(tournament_id).await? {
+        taken.insert(bot.name);
     }
+    Ok(taken)
+}
 
-    sp.commit().await.map_err(report_postgres_err)?;
+// every year that is over, as seen by the analytics
+async fn analytics_years(
+    sp: &mut dyn StoreTransaction,
+    tournament: &Tournament,
+) -> Result<Vec<analytics::Year>, response::AppError> {
+    // the current year is still being played, unless the game is over
+    let tournament_year = sp
+        .tournament_year_get_recent(tournament.tournament_id)
+        .await?
+        .ok_or(response::AppError::TournamentNonexistent)?;
+    let last_year = match get_tournament_state(sp, tournament.tournament_id).await? {
+        TournamentStateKind::Finished | TournamentStateKind::Archived => {
+            tournament_year.current_year
+        }
+        _ => tournament_year.current_year - 1,
+    };
 
-    // return json
-    fill_tournament_year(con, tournament_year).await
+    let submissions = sp
+        .tournament_submission_get_recent_by_tournament(tournament.tournament_id)
+        .await?;
+    let demands = sp
+        .tournament_year_demand_get_by_tournament(tournament.tournament_id)
+        .await?;
+    Ok(analytics::years(
+        tournament,
+        &submissions,
+        &demands,
+        last_year,
+    ))
 }
 
-pub async fn tournament_membership_new(
-    _config: Config,
-    db: Db,
-    auth_service: AuthService,
-    props: request::TournamentMembershipNewProps,
-) -> Result<response::TournamentMembership, response::AppError> {
-    // validate api key
-    let user = get_user_if_api_key_valid(&auth_service, props.api_key).await?;
-
-    let con = &mut *db.lock().await;
+// what a firm is called in results, the player's pseudonym or the team's or bot's name
+async fn firm_name(
+    sp: &mut dyn StoreTransaction,
+    tournament_id: i64,
+    firm: Firm,
+) -> Result<Option<String>, response::AppError> {
+    Ok(match firm {
+        Firm::User(user_id) => get_pseudonym(sp, tournament_id, user_id).await?,
+        Firm::Team(tournament_team_id) => sp
+            .tournament_team_get(tournament_team_id)
+            .await?
+            .map(|t| t.name),
+        Firm::Bot(tournament_bot_id) => sp
+            .tournament_bot_get(tournament_bot_id)
+            .await?
+            .map(|b| b.name),
+    })
+}
 
-    let mut sp = con.transaction().await.map_err(report_postgres_err)?;
+// gives a new member a name nobody else in the tournament goes by
+// returns false if they already had one
+async fn add_pseudonym(
+    sp: &mut dyn StoreTransaction,
+    tournament_id: i64,
+    user_id: i64,
+) -> Result<bool, response::AppError> {
+    let taken = taken_names(sp, tournament_id).await?;
+    Ok(sp
+        .tournament_pseudonym_add(user_id, tournament_id, user_id, gen_pseudonym(&taken))
+        .await?
+        .is_some())
+}
 
-    // ensure that tournament exists and belongs to you
-    let tournament = tournament_service::get_by_tournament_id(&mut sp, props.tournament_id)
-        .await
-        .map_err(report_postgres_err)?
-        .ok_or(response::AppError::TournamentNonexistent)?;
+// how many people currently take part in the market
+async fn count_players(
+    sp: &mut dyn StoreTransaction,
+    tournament_id: i64,
+) -> Result<i64, response::AppError> {
+    let memberships = sp
+        .tournament_membership_get_recent_by_tournament(tournament_id)
+        .await?;
+    Ok(memberships.iter().filter(|m| is_player(m)).count() as i64)
+}
 
-    // cannot create a membership if you created the tournament
-    if tournament.creator_user_id == user.user_id {
-        return Err(response::AppError::TournamentMembershipInvalid);
+// checked right before someone becomes a player, observers and pending members don't take a seat
+async fn ensure_room(
+    sp: &mut dyn StoreTransaction,
+    tournament: &Tournament,
+) -> Result<(), response::AppError> {
+    if let Some(max_players) = tournament.max_players {
+        if count_players(sp, tournament.tournament_id).await? >= max_players {
+            return Err(response::AppError::TournamentFull);
+        }
     }
+    Ok(())
+}
 
-    // validate that the tournament isn't archived
-    let tournament_data =
-        tournament_data_service::get_recent_by_tournament_id(&mut sp, props.tournament_id)
-            .await
-            .map_err(report_postgres_err)?
-            .ok_or(response::AppError::TournamentNonexistent)?;
-
-    if !tournament_data.active {
-        return Err(response::AppError::TournamentArchived);
+// starts the game on behalf of the creator once enough players are in the lobby
+async fn maybe_auto_start(
+    sp: &mut dyn StoreTransaction,
+    tournament: &Tournament,
+) -> Result<(), response::AppError> {
+    let min_players = match tournament.min_players {
+        Some(min_players) if tournament.auto_start => min_players,
+        _ => return Ok(()),
+    };
+    if get_tournament_state(sp, tournament.tournament_id).await? != TournamentStateKind::Lobby {
+        return Ok(());
+    }
+    if count_players(sp, tournament.tournament_id).await? >= min_players {
+        tracing::debug!(tournament_id = tournament.tournament_id, "auto starting");
+        change_state(
+            sp,
+            tournament.creator_user_id,
+            tournament,
+            TournamentStateKind::Running,
+        )
+        .await?;
     }
+    Ok(())
+}
 
-    // also validate that we haven't started the game yet
-    let tournament_year =
-        tournament_year_service::get_recent_by_tournament_id(&mut sp, props.tournament_id)
-            .await
-            .map_err(report_postgres_err)?
-            .ok_or(response::AppError::TournamentNonexistent)?;
+// firms start with a demand for the current year
+// unless they already got one, from leaving and rejoining or from a teammate
+async fn add_current_demand(
+    sp: &mut dyn StoreTransaction,
+    tournament: &Tournament,
+    firm: Firm,
+) -> Result<(), response::AppError> {
+    let tournament_year = sp
+        .tournament_year_get_recent(tournament.tournament_id)
+        .await?
+        .ok_or(response::AppError::TournamentNonexistent)?;
 
-    if !tournament_year.current_year > 0 {
-        return Err(response::AppError::TournamentStarted);
+    let has_demand = sp
+        .tournament_year_demand_get_by_tournament(tournament.tournament_id)
+        .await?
+        .iter()
+        .any(|d| d.firm() == firm && d.year == tournament_year.current_year);
+    if has_demand {
+        return Ok(());
     }
 
-    // create tournament membership
-    let tournament_membership = tournament_membership_service::add(
-        &mut sp,
-        user.user_id,
-        tournament.tournament_id,
-        props.active,
-    )
-    .await
-    .map_err(report_postgres_err)?;
-
     // generate demand value
-    let incentive = if tournament.incentive_start_year <= 0 {
+    let incentive = if tournament_year.current_year >= tournament.incentive_start_year {
         gen_random_incentive(tournament.incentive_multiplier)
     } else {
         0
     };
 
-    // create demand for this year
-    tournament_year_demand_service::add(
-        &mut sp,
-        user.user_id,
+    sp.tournament_year_demand_add(
+        firm,
         tournament.tournament_id,
-        0,
+        tournament_year.current_year,
         incentive,
     )
-    .await
-    .map_err(report_postgres_err)?;
+    .await?;
 
-    sp.commit().await.map_err(report_postgres_err)?;
+    Ok(())
+}
 
-    // return json
-    fill_tournament_membership(con, tournament_membership).await
+// bots submit right before the year closes, so everyone else had their chance
+async fn play_bots(
+    sp: &mut dyn StoreTransaction,
+    tournament: &Tournament,
+    year: i64,
+    firms: &BTreeSet<Firm>,
+) -> Result<(), response::AppError> {
+    let bots = sp
+        .tournament_bot_get_by_tournament(tournament.tournament_id)
+        .await?;
+    if bots.is_empty() {
+        return Ok(());
+    }
+
+    let submissions = sp
+        .tournament_submission_get_recent_by_tournament(tournament.tournament_id)
+        .await?;
+    let total_incentive = sp
+        .tournament_year_demand_get_by_tournament(tournament.tournament_id)
+        .await?
+        .iter()
+        .filter(|d| d.year == year)
+        .map(|d| d.demand)
+        .sum();
+    let market = bots::Market::new(tournament, total_incentive, firms.len());
+
+    for bot in bots {
+        let firm = Firm::Bot(bot.tournament_bot_id);
+
+        // what everyone else produced together in each earlier year
+        let mut others = BTreeMap::new();
+        for s in submissions
+            .iter()
+            .filter(|s| s.year < year && s.firm() != firm)
+        {
+            *others.entry(s.year).or_insert(0) += s.amount;
+        }
+        let others: Vec<f64> = others.into_values().map(|x| x as f64).collect();
+
+        let amount = bots::decide(bot.strategy, bot.quantity, &market, &others);
+        sp.tournament_submission_add(
+            bot.creator_user_id,
+            firm,
+            tournament.tournament_id,
+            year,
+            amount,
+            true,
+        )
+        .await?;
+    }
+
+    Ok(())
 }
 
-pub async fn tournament_submission_new(
-    _config: Config,
-    db: Db,
-    auth_service: AuthService,
-    props: request::TournamentSubmissionNewProps,
-) -> Result<response::TournamentSubmission, response::AppError> {
-    // validate api key
-    let user = get_user_if_api_key_valid(&auth_service, props.api_key).await?;
+// lets the bots play, then gives every firm that didn't submit anything this year a submission of 0
+async fn close_year(
+    sp: &mut dyn StoreTransaction,
+    tournament: &Tournament,
+    year: i64,
+    memberships: &[TournamentMembership],
+) -> Result<(), response::AppError> {
+    let tournament_id = tournament.tournament_id;
+    let firms = market_firms(sp, tournament_id, memberships).await?;
+    play_bots(sp, tournament, year, &firms).await?;
+
+    // teams are filled in under their first player
+    let mut firms_who_didnt_submit = BTreeMap::new();
+    for membership in memberships.iter().filter(|m| is_player(m)) {
+        firms_who_didnt_submit
+            .entry(membership.firm())
+            .or_insert(membership.creator_user_id);
+    }
+    for submission in sp
+        .tournament_submission_get_recent_by_tournament(tournament_id)
+        .await?
+    {
+        if submission.year == year {
+            firms_who_didnt_submit.remove(&submission.firm());
+        }
+    }
 
-    let con = &mut *db.lock().await;
+    // autogenerate a submission if not submitted
+    for (firm, user_id) in firms_who_didnt_submit {
+        // create tournament submission
+        sp.tournament_submission_add(user_id, firm, tournament_id, year, 0, true)
+            .await?;
+    }
 
-    let mut sp = con.transaction().await.map_err(report_postgres_err)?;
+    Ok(())
+}
 
-    // ensure that tournament exists
-    let tournament = tournament_service::get_by_tournament_id(&mut sp, props.tournament_id)
-        .await
-        .map_err(report_postgres_err)?
+// every firm that still has someone playing for it
+fn player_firms(memberships: &[TournamentMembership]) -> BTreeSet<Firm> {
+    memberships
+        .iter()
+        .filter(|m| is_player(m))
+        .map(|m| m.firm())
+        .collect()
+}
+
+// every firm selling this year, bots play until the end
+async fn market_firms(
+    sp: &mut dyn StoreTransaction,
+    tournament_id: i64,
+    memberships: &[TournamentMembership],
+) -> Result<BTreeSet<Firm>, response::AppError> {
+    let mut firms = player_firms(memberships);
+    for bot in sp.tournament_bot_get_by_tournament(tournament_id).await? {
+        firms.insert(Firm::Bot(bot.tournament_bot_id));
+    }
+    Ok(firms)
+}
+
+// the bots every practice game comes with, students may add more with tournament_bot/new
+const PRACTICE_BOTS: [BotStrategyKind; 3] = [
+    BotStrategyKind::Nash,
+    BotStrategyKind::BestResponse,
+    BotStrategyKind::TitForTat,
+];
+
+// seats the creator and the bots, and starts the game
+async fn start_practice(
+    sp: &mut dyn StoreTransaction,
+    tournament: &Tournament,
+) -> Result<(), response::AppError> {
+    let user_id = tournament.creator_user_id;
+    let tournament_membership = sp
+        .tournament_membership_add(
+            user_id,
+            tournament.tournament_id,
+            true,
+            false,
+            ApprovalKind::Approved,
+            false,
+            false,
+            user_id,
+            None,
+            None,
+        )
+        .await?;
+    add_pseudonym(sp, tournament.tournament_id, user_id).await?;
+    add_current_demand(sp, tournament, tournament_membership.firm()).await?;
+
+    for strategy in PRACTICE_BOTS {
+        let taken = taken_names(sp, tournament.tournament_id).await?;
+        let tournament_bot = sp
+            .tournament_bot_add(
+                user_id,
+                tournament.tournament_id,
+                strategy,
+                None,
+                gen_pseudonym(&taken),
+            )
+            .await?;
+        add_current_demand(sp, tournament, Firm::Bot(tournament_bot.tournament_bot_id)).await?;
+    }
+
+    change_state(sp, user_id, tournament, TournamentStateKind::Running).await?;
+    Ok(())
+}
+
+// moves a tournament to a new state
+// years start counting down once the game starts, and pauses push the deadline back
+async fn change_state(
+    sp: &mut dyn StoreTransaction,
+    user_id: i64,
+    tournament: &Tournament,
+    state: TournamentStateKind,
+) -> Result<TournamentState, response::AppError> {
+    let previous = sp
+        .tournament_state_get_recent(tournament.tournament_id)
+        .await?
         .ok_or(response::AppError::TournamentNonexistent)?;
 
-    // validate user is member
-    let tournament_membership = tournament_membership_service::get_recent_by_tournament_user(
-        &mut sp,
-        props.tournament_id,
-        user.user_id,
-    )
-    .await
-    .map_err(report_postgres_err)?
-    .ok_or(response::AppError::Unauthorized)?;
+    let tournament_state = sp
+        .tournament_state_add(user_id, tournament.tournament_id, state)
+        .await?;
+    let now = tournament_state.creation_time;
 
-    // validate that the tournament isn't archived
-    let tournament_data =
-        tournament_data_service::get_recent_by_tournament_id(&mut sp, props.tournament_id)
-            .await
-            .map_err(report_postgres_err)?
+    if previous.state == TournamentStateKind::Paused {
+        sp.tournament_pause_add(
+            user_id,
+            tournament.tournament_id,
+            previous.creation_time,
+            now,
+        )
+        .await?;
+    }
+
+    if state == TournamentStateKind::Running {
+        let tournament_year = sp
+            .tournament_year_get_recent(tournament.tournament_id)
+            .await?
             .ok_or(response::AppError::TournamentNonexistent)?;
 
-    if !tournament_data.active {
-        return Err(response::AppError::TournamentArchived);
+        let deadline = match (previous.state, tournament_year.deadline) {
+            (TournamentStateKind::Paused, Some(deadline)) => {
+                Some(deadline + now - previous.creation_time)
+            }
+            (TournamentStateKind::Lobby, _) => tournament.year_duration_ms.map(|d| now + d),
+            _ => None,
+        };
+        if deadline.is_some() {
+            sp.tournament_year_add(
+                user_id,
+                tournament.tournament_id,
+                tournament_year.current_year,
+                deadline,
+            )
+            .await?;
+        }
     }
 
-    // get current year from tournament_data
-    let tournament_year =
-        tournament_year_service::get_recent_by_tournament_id(&mut sp, props.tournament_id)
-            .await
-            .map_err(report_postgres_err)?
-            .ok_or(response::AppError::TournamentNonexistent)?;
+    Ok(tournament_state)
+}
 
-    // create tournament submission
-    let tournament_submission = tournament_submission_service::add(
-        &mut sp,
-        user.user_id,
-        tournament.tournament_id,
-        tournament_year.current_year,
-        props.amount,
-        false,
-    )
-    .await
-    .map_err(report_postgres_err)?;
+// closes the current year and starts the next one
+async fn advance_year(
+    sp: &mut dyn StoreTransaction,
+    user_id: i64,
+    tournament: &Tournament,
+    tournament_year: &TournamentYear,
+) -> Result<TournamentYear, response::AppError> {
+    let memberships = sp
+        .tournament_membership_get_recent_by_tournament(tournament.tournament_id)
+        .await?;
 
-    sp.commit().await.map_err(report_postgres_err)?;
+    close_year(sp, tournament, tournament_year.current_year, &memberships).await?;
 
-    // return json
-    fill_tournament_submission(con, tournament_submission).await
-}
+    // create tournament data
+    let tournament_year = sp
+        .tournament_year_add(
+            user_id,
+            tournament.tournament_id,
+            tournament_year.current_year + 1,
+            tournament
+                .year_duration_ms
+                .map(|d| utils::current_time_millis() + d),
+        )
+        .await?;
 
-pub async fn tournament_data_view(
-    _config: Config,
-    db: Db,
-    _auth_service: AuthService,
-    props: request::TournamentDataViewProps,
-) -> Result<Vec<response::TournamentData>, response::AppError> {
-    let con = &mut *db.lock().await;
-    // get users
-    let tournament_data = tournament_data_service::query(con, props)
-        .await
-        .map_err(report_postgres_err)?;
+    // generate the new demands for all firms
+    let do_incentives = tournament_year.current_year >= tournament.incentive_start_year;
+    for firm in market_firms(sp, tournament.tournament_id, &memberships).await? {
+        let incentive = if do_incentives {
+            gen_random_incentive(tournament.incentive_multiplier)
+        } else {
+            0
+        };
 
-    // return tournament_datas
-    let mut resp_tournament_datas = vec![];
-    for u in tournament_data.into_iter() {
-        resp_tournament_datas.push(fill_tournament_data(con, u).await?);
+        sp.tournament_year_demand_add(
+            firm,
+            tournament.tournament_id,
+            tournament_year.current_year,
+            incentive,
+        )
+        .await?;
     }
 
-    Ok(resp_tournament_datas)
+    Ok(tournament_year)
 }
 
-pub async fn tournament_membership_view(
-    _config: Config,
-    db: Db,
-    _auth_service: AuthService,
-    props: request::TournamentMembershipViewProps,
-) -> Result<Vec<response::TournamentMembership>, response::AppError> {
-    let con = &mut *db.lock().await;
-    // get users
-    let tournament_membership = tournament_membership_service::query(con, props)
+// closes the last year, then ranks everyone and finishes the tournament
+async fn finalize(
+    sp: &mut dyn StoreTransaction,
+    user_id: i64,
+    tournament: &Tournament,
+) -> Result<Vec<TournamentFinalResult>, response::AppError> {
+    // the current year is the last one, usually max_years unless the game is ended early
+    let tournament_year = sp
+        .tournament_year_get_recent(tournament.tournament_id)
+        .await?
+        .ok_or(response::AppError::TournamentNonexistent)?;
+
+    let memberships = sp
+        .tournament_membership_get_recent_by_tournament(tournament.tournament_id)
+        .await?;
+
+    close_year(sp, tournament, tournament_year.current_year, &memberships).await?;
+
+    let submissions = sp
+        .tournament_submission_get_recent_by_tournament(tournament.tournament_id)
+        .await?;
+    let demands = sp
+        .tournament_year_demand_get_by_tournament(tournament.tournament_id)
+        .await?;
+
+    // firms that never sold anything still get a place
+    let firms = market_firms(sp, tournament.tournament_id, &memberships).await?;
+    let mut balances = market::balances(tournament, &submissions, &demands);
+    for firm in firms.iter() {
+        balances.entry(*firm).or_insert(0.0);
+    }
+
+    // players who left lose their place, unless the tournament lets them keep it
+    // players who were removed always lose it
+    // teams keep theirs as long as someone still plays for them
+    for membership in memberships.iter().filter(|m| !m.active) {
+        if firms.contains(&membership.firm()) {
+            continue;
+        }
+        if membership.removed || tournament.exit_policy == ExitPolicyKind::Forfeit {
+            balances.remove(&membership.firm());
+        }
+    }
+
+    for (firm, rank, balance) in market::rank(&balances) {
+        sp.tournament_final_result_add(user_id, tournament.tournament_id, firm, rank, balance)
+            .await?;
+    }
+
+    change_state(sp, user_id, tournament, TournamentStateKind::Finished).await?;
+
+    sp.tournament_final_result_get_by_tournament(tournament.tournament_id)
         .await
-        .map_err(report_postgres_err)?;
+}
 
-    // return tournament_memberships
-    let mut resp_tournament_memberships = vec![];
-    for u in tournament_membership.into_iter() {
-        resp_tournament_memberships.push(fill_tournament_membership(con, u).await?);
+const PSEUDONYM_COLORS: [&str; 16] = [
+    "Amber", "Azure", "Cedar", "Cobalt", "Coral", "Crimson", "Ivory", "Jade", "Lilac", "Ochre",
+    "Olive", "Onyx", "Pearl", "Saffron", "Slate", "Teal",
+];
+
+// "Firm Amber", starting from a random color, and numbered once every color is taken
+fn gen_pseudonym(taken: &BTreeSet<String>) -> String {
+    let start = utils::random_number(0, PSEUDONYM_COLORS.len() as i64) as usize;
+    let mut round = 1;
+    loop {
+        for i in 0..PSEUDONYM_COLORS.len() {
+            let color = PSEUDONYM_COLORS[(start + i) % PSEUDONYM_COLORS.len()];
+            let pseudonym = match round {
+                1 => format!("Firm {}", color),
+                _ => format!("Firm {} {}", color, round),
+            };
+            if !taken.contains(&pseudonym) {
+                return pseudonym;
+            }
+        }
+        round += 1;
     }
+}
 
-    Ok(resp_tournament_memberships)
+// generate 2 random numbers between -|m| and |m|, pick the one farthest from zero
+fn gen_random_incentive(m: i64) -> i64 {
+    if m == 0 {
+        return 0;
+    }
+    let absmul = m.abs();
+    let r1 = utils::random_number(-absmul, absmul);
+    let r2 = utils::random_number(-absmul, absmul);
+    if r1.abs() > r2.abs() {
+        return r1;
+    } else {
+        return r2;
+    }
 }
 
-pub async fn tournament_submission_view(
-    _config: Config,
+#[tracing::instrument(skip_all)]
+pub async fn tournament_new(
+    config: Config,
     db: Db,
-    _auth_service: AuthService,
-    props: request::TournamentSubmissionViewProps,
-) -> Result<Vec<response::TournamentSubmission>, response::AppError> {
-    let con = &mut *db.lock().await;
-    // get users
-    let tournament_submission = tournament_submission_service::query(con, props)
-        .await
-        .map_err(report_postgres_err)?;
+    auth_service: Auth,
+    props: request::TournamentNewProps,
+) -> Result<response::TournamentData, response::AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
 
-    // return tournament_submissions
-    let mut resp_tournament_submissions = vec![];
-    for u in tournament_submission.into_iter() {
-        resp_tournament_submissions.push(fill_tournament_submission(con, u).await?);
+    if props.max_years <= 1 {
+        return Err(response::AppError::TournamentMaxYearsInvalid);
     }
 
-    Ok(resp_tournament_submissions)
+    if props.incentive_start_year <= 1 {
+        return Err(response::AppError::TournamentIncentiveStartYearInvalid);
+    }
+
+    if props.year_duration_ms.is_some_and(|d| d <= 0) {
+        return Err(response::AppError::TournamentYearDurationInvalid);
+    }
+
+    // starting on its own needs to know when there are enough players
+    let auto_start = props.auto_start.unwrap_or(false);
+    match (props.min_players, props.max_players) {
+        (Some(min), _) if min <= 0 => return Err(response::AppError::TournamentPlayerCountInvalid),
+        (_, Some(max)) if max <= 0 => return Err(response::AppError::TournamentPlayerCountInvalid),
+        (Some(min), Some(max)) if min > max => {
+            return Err(response::AppError::TournamentPlayerCountInvalid)
+        }
+        (None, _) if auto_start => return Err(response::AppError::TournamentPlayerCountInvalid),
+        _ => {}
+    }
+
+    // practice games are for the creator alone, and start right away
+    let practice = props.practice.unwrap_or(false);
+    if practice && (props.min_players.is_some() || props.max_players.is_some() || auto_start) {
+        return Err(response::AppError::TournamentPlayerCountInvalid);
+    }
+    if practice
+        && props
+            .visibility
+            .is_some_and(|v| v != VisibilityKind::Public)
+    {
+        return Err(response::AppError::TournamentVisibilityInvalid);
+    }
+
+    let mut sp = db.begin().await?;
+
+    // a retry of a request that already went through
+    if let Some(resp) = replay(&mut *sp, &config, user.user_id, "tournament/new", &props).await? {
+        return Ok(resp);
+    }
+
+    // create tournament
+    let tournament = sp
+        .tournament_add(
+            user.user_id,
+            props.cost_per_unit,
+            props.demand_xintercept,
+            props.demand_yintercept,
+            props.incentive_multiplier,
+            props.incentive_start_year,
+            props.max_years,
+            props.year_duration_ms,
+            props
+                .late_join_policy
+                .unwrap_or(LateJoinPolicyKind::Forbidden),
+            props.exit_policy.unwrap_or(ExitPolicyKind::KeepBalance),
+            props.visibility.unwrap_or(VisibilityKind::Public),
+            props.min_players,
+            props.max_players,
+            auto_start,
+            practice,
+        )
+        .await?;
+
+    // create tournament data
+    let tournament_data = sp
+        .tournament_data_add(
+            user.user_id,
+            tournament.tournament_id,
+            props.title.clone(),
+            true,
+        )
+        .await?;
+
+    // create year
+    sp.tournament_year_add(user.user_id, tournament.tournament_id, 0, None)
+        .await?;
+
+    // players can join until the creator starts the game
+    sp.tournament_state_add(
+        user.user_id,
+        tournament.tournament_id,
+        TournamentStateKind::Lobby,
+    )
+    .await?;
+
+    // the creator owns the tournament, and may share running it with others
+    sp.tournament_role_add(
+        user.user_id,
+        tournament.tournament_id,
+        user.user_id,
+        TournamentRoleKind::Owner,
+        true,
+    )
+    .await?;
+
+    // invite only tournaments start out with a code to hand out
+    if tournament.visibility == VisibilityKind::InviteCode {
+        sp.tournament_invite_code_add(
+            user.user_id,
+            tournament.tournament_id,
+            utils::random_string(),
+        )
+        .await?;
+    }
+
+    if tournament.practice {
+        start_practice(&mut *sp, &tournament).await?;
+    }
+
+    // return json
+    let resp = fill_tournament_data(&mut *sp, tournament_data).await?;
+
+    remember(
+        &mut *sp,
+        &config,
+        user.user_id,
+        "tournament/new",
+        &props,
+        &resp,
+    )
+    .await?;
+
+    sp.commit().await?;
+
+    Ok(resp)
 }
 
-pub async fn tournament_year_view(
-    _config: Config,
+#[tracing::instrument(skip_all, fields(tournament_id = props.tournament_id))]
+pub async fn tournament_data_new(
+    config: Config,
     db: Db,
-    _auth_service: AuthService,
-    props: request::TournamentYearViewProps,
-) -> Result<Vec<response::TournamentYear>, response::AppError> {
-    let con = &mut *db.lock().await;
-    // get users
-    let tournament_year = tournament_year_service::query(con, props)
-        .await
-        .map_err(report_postgres_err)?;
+    auth_service: Auth,
+    props: request::TournamentDataNewProps,
+) -> Result<response::TournamentData, response::AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
 
-    // return tournament_years
-    let mut resp_tournament_years = vec![];
-    for u in tournament_year.into_iter() {
-        resp_tournament_years.push(fill_tournament_year(con, u).await?);
+    let mut sp = db.begin().await?;
+
+    // a retry of a request that already went through
+    if let Some(resp) = replay(
+        &mut *sp,
+        &config,
+        user.user_id,
+        "tournament_data/new",
+        &props,
+    )
+    .await?
+    {
+        return Ok(resp);
     }
 
-    Ok(resp_tournament_years)
+    // ensure that tournament exists and you help run it
+    let tournament = sp
+        .tournament_get(props.tournament_id)
+        .await?
+        .ok_or(response::AppError::TournamentNonexistent)?;
+    ensure_organizer(&mut *sp, tournament.tournament_id, user.user_id).await?;
+
+    // archived tournaments can't be edited or brought back
+    let state = get_tournament_state(&mut *sp, tournament.tournament_id).await?;
+    if state == TournamentStateKind::Archived {
+        return Err(response::AppError::TournamentArchived);
+    }
+
+    // create tournament data
+    let tournament_data = sp
+        .tournament_data_add(
+            user.user_id,
+            tournament.tournament_id,
+            props.title.clone(),
+            props.active,
+        )
+        .await?;
+
+    // deactivating a tournament archives it
+    if !props.active {
+        change_state(
+            &mut *sp,
+            user.user_id,
+            &tournament,
+            TournamentStateKind::Archived,
+        )
+        .await?;
+    }
+
+    // return json
+    let resp = fill_tournament_data(&mut *sp, tournament_data).await?;
+
+    remember(
+        &mut *sp,
+        &config,
+        user.user_id,
+        "tournament_data/new",
+        &props,
+        &resp,
+    )
+    .await?;
+
+    sp.commit().await?;
+
+    Ok(resp)
 }
 
-pub async fn tournament_year_demand_view(
-    _config: Config,
+#[tracing::instrument(skip_all, fields(tournament_id = props.tournament_id))]
+pub async fn tournament_state_new(
+    config: Config,
     db: Db,
-    _auth_service: AuthService,
-    props: request::TournamentYearDemandViewProps,
-) -> Result<Vec<response::TournamentYearDemand>, response::AppError> {
-    let con = &mut *db.lock().await;
-    // get users
-    let tournament_year_demand = tournament_year_demand_service::query(con, props)
-        .await
-        .map_err(report_postgres_err)?;
+    auth_service: Auth,
+    props: request::TournamentStateNewProps,
+) -> Result<response::TournamentState, response::AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
 
-    // return tournament_year_demands
-    let mut resp_tournament_year_demands = vec![];
-    for u in tournament_year_demand.into_iter() {
-        resp_tournament_year_demands.push(fill_tournament_year_demand(con, u).await?);
+    let mut sp = db.begin().await?;
+
+    // a retry of a request that already went through
+    if let Some(resp) = replay(
+        &mut *sp,
+        &config,
+        user.user_id,
+        "tournament_state/new",
+        &props,
+    )
+    .await?
+    {
+        return Ok(resp);
     }
 
-    Ok(resp_tournament_year_demands)
+    // ensure that tournament exists and you help run it
+    let tournament = sp
+        .tournament_get(props.tournament_id)
+        .await?
+        .ok_or(response::AppError::TournamentNonexistent)?;
+    ensure_organizer(&mut *sp, tournament.tournament_id, user.user_id).await?;
+
+    let state = get_tournament_state(&mut *sp, tournament.tournament_id).await?;
+    if state == TournamentStateKind::Archived {
+        return Err(response::AppError::TournamentArchived);
+    }
+    if !is_valid_transition(state, props.state) {
+        return Err(response::AppError::TournamentStateTransitionInvalid);
+    }
+
+    // the game can't start until enough players have joined
+    if let (TournamentStateKind::Lobby, TournamentStateKind::Running, Some(min_players)) =
+        (state, props.state, tournament.min_players)
+    {
+        if count_players(&mut *sp, tournament.tournament_id).await? < min_players {
+            return Err(response::AppError::TournamentNotEnoughPlayers);
+        }
+    }
+
+    let tournament_state = change_state(&mut *sp, user.user_id, &tournament, props.state).await?;
+
+    // archived tournaments are also hidden
+    if props.state == TournamentStateKind::Archived {
+        let tournament_data = sp
+            .tournament_data_get_recent(tournament.tournament_id)
+            .await?
+            .ok_or(response::AppError::TournamentNonexistent)?;
+        sp.tournament_data_add(
+            user.user_id,
+            tournament.tournament_id,
+            tournament_data.title,
+            false,
+        )
+        .await?;
+    }
+
+    // return json
+    let resp = fill_tournament_state(&mut *sp, tournament_state).await?;
+
+    remember(
+        &mut *sp,
+        &config,
+        user.user_id,
+        "tournament_state/new",
+        &props,
+        &resp,
+    )
+    .await?;
+
+    sp.commit().await?;
+
+    Ok(resp)
+}
+
+#[tracing::instrument(skip_all, fields(tournament_id = props.tournament_id))]
+pub async fn tournament_year_new(
+    config: Config,
+    db: Db,
+    auth_service: Auth,
+    props: request::TournamentYearNewProps,
+) -> Result<response::TournamentYear, response::AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
+
+    let start = Instant::now();
+
+    let mut sp = db.begin().await?;
+
+    // a retry of a request that already went through
+    if let Some(resp) = replay(
+        &mut *sp,
+        &config,
+        user.user_id,
+        "tournament_year/new",
+        &props,
+    )
+    .await?
+    {
+        return Ok(resp);
+    }
+
+    // ensure that tournament exists and you help run it
+    let tournament = sp
+        .tournament_get(props.tournament_id)
+        .await?
+        .ok_or(response::AppError::TournamentNonexistent)?;
+    ensure_organizer(&mut *sp, tournament.tournament_id, user.user_id).await?;
+
+    // validate tournament is being played
+    ensure_running(get_tournament_state(&mut *sp, props.tournament_id).await?)?;
+
+    // get old tournament year
+    let tournament_year = sp
+        .tournament_year_get_recent(props.tournament_id)
+        .await?
+        .ok_or(response::AppError::TournamentNonexistent)?;
+
+    // if max years hit
+    if tournament_year.current_year >= tournament.max_years {
+        return Err(response::AppError::TournamentMaxYearsAchieved);
+    }
+
+    let tournament_year =
+        advance_year(&mut *sp, user.user_id, &tournament, &tournament_year).await?;
+
+    // return json
+    let resp = fill_tournament_year(&mut *sp, tournament_year).await?;
+
+    remember(
+        &mut *sp,
+        &config,
+        user.user_id,
+        "tournament_year/new",
+        &props,
+        &resp,
+    )
+    .await?;
+
+    sp.commit().await?;
+
+    metrics::record_year_advance(start.elapsed());
+
+    Ok(resp)
+}
+
+#[tracing::instrument(skip_all, fields(tournament_id = props.tournament_id))]
+pub async fn tournament_membership_new(
+    config: Config,
+    db: Db,
+    auth_service: Auth,
+    props: request::TournamentMembershipNewProps,
+) -> Result<response::TournamentMembership, response::AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
+
+    let mut sp = db.begin().await?;
+
+    // a retry of a request that already went through
+    if let Some(resp) = replay(
+        &mut *sp,
+        &config,
+        user.user_id,
+        "tournament_membership/new",
+        &props,
+    )
+    .await?
+    {
+        return Ok(resp);
+    }
+
+    // ensure that tournament exists and belongs to you
+    let tournament = sp
+        .tournament_get(props.tournament_id)
+        .await?
+        .ok_or(response::AppError::TournamentNonexistent)?;
+
+    // nobody joins someone else's practice game
+    if tournament.practice && tournament.creator_user_id != user.user_id {
+        return Err(response::AppError::TournamentPractice);
+    }
+
+    // cannot create a membership if you created or help run the tournament
+    if tournament.creator_user_id == user.user_id
+        || get_tournament_role(&mut *sp, tournament.tournament_id, user.user_id)
+            .await?
+            .is_some()
+    {
+        return Err(response::AppError::TournamentMembershipInvalid);
+    }
+
+    let state = get_tournament_state(&mut *sp, props.tournament_id).await?;
+    match state {
+        TournamentStateKind::Finished => return Err(response::AppError::TournamentFinished),
+        TournamentStateKind::Archived => return Err(response::AppError::TournamentArchived),
+        _ => {}
+    }
+
+    let previous_membership = sp
+        .tournament_membership_get_recent_by_tournament_user(props.tournament_id, user.user_id)
+        .await?;
+
+    // joining twice or leaving twice changes nothing
+    match &previous_membership {
+        Some(m) if m.active == props.active => {
+            let resp = fill_tournament_membership(&mut *sp, m.clone(), user.user_id).await?;
+            sp.commit().await?;
+            return Ok(resp);
+        }
+        None if !props.active => return Err(response::AppError::TournamentMembershipInvalid),
+        _ => {}
+    }
+
+    // turned away members can't ask again, unless an organizer changes their mind
+    if let Some(m) = &previous_membership {
+        if m.banned {
+            return Err(response::AppError::TournamentMembershipBanned);
+        }
+        if m.removed {
+            return Err(response::AppError::TournamentMembershipRemoved);
+        }
+        if m.approval == ApprovalKind::Rejected {
+            return Err(response::AppError::TournamentMembershipRejected);
+        }
+    }
+
+    // joining for the first time is up to who the tournament lets in
+    if previous_membership.is_none() {
+        match tournament.visibility {
+            VisibilityKind::Public => {}
+            VisibilityKind::InviteCode => {
+                let invite_code = sp
+                    .tournament_invite_code_get_recent(tournament.tournament_id)
+                    .await?;
+                match (invite_code, &props.invite_code) {
+                    (Some(invite_code), Some(code)) if &invite_code.invite_code == code => {}
+                    _ => return Err(response::AppError::TournamentInviteCodeInvalid),
+                }
+            }
+            // organizers only let players in before the game starts
+            VisibilityKind::ApprovalRequired => {
+                if state != TournamentStateKind::Lobby {
+                    return Err(response::AppError::TournamentStarted);
+                }
+            }
+        }
+    }
+
+    // players pick their team when they first join, and stay on it
+    let tournament_team_id = match (&previous_membership, props.tournament_team_id) {
+        (Some(m), Some(team_id)) if m.tournament_team_id != Some(team_id) => {
+            return Err(response::AppError::TournamentTeamInvalid)
+        }
+        (Some(m), _) => m.tournament_team_id,
+        (None, Some(team_id)) => match sp.tournament_team_get(team_id).await? {
+            Some(team) if team.tournament_id == tournament.tournament_id => Some(team_id),
+            _ => return Err(response::AppError::TournamentTeamInvalid),
+        },
+        (None, None) => None,
+    };
+
+    // leaving is always possible, what happens to the balance is up to the exit policy
+    // rejoining picks up where you left off, as player or observer
+    // joining for the first time after the start is up to the late join policy
+    let first_approval = match tournament.visibility {
+        VisibilityKind::ApprovalRequired => ApprovalKind::Pending,
+        _ => ApprovalKind::Approved,
+    };
+    let (observer, approval) = match &previous_membership {
+        Some(m) => (m.observer, m.approval),
+        None if state == TournamentStateKind::Lobby => (false, first_approval),
+        None => match tournament.late_join_policy {
+            LateJoinPolicyKind::Forbidden => return Err(response::AppError::TournamentStarted),
+            LateJoinPolicyKind::CatchUp => (false, first_approval),
+            LateJoinPolicyKind::Observer => (true, first_approval),
+        },
+    };
+
+    if props.active && !observer && approval == ApprovalKind::Approved {
+        ensure_room(&mut *sp, &tournament).await?;
+    }
+
+    // other players only ever see the pseudonym
+    // it's handed out before anything else, so of two first joins at once only one goes through
+    if previous_membership.is_none()
+        && !add_pseudonym(&mut *sp, tournament.tournament_id, user.user_id).await?
+    {
+        // the other one already let them in, which is the same as joining twice
+        drop(sp);
+        let mut sp = db.begin().await?;
+        let tournament_membership = sp
+            .tournament_membership_get_recent_by_tournament_user(
+                tournament.tournament_id,
+                user.user_id,
+            )
+            .await?
+            .ok_or(response::AppError::InternalServerError)?;
+        let resp =
+            fill_tournament_membership(&mut *sp, tournament_membership, user.user_id).await?;
+        sp.commit().await?;
+        return Ok(resp);
+    }
+
+    // create tournament membership
+    let tournament_membership = sp
+        .tournament_membership_add(
+            user.user_id,
+            tournament.tournament_id,
+            props.active,
+            observer,
+            approval,
+            false,
+            false,
+            user.user_id,
+            None,
+            tournament_team_id,
+        )
+        .await?;
+
+    if is_player(&tournament_membership) {
+        add_current_demand(&mut *sp, &tournament, tournament_membership.firm()).await?;
+        maybe_auto_start(&mut *sp, &tournament).await?;
+    }
+
+    // return json
+    let resp = fill_tournament_membership(&mut *sp, tournament_membership, user.user_id).await?;
+
+    remember(
+        &mut *sp,
+        &config,
+        user.user_id,
+        "tournament_membership/new",
+        &props,
+        &resp,
+    )
+    .await?;
+
+    sp.commit().await?;
+
+    Ok(resp)
+}
+
+#[tracing::instrument(skip_all, fields(tournament_id = props.tournament_id))]
+pub async fn tournament_membership_approval_new(
+    config: Config,
+    db: Db,
+    auth_service: Auth,
+    props: request::TournamentMembershipApprovalNewProps,
+) -> Result<response::TournamentMembership, response::AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
+
+    let mut sp = db.begin().await?;
+
+    // a retry of a request that already went through
+    if let Some(resp) = replay(
+        &mut *sp,
+        &config,
+        user.user_id,
+        "tournament_membership_approval/new",
+        &props,
+    )
+    .await?
+    {
+        return Ok(resp);
+    }
+
+    // ensure that tournament exists and you help run it
+    let tournament = sp
+        .tournament_get(props.tournament_id)
+        .await?
+        .ok_or(response::AppError::TournamentNonexistent)?;
+    ensure_organizer(&mut *sp, tournament.tournament_id, user.user_id).await?;
+
+    // players are let in before the game starts
+    match get_tournament_state(&mut *sp, tournament.tournament_id).await? {
+        TournamentStateKind::Lobby => {}
+        TournamentStateKind::Finished => return Err(response::AppError::TournamentFinished),
+        TournamentStateKind::Archived => return Err(response::AppError::TournamentArchived),
+        _ => return Err(response::AppError::TournamentStarted),
+    }
+
+    // only members who haven't been let in yet wait for a decision
+    let tournament_membership = sp
+        .tournament_membership_get_recent_by_tournament_user(props.tournament_id, props.user_id)
+        .await?
+        .ok_or(response::AppError::TournamentMembershipInvalid)?;
+    if tournament_membership.approval == ApprovalKind::Approved {
+        return Err(response::AppError::TournamentMembershipInvalid);
+    }
+
+    // turning someone away also ends their membership
+    let (active, approval) = if props.approved {
+        (tournament_membership.active, ApprovalKind::Approved)
+    } else {
+        (false, ApprovalKind::Rejected)
+    };
+    if active && !tournament_membership.observer && approval == ApprovalKind::Approved {
+        ensure_room(&mut *sp, &tournament).await?;
+    }
+
+    // memberships are always recorded under the member
+    let tournament_membership = sp
+        .tournament_membership_add(
+            props.user_id,
+            tournament.tournament_id,
+            active,
+            tournament_membership.observer,
+            approval,
+            tournament_membership.removed,
+            tournament_membership.banned,
+            user.user_id,
+            None,
+            tournament_membership.tournament_team_id,
+        )
+        .await?;
+
+    if is_player(&tournament_membership) {
+        add_current_demand(&mut *sp, &tournament, tournament_membership.firm()).await?;
+        maybe_auto_start(&mut *sp, &tournament).await?;
+    }
+
+    // return json
+    let resp = fill_tournament_membership(&mut *sp, tournament_membership, user.user_id).await?;
+
+    remember(
+        &mut *sp,
+        &config,
+        user.user_id,
+        "tournament_membership_approval/new",
+        &props,
+        &resp,
+    )
+    .await?;
+
+    sp.commit().await?;
+
+    Ok(resp)
+}
+
+#[tracing::instrument(skip_all, fields(tournament_id = props.tournament_id))]
+pub async fn tournament_membership_action_new(
+    config: Config,
+    db: Db,
+    auth_service: Auth,
+    props: request::TournamentMembershipActionNewProps,
+) -> Result<response::TournamentMembership, response::AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
+
+    let mut sp = db.begin().await?;
+
+    // a retry of a request that already went through
+    if let Some(resp) = replay(
+        &mut *sp,
+        &config,
+        user.user_id,
+        "tournament_membership_action/new",
+        &props,
+    )
+    .await?
+    {
+        return Ok(resp);
+    }
+
+    // ensure that tournament exists and you help run it
+    let tournament = sp
+        .tournament_get(props.tournament_id)
+        .await?
+        .ok_or(response::AppError::TournamentNonexistent)?;
+    ensure_organizer(&mut *sp, tournament.tournament_id, user.user_id).await?;
+
+    // the results are final once the game is over
+    match get_tournament_state(&mut *sp, tournament.tournament_id).await? {
+        TournamentStateKind::Finished => return Err(response::AppError::TournamentFinished),
+        TournamentStateKind::Archived => return Err(response::AppError::TournamentArchived),
+        _ => {}
+    }
+
+    let tournament_membership = sp
+        .tournament_membership_get_recent_by_tournament_user(props.tournament_id, props.user_id)
+        .await?
+        .ok_or(response::AppError::TournamentMembershipInvalid)?;
+
+    // (active, removed, banned) after the action
+    let (active, removed, banned) = match props.action {
+        request::MembershipActionKind::Remove if !tournament_membership.removed => {
+            (false, true, false)
+        }
+        request::MembershipActionKind::Ban if !tournament_membership.banned => (false, true, true),
+        request::MembershipActionKind::Reinstate if tournament_membership.removed => {
+            (true, false, false)
+        }
+        _ => return Err(response::AppError::TournamentMembershipInvalid),
+    };
+    if active
+        && !tournament_membership.observer
+        && tournament_membership.approval == ApprovalKind::Approved
+    {
+        ensure_room(&mut *sp, &tournament).await?;
+    }
+
+    // memberships are always recorded under the member
+    let tournament_membership = sp
+        .tournament_membership_add(
+            props.user_id,
+            tournament.tournament_id,
+            active,
+            tournament_membership.observer,
+            tournament_membership.approval,
+            removed,
+            banned,
+            user.user_id,
+            props.reason.clone(),
+            tournament_membership.tournament_team_id,
+        )
+        .await?;
+
+    // reinstated players catch up from the current year
+    if is_player(&tournament_membership) {
+        add_current_demand(&mut *sp, &tournament, tournament_membership.firm()).await?;
+        maybe_auto_start(&mut *sp, &tournament).await?;
+    }
+
+    // return json
+    let resp = fill_tournament_membership(&mut *sp, tournament_membership, user.user_id).await?;
+
+    remember(
+        &mut *sp,
+        &config,
+        user.user_id,
+        "tournament_membership_action/new",
+        &props,
+        &resp,
+    )
+    .await?;
+
+    sp.commit().await?;
+
+    Ok(resp)
+}
+
+#[tracing::instrument(skip_all, fields(tournament_id = props.tournament_id))]
+pub async fn tournament_invite_code_new(
+    config: Config,
+    db: Db,
+    auth_service: Auth,
+    props: request::TournamentInviteCodeNewProps,
+) -> Result<response::TournamentInviteCode, response::AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
+
+    let mut sp = db.begin().await?;
+
+    // a retry of a request that already went through
+    if let Some(resp) = replay(
+        &mut *sp,
+        &config,
+        user.user_id,
+        "tournament_invite_code/new",
+        &props,
+    )
+    .await?
+    {
+        return Ok(resp);
+    }
+
+    // ensure that tournament exists and you help run it
+    let tournament = sp
+        .tournament_get(props.tournament_id)
+        .await?
+        .ok_or(response::AppError::TournamentNonexistent)?;
+    ensure_organizer(&mut *sp, tournament.tournament_id, user.user_id).await?;
+
+    if tournament.visibility != VisibilityKind::InviteCode {
+        return Err(response::AppError::TournamentVisibilityInvalid);
+    }
+
+    let state = get_tournament_state(&mut *sp, tournament.tournament_id).await?;
+    if state == TournamentStateKind::Archived {
+        return Err(response::AppError::TournamentArchived);
+    }
+
+    // the old code stops working right away
+    let tournament_invite_code = sp
+        .tournament_invite_code_add(
+            user.user_id,
+            tournament.tournament_id,
+            utils::random_string(),
+        )
+        .await?;
+
+    // return json
+    let resp = fill_tournament_invite_code(&mut *sp, tournament_invite_code).await?;
+
+    remember(
+        &mut *sp,
+        &config,
+        user.user_id,
+        "tournament_invite_code/new",
+        &props,
+        &resp,
+    )
+    .await?;
+
+    sp.commit().await?;
+
+    Ok(resp)
+}
+
+#[tracing::instrument(skip_all, fields(tournament_id = props.tournament_id))]
+pub async fn tournament_team_new(
+    config: Config,
+    db: Db,
+    auth_service: Auth,
+    props: request::TournamentTeamNewProps,
+) -> Result<response::TournamentTeam, response::AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
+
+    let mut sp = db.begin().await?;
+
+    // a retry of a request that already went through
+    if let Some(resp) = replay(
+        &mut *sp,
+        &config,
+        user.user_id,
+        "tournament_team/new",
+        &props,
+    )
+    .await?
+    {
+        return Ok(resp);
+    }
+
+    // ensure that tournament exists and you help run it
+    let tournament = sp
+        .tournament_get(props.tournament_id)
+        .await?
+        .ok_or(response::AppError::TournamentNonexistent)?;
+    ensure_organizer(&mut *sp, tournament.tournament_id, user.user_id).await?;
+
+    // teams can be set up until the game is over
+    match get_tournament_state(&mut *sp, tournament.tournament_id).await? {
+        TournamentStateKind::Finished => return Err(response::AppError::TournamentFinished),
+        TournamentStateKind::Archived => return Err(response::AppError::TournamentArchived),
+        _ => {}
+    }
+
+    // create tournament team
+    let tournament_team = sp
+        .tournament_team_add(user.user_id, tournament.tournament_id, props.name.clone())
+        .await?;
+
+    // return json
+    let resp = fill_tournament_team(&mut *sp, tournament_team).await?;
+
+    remember(
+        &mut *sp,
+        &config,
+        user.user_id,
+        "tournament_team/new",
+        &props,
+        &resp,
+    )
+    .await?;
+
+    sp.commit().await?;
+
+    Ok(resp)
+}
+
+#[tracing::instrument(skip_all, fields(tournament_id = props.tournament_id))]
+pub async fn tournament_bot_new(
+    config: Config,
+    db: Db,
+    auth_service: Auth,
+    props: request::TournamentBotNewProps,
+) -> Result<response::TournamentBot, response::AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
+
+    let mut sp = db.begin().await?;
+
+    // a retry of a request that already went through
+    if let Some(resp) = replay(
+        &mut *sp,
+        &config,
+        user.user_id,
+        "tournament_bot/new",
+        &props,
+    )
+    .await?
+    {
+        return Ok(resp);
+    }
+
+    // only fixed quantity bots are told what to submit, and never less than nothing
+    match (props.strategy, props.quantity) {
+        (BotStrategyKind::FixedQuantity, Some(quantity)) if quantity >= 0 => {}
+        (BotStrategyKind::FixedQuantity, _) => {
+            return Err(response::AppError::TournamentBotInvalid)
+        }
+        (_, Some(_)) => return Err(response::AppError::TournamentBotInvalid),
+        (_, None) => {}
+    }
+
+    // ensure that tournament exists and you help run it
+    let tournament = sp
+        .tournament_get(props.tournament_id)
+        .await?
+        .ok_or(response::AppError::TournamentNonexistent)?;
+    ensure_organizer(&mut *sp, tournament.tournament_id, user.user_id).await?;
+
+    // bots can join until the game is over, and catch up like late joiners
+    match get_tournament_state(&mut *sp, tournament.tournament_id).await? {
+        TournamentStateKind::Finished => return Err(response::AppError::TournamentFinished),
+        TournamentStateKind::Archived => return Err(response::AppError::TournamentArchived),
+        _ => {}
+    }
+
+    // bots go by a name like everyone else's, tournament_bot/view tells them apart
+    let taken = taken_names(&mut *sp, tournament.tournament_id).await?;
+
+    // create tournament bot
+    let tournament_bot = sp
+        .tournament_bot_add(
+            user.user_id,
+            tournament.tournament_id,
+            props.strategy,
+            props.quantity,
+            gen_pseudonym(&taken),
+        )
+        .await?;
+
+    add_current_demand(
+        &mut *sp,
+        &tournament,
+        Firm::Bot(tournament_bot.tournament_bot_id),
+    )
+    .await?;
+
+    // return json
+    let resp = fill_tournament_bot(&mut *sp, tournament_bot).await?;
+
+    remember(
+        &mut *sp,
+        &config,
+        user.user_id,
+        "tournament_bot/new",
+        &props,
+        &resp,
+    )
+    .await?;
+
+    sp.commit().await?;
+
+    Ok(resp)
+}
+
+#[tracing::instrument(skip_all, fields(tournament_id = props.tournament_id))]
+pub async fn tournament_role_new(
+    config: Config,
+    db: Db,
+    auth_service: Auth,
+    props: request::TournamentRoleNewProps,
+) -> Result<response::TournamentRole, response::AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
+
+    let mut sp = db.begin().await?;
+
+    // a retry of a request that already went through
+    if let Some(resp) = replay(
+        &mut *sp,
+        &config,
+        user.user_id,
+        "tournament_role/new",
+        &props,
+    )
+    .await?
+    {
+        return Ok(resp);
+    }
+
+    // ensure that tournament exists and you own it
+    let tournament = sp
+        .tournament_get(props.tournament_id)
+        .await?
+        .ok_or(response::AppError::TournamentNonexistent)?;
+    if get_tournament_role(&mut *sp, tournament.tournament_id, user.user_id).await?
+        != Some(TournamentRoleKind::Owner)
+    {
+        return Err(response::AppError::TournamentNonexistent);
+    }
+
+    let state = get_tournament_state(&mut *sp, tournament.tournament_id).await?;
+    if state == TournamentStateKind::Archived {
+        return Err(response::AppError::TournamentArchived);
+    }
+
+    // you can't change your own role, so there's always an owner left
+    if props.user_id == user.user_id {
+        return Err(response::AppError::TournamentRoleInvalid);
+    }
+
+    // players can't help run the game they're playing
+    let tournament_membership = sp
+        .tournament_membership_get_recent_by_tournament_user(props.tournament_id, props.user_id)
+        .await?;
+    if props.active && tournament_membership.is_some_and(|m| m.active) {
+        return Err(response::AppError::TournamentRoleInvalid);
+    }
+
+    let tournament_role = sp
+        .tournament_role_add(
+            user.user_id,
+            tournament.tournament_id,
+            props.user_id,
+            props.role,
+            props.active,
+        )
+        .await?;
+
+    // return json
+    let resp = fill_tournament_role(&mut *sp, tournament_role).await?;
+
+    remember(
+        &mut *sp,
+        &config,
+        user.user_id,
+        "tournament_role/new",
+        &props,
+        &resp,
+    )
+    .await?;
+
+    sp.commit().await?;
+
+    Ok(resp)
+}
+
+#[tracing::instrument(skip_all, fields(tournament_id = props.tournament_id))]
+pub async fn tournament_submission_new(
+    config: Config,
+    db: Db,
+    auth_service: Auth,
+    props: request::TournamentSubmissionNewProps,
+) -> Result<response::TournamentSubmission, response::AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
+
+    let mut sp = db.begin().await?;
+
+    // a retry of a request that already went through
+    if let Some(resp) = replay(
+        &mut *sp,
+        &config,
+        user.user_id,
+        "tournament_submission/new",
+        &props,
+    )
+    .await?
+    {
+        return Ok(resp);
+    }
+
+    // ensure that tournament exists
+    let tournament = sp
+        .tournament_get(props.tournament_id)
+        .await?
+        .ok_or(response::AppError::TournamentNonexistent)?;
+
+    // validate user is playing
+    let tournament_membership = sp
+        .tournament_membership_get_recent_by_tournament_user(props.tournament_id, user.user_id)
+        .await?
+        .ok_or(response::AppError::Unauthorized)?;
+    if !is_player(&tournament_membership) {
+        return Err(response::AppError::Unauthorized);
+    }
+
+    // validate tournament is being played
+    ensure_running(get_tournament_state(&mut *sp, props.tournament_id).await?)?;
+
+    // get current year from tournament_data
+    let tournament_year = sp
+        .tournament_year_get_recent(props.tournament_id)
+        .await?
+        .ok_or(response::AppError::TournamentNonexistent)?;
+
+    // create tournament submission, team members submit for their team
+    let tournament_submission = sp
+        .tournament_submission_add(
+            user.user_id,
+            tournament_membership.firm(),
+            tournament.tournament_id,
+            tournament_year.current_year,
+            props.amount,
+            false,
+        )
+        .await?;
+
+    // practice games move on as soon as the student made up their mind
+    if tournament.practice {
+        if tournament_year.current_year >= tournament.max_years {
+            finalize(&mut *sp, user.user_id, &tournament).await?;
+        } else {
+            advance_year(&mut *sp, user.user_id, &tournament, &tournament_year).await?;
+        }
+    }
+
+    // return json
+    let resp = fill_tournament_submission(&mut *sp, tournament_submission, user.user_id).await?;
+
+    remember(
+        &mut *sp,
+        &config,
+        user.user_id,
+        "tournament_submission/new",
+        &props,
+        &resp,
+    )
+    .await?;
+
+    sp.commit().await?;
+
+    Ok(resp)
+}
+
+#[tracing::instrument(skip_all, fields(tournament_id = props.tournament_id))]
+pub async fn tournament_final_result_new(
+    config: Config,
+    db: Db,
+    auth_service: Auth,
+    props: request::TournamentFinalResultNewProps,
+) -> Result<Vec<response::TournamentFinalResult>, response::AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
+
+    let mut sp = db.begin().await?;
+
+    // a retry of a request that already went through
+    if let Some(resp) = replay(
+        &mut *sp,
+        &config,
+        user.user_id,
+        "tournament_final_result/new",
+        &props,
+    )
+    .await?
+    {
+        return Ok(resp);
+    }
+
+    // ensure that tournament exists and you help run it
+    let tournament = sp
+        .tournament_get(props.tournament_id)
+        .await?
+        .ok_or(response::AppError::TournamentNonexistent)?;
+    ensure_organizer(&mut *sp, tournament.tournament_id, user.user_id).await?;
+
+    // a paused game may be ended as well
+    match get_tournament_state(&mut *sp, tournament.tournament_id).await? {
+        TournamentStateKind::Running | TournamentStateKind::Paused => {}
+        state => ensure_running(state)?,
+    }
+
+    let tournament_final_result = finalize(&mut *sp, user.user_id, &tournament).await?;
+
+    // return json
+    let mut resp_tournament_final_results = vec![];
+    for u in tournament_final_result.into_iter() {
+        resp_tournament_final_results
+            .push(fill_tournament_final_result(&mut *sp, u, user.user_id).await?);
+    }
+
+    remember(
+        &mut *sp,
+        &config,
+        user.user_id,
+        "tournament_final_result/new",
+        &props,
+        &resp_tournament_final_results,
+    )
+    .await?;
+
+    sp.commit().await?;
+
+    Ok(resp_tournament_final_results)
+}
+
+// Called by the scheduler: advances every running tournament whose year is past its deadline,
+// and finalizes those that were already in their last year. Returns how many were handled.
+pub async fn advance_due_years(db: &Db, now: i64) -> Result<usize, response::AppError> {
+    let mut sp = db.begin().await?;
+    let due = sp.tournament_year_get_due(now).await?;
+    sp.commit().await?;
+
+    let mut handled = 0;
+    // every tournament gets its own transaction, so one failure doesn't hold up the rest
+    for tournament_year in due {
+        match advance_due_year(db, &tournament_year).await {
+            Ok(true) => handled += 1,
+            Ok(false) => {}
+            // the next tick tries again
+            Err(e) => tracing::warn!(
+                tournament_id = tournament_year.tournament_id,
+                error = %e,
+                "couldn't advance tournament"
+            ),
+        }
+    }
+
+    Ok(handled)
+}
+
+// returns whether the tournament still needed advancing
+async fn advance_due_year(
+    db: &Db,
+    tournament_year: &TournamentYear,
+) -> Result<bool, response::AppError> {
+    let start = Instant::now();
+    let mut sp = db.begin().await?;
+
+    let tournament = sp
+        .tournament_get(tournament_year.tournament_id)
+        .await?
+        .ok_or(response::AppError::TournamentNonexistent)?;
+
+    // the creator may have advanced or paused it in the meantime
+    let state = get_tournament_state(&mut *sp, tournament.tournament_id).await?;
+    let recent_year = sp
+        .tournament_year_get_recent(tournament.tournament_id)
+        .await?
+        .ok_or(response::AppError::TournamentNonexistent)?;
+    if state != TournamentStateKind::Running
+        || recent_year.tournament_year_id != tournament_year.tournament_year_id
+    {
+        return Ok(false);
+    }
+
+    if tournament_year.current_year >= tournament.max_years {
+        finalize(&mut *sp, tournament.creator_user_id, &tournament).await?;
+    } else {
+        advance_year(
+            &mut *sp,
+            tournament.creator_user_id,
+            &tournament,
+            tournament_year,
+        )
+        .await?;
+        metrics::record_year_advance(start.elapsed());
+    }
+
+    sp.commit().await?;
+    Ok(true)
+}
+
+pub async fn active_tournament_count(db: &Db) -> Result<i64, response::AppError> {
+    let mut sp = db.begin().await?;
+    let count = sp.tournament_data_count_active().await?;
+    sp.commit().await?;
+    Ok(count)
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn tournament_data_view(
+    _config: Config,
+    db: Db,
+    auth_service: Auth,
+    props: request::TournamentDataViewProps,
+) -> Result<Vec<response::TournamentData>, response::AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
+
+    let mut sp = db.begin().await?;
+    let mut viewable = Viewable::new(user.user_id);
+    // get users
+    let tournament_data = sp.tournament_data_query(props).await?;
+
+    // return tournament_datas
+    let mut resp_tournament_datas = vec![];
+    for u in tournament_data.into_iter() {
+        if !viewable.contains(&mut *sp, u.tournament_id).await? {
+            continue;
+        }
+        resp_tournament_datas.push(fill_tournament_data(&mut *sp, u).await?);
+    }
+
+    sp.commit().await?;
+
+    Ok(resp_tournament_datas)
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn tournament_membership_view(
+    _config: Config,
+    db: Db,
+    auth_service: Auth,
+    props: request::TournamentMembershipViewProps,
+) -> Result<Vec<response::TournamentMembership>, response::AppError> {
+    // validate api key, whether user ids are shown depends on who's asking
+    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
+
+    // filtering by someone else's id would give away who is behind their pseudonym
+    let by_user = props.creator_user_id.is_some();
+
+    let mut sp = db.begin().await?;
+    let mut viewable = Viewable::new(user.user_id);
+    // get users
+    let tournament_membership = sp.tournament_membership_query(props).await?;
+
+    // return tournament_memberships
+    let mut resp_tournament_memberships = vec![];
+    for u in tournament_membership.into_iter() {
+        // your own membership stays visible, so you can tell whether you were let in
+        if u.creator_user_id != user.user_id
+            && !viewable.contains(&mut *sp, u.tournament_id).await?
+        {
+            continue;
+        }
+        let resp = fill_tournament_membership(&mut *sp, u, user.user_id).await?;
+        if by_user && resp.creator_user_id.is_none() {
+            continue;
+        }
+        resp_tournament_memberships.push(resp);
+    }
+
+    sp.commit().await?;
+
+    Ok(resp_tournament_memberships)
+}
+
+// only organizers get to see the code they hand out
+#[tracing::instrument(skip_all, fields(tournament_id = props.tournament_id))]
+pub async fn tournament_invite_code_view(
+    _config: Config,
+    db: Db,
+    auth_service: Auth,
+    props: request::TournamentInviteCodeViewProps,
+) -> Result<Vec<response::TournamentInviteCode>, response::AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth_service, props.api_key).await?;
+
+    let mut sp = db.begin().await?;
+
+    let tournament = sp
+        .tournament_get(props.tournament_id)
+        .await?
+        .ok_or(response::AppError::TournamentNonexistent)?;
+    ensure_organizer(&mut *sp, tournament.tournament_id, user.user_id).await?;
+
+    // empty unless the tournament is invite only
+    let tournament_invite_code = sp
+        .tournament_invite_code_get_recent(tournament.tournament_id)
+        .await?;
+
+    // return tournament_invite_codes
+    let mut resp_tournament_invite_codes = vec![];
+    for u in tournament_invite_code.into_iter() {
+        resp_tournament_invite_codes.push(fill_tournament_invite_code(&mut *sp, u).await?);
+    }
+
+    sp.commit().await?;
+
+    Ok(resp_tournament_invite_codes)
+}
+
+#[tracing::instrument(skip_all, fields(tournament_id = props.tournament_id))]
+pub async fn tournament_pseudonym_view(
+    _config: Config,
+    db: Db,
+    auth_service: Auth,
+    props: request::TournamentPseudonymViewProps,
+) -> Result<Vec<response::TournamentPseudonym>, response::AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth_service, props.api_key).await?;
+
+    let mut sp = db.begin().await?;
+
+    // only whoever helps run the tournament may know who is behind a pseudonym
+    let tournament = sp
+        .tournament_get(props.tournament_id)
+        .await?
+        .ok_or(response::AppError::TournamentNonexistent)?;
+    ensure_organizer(&mut *sp, tournament.tournament_id, user.user_id).await?;
+
+    let tournament_pseudonym = sp
+        .tournament_pseudonym_get_by_tournament(tournament.tournament_id)
+        .await?;
+
+    // return tournament_pseudonyms
+    let mut resp_tournament_pseudonyms = vec![];
+    for u in tournament_pseudonym.into_iter() {
+        resp_tournament_pseudonyms.push(fill_tournament_pseudonym(&mut *sp, u).await?);
+    }
+
+    sp.commit().await?;
+
+    Ok(resp_tournament_pseudonyms)
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn tournament_team_view(
+    _config: Config,
+    db: Db,
+    auth_service: Auth,
+    props: request::TournamentTeamViewProps,
+) -> Result<Vec<response::TournamentTeam>, response::AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
+
+    let mut sp = db.begin().await?;
+    let mut viewable = Viewable::new(user.user_id);
+    // get teams
+    let tournament_team = sp.tournament_team_query(props).await?;
+
+    // return tournament_teams
+    let mut resp_tournament_teams = vec![];
+    for u in tournament_team.into_iter() {
+        if !viewable.contains(&mut *sp, u.tournament_id).await? {
+            continue;
+        }
+        resp_tournament_teams.push(fill_tournament_team(&mut *sp, u).await?);
+    }
+
+    sp.commit().await?;
+
+    Ok(resp_tournament_teams)
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn tournament_bot_view(
+    _config: Config,
+    db: Db,
+    auth_service: Auth,
+    props: request::TournamentBotViewProps,
+) -> Result<Vec<response::TournamentBot>, response::AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
+
+    let mut sp = db.begin().await?;
+    let mut viewable = Viewable::new(user.user_id);
+    // get bots
+    let tournament_bot = sp.tournament_bot_query(props).await?;
+
+    // return tournament_bots
+    let mut resp_tournament_bots = vec![];
+    for u in tournament_bot.into_iter() {
+        if !viewable.contains(&mut *sp, u.tournament_id).await? {
+            continue;
+        }
+        resp_tournament_bots.push(fill_tournament_bot(&mut *sp, u).await?);
+    }
+
+    sp.commit().await?;
+
+    Ok(resp_tournament_bots)
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn tournament_role_view(
+    _config: Config,
+    db: Db,
+    auth_service: Auth,
+    props: request::TournamentRoleViewProps,
+) -> Result<Vec<response::TournamentRole>, response::AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
+
+    let mut sp = db.begin().await?;
+    let mut viewable = Viewable::new(user.user_id);
+    // get roles
+    let tournament_role = sp.tournament_role_query(props).await?;
+
+    // return tournament_roles
+    let mut resp_tournament_roles = vec![];
+    for u in tournament_role.into_iter() {
+        if !viewable.contains(&mut *sp, u.tournament_id).await? {
+            continue;
+        }
+        resp_tournament_roles.push(fill_tournament_role(&mut *sp, u).await?);
+    }
+
+    sp.commit().await?;
+
+    Ok(resp_tournament_roles)
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn tournament_submission_view(
+    _config: Config,
+    db: Db,
+    auth_service: Auth,
+    props: request::TournamentSubmissionViewProps,
+) -> Result<Vec<response::TournamentSubmission>, response::AppError> {
+    // validate api key, whether user ids are shown depends on who's asking
+    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
+
+    // filtering by someone else's id would give away who is behind their pseudonym
+    let by_user = props.creator_user_id.is_some();
+
+    let mut sp = db.begin().await?;
+    let mut viewable = Viewable::new(user.user_id);
+    // get users
+    let tournament_submission = sp.tournament_submission_query(props).await?;
+
+    // return tournament_submissions
+    let mut resp_tournament_submissions = vec![];
+    for u in tournament_submission.into_iter() {
+        if !viewable.contains(&mut *sp, u.tournament_id).await? {
+            continue;
+        }
+        let resp = fill_tournament_submission(&mut *sp, u, user.user_id).await?;
+        if by_user && resp.creator_user_id.is_none() {
+            continue;
+        }
+        resp_tournament_submissions.push(resp);
+    }
+
+    sp.commit().await?;
+
+    Ok(resp_tournament_submissions)
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn tournament_year_view(
+    _config: Config,
+    db: Db,
+    auth_service: Auth,
+    props: request::TournamentYearViewProps,
+) -> Result<Vec<response::TournamentYear>, response::AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
+
+    let mut sp = db.begin().await?;
+    let mut viewable = Viewable::new(user.user_id);
+    // get users
+    let tournament_year = sp.tournament_year_query(props).await?;
+
+    // return tournament_years
+    let mut resp_tournament_years = vec![];
+    for u in tournament_year.into_iter() {
+        if !viewable.contains(&mut *sp, u.tournament_id).await? {
+            continue;
+        }
+        resp_tournament_years.push(fill_tournament_year(&mut *sp, u).await?);
+    }
+
+    sp.commit().await?;
+
+    Ok(resp_tournament_years)
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn tournament_year_demand_view(
+    _config: Config,
+    db: Db,
+    auth_service: Auth,
+    props: request::TournamentYearDemandViewProps,
+) -> Result<Vec<response::TournamentYearDemand>, response::AppError> {
+    // validate api key, whether user ids are shown depends on who's asking
+    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
+
+    // filtering by someone else's id would give away who is behind their pseudonym
+    let by_user = props.user_id.is_some();
+
+    let mut sp = db.begin().await?;
+    let mut viewable = Viewable::new(user.user_id);
+    // get users
+    let tournament_year_demand = sp.tournament_year_demand_query(props).await?;
+
+    // return tournament_year_demands
+    let mut resp_tournament_year_demands = vec![];
+    for u in tournament_year_demand.into_iter() {
+        if !viewable.contains(&mut *sp, u.tournament_id).await? {
+            continue;
+        }
+        let resp = fill_tournament_year_demand(&mut *sp, u, user.user_id).await?;
+        if by_user && resp.user_id.is_none() {
+            continue;
+        }
+        resp_tournament_year_demands.push(resp);
+    }
+
+    sp.commit().await?;
+
+    Ok(resp_tournament_year_demands)
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn tournament_pause_view(
+    _config: Config,
+    db: Db,
+    auth_service: Auth,
+    props: request::TournamentPauseViewProps,
+) -> Result<Vec<response::TournamentPause>, response::AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth_service, props.api_key.clone()).await?;
+
+    let mut sp = db.begin().await?;
+    let mut viewable = Viewable::new(user.user_id);
+    // get users
+    let tournament_pause = sp.tournament_pause_query(props).await?;
+
+    // return tournament_pauses
+    let mut resp_tournament_pauses = vec![];
+    for u in tournament_pause.into_iter() {
+        if !viewable.contains(&mut *sp, u.tournament_id).await? {
+            continue;
+        }
+        resp_tournament_pauses.push(fill_tournament_pause(&mut *sp, u).await?);
+    }
+
+    sp.commit().await?;
+
+    Ok(resp_tournament_pauses)
+}
+
+// shares, concentration and how far everyone was from their best response, for the debrief
+#[tracing::instrument(skip_all, fields(tournament_id = props.tournament_id))]
+pub async fn tournament_analytics_view(
+    _config: Config,
+    db: Db,
+    auth_service: Auth,
+    props: request::TournamentAnalyticsViewProps,
+) -> Result<response::TournamentAnalytics, response::AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth_service, props.api_key).await?;
+
+    let mut sp = db.begin().await?;
+
+    // only for whoever runs the tournament
+    let tournament = sp
+        .tournament_get(props.tournament_id)
+        .await?
+        .ok_or(response::AppError::TournamentNonexistent)?;
+    ensure_organizer(&mut *sp, tournament.tournament_id, user.user_id).await?;
+
+    let years = analytics_years(&mut *sp, &tournament).await?;
+    let summaries = analytics::summaries(&years);
+
+    // return json
+    let resp = fill_tournament_analytics(&mut *sp, tournament, years, summaries).await?;
+
+    sp.commit().await?;
+
+    Ok(resp)
+}
+
+// signs of tacit collusion, over the whole market and between every two firms
+#[tracing::instrument(skip_all, fields(tournament_id = props.tournament_id))]
+pub async fn tournament_collusion_view(
+    _config: Config,
+    db: Db,
+    auth_service: Auth,
+    props: request::TournamentCollusionViewProps,
+) -> Result<response::TournamentCollusion, response::AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth_service, props.api_key).await?;
+
+    let mut sp = db.begin().await?;
+
+    // only for whoever runs the tournament
+    let tournament = sp
+        .tournament_get(props.tournament_id)
+        .await?
+        .ok_or(response::AppError::TournamentNonexistent)?;
+    ensure_organizer(&mut *sp, tournament.tournament_id, user.user_id).await?;
+
+    let years = analytics_years(&mut *sp, &tournament).await?;
+    let findings = collusion::find(&years);
+
+    // return json
+    let resp = fill_tournament_collusion(&mut *sp, tournament, findings).await?;
+
+    sp.commit().await?;
+
+    Ok(resp)
+}
+
+// what the textbook says each year should have looked like, for every year with known incentives
+#[tracing::instrument(skip_all, fields(tournament_id = props.tournament_id))]
+pub async fn tournament_benchmarks_view(
+    _config: Config,
+    db: Db,
+    auth_service: Auth,
+    props: request::TournamentBenchmarksViewProps,
+) -> Result<Vec<response::TournamentBenchmark>, response::AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth_service, props.api_key).await?;
+
+    let mut sp = db.begin().await?;
+
+    let tournament = sp
+        .tournament_get(props.tournament_id)
+        .await?
+        .ok_or(response::AppError::TournamentNonexistent)?;
+
+    // the incentives say how the year went
+    ensure_can_view(&mut *sp, &tournament, user.user_id).await?;
+
+    // every firm selling in a year has exactly one demand for it
+    let mut years: BTreeMap<i64, (i64, i64)> = BTreeMap::new();
+    for d in sp
+        .tournament_year_demand_get_by_tournament(tournament.tournament_id)
+        .await?
+    {
+        let (firms, total_incentive) = years.entry(d.year).or_insert((0, 0));
+        *firms += 1;
+        *total_incentive += d.demand;
+    }
+
+    sp.commit().await?;
+
+    Ok(years
+        .into_iter()
+        .map(
+            |(year, (firms, total_incentive))| response::TournamentBenchmark {
+                tournament_id: tournament.tournament_id,
+                year,
+                firms,
+                total_incentive,
+                nash: fill_benchmark_outcome(market::cournot(&tournament, total_incentive, firms)),
+                monopoly: fill_benchmark_outcome(market::monopoly(
+                    &tournament,
+                    total_incentive,
+                    firms,
+                )),
+                competitive: fill_benchmark_outcome(market::competitive(
+                    &tournament,
+                    total_incentive,
+                    firms,
+                )),
+            },
+        )
+        .collect())
+}
+
+// results are shown to whoever can see the tournament
+#[tracing::instrument(skip_all, fields(tournament_id = props.tournament_id))]
+pub async fn tournament_final_result_view(
+    _config: Config,
+    db: Db,
+    auth_service: Auth,
+    props: request::TournamentFinalResultViewProps,
+) -> Result<Vec<response::TournamentFinalResult>, response::AppError> {
+    // validate api key
+    let user = get_user_if_api_key_valid(&auth_service, props.api_key).await?;
+
+    let mut sp = db.begin().await?;
+
+    let tournament = sp
+        .tournament_get(props.tournament_id)
+        .await?
+        .ok_or(response::AppError::TournamentNonexistent)?;
+
+    ensure_can_view(&mut *sp, &tournament, user.user_id).await?;
+
+    // empty until the tournament is finalized
+    let tournament_final_result = sp
+        .tournament_final_result_get_by_tournament(props.tournament_id)
+        .await?;
+
+    // return tournament_final_results
+    let mut resp_tournament_final_results = vec![];
+    for u in tournament_final_result.into_iter() {
+        resp_tournament_final_results
+            .push(fill_tournament_final_result(&mut *sp, u, user.user_id).await?);
+    }
+
+    sp.commit().await?;
+
+    Ok(resp_tournament_final_results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory_store::MemoryStore;
+    use crate::response::AppError;
+    use crate::test_utils::{api_key, dev_auth};
+    use std::sync::Arc;
+
+    struct Env {
+        config: Config,
+        db: Db,
+        auth_service: Auth,
+    }
+
+    fn env() -> Env {
+        Env {
+            config: Config {
+                site_external_url: "http://localhost".to_owned(),
+                idempotency_window_ms: 60_000,
+            },
+            db: Arc::new(MemoryStore::new()),
+            auth_service: dev_auth(),
+        }
+    }
+
+    fn tournament_props(user_id: i64) -> request::TournamentNewProps {
+        request::TournamentNewProps {
+            api_key: api_key(user_id),
+            title: "test".to_owned(),
+            cost_per_unit: 10,
+            demand_xintercept: 100,
+            demand_yintercept: 100,
+            incentive_multiplier: 5,
+            incentive_start_year: 2,
+            max_years: 3,
+            year_duration_ms: None,
+            late_join_policy: None,
+            exit_policy: None,
+            visibility: None,
+            min_players: None,
+            max_players: None,
+            auto_start: None,
+            practice: None,
+            idempotency_key: None,
+        }
+    }
+
+    impl Env {
+        async fn new_tournament(
+            &self,
+            props: request::TournamentNewProps,
+        ) -> Result<i64, AppError> {
+            let data = tournament_new(
+                self.config.clone(),
+                self.db.clone(),
+                self.auth_service.clone(),
+                props,
+            )
+            .await?;
+            Ok(data.tournament.tournament_id)
+        }
+
+        async fn archive(&self, user_id: i64, tournament_id: i64) -> Result<(), AppError> {
+            tournament_data_new(
+                self.config.clone(),
+                self.db.clone(),
+                self.auth_service.clone(),
+                request::TournamentDataNewProps {
+                    tournament_id,
+                    title: "test".to_owned(),
+                    active: false,
+                    api_key: api_key(user_id),
+                    idempotency_key: None,
+                },
+            )
+            .await?;
+            Ok(())
+        }
+
+        async fn set_state(
+            &self,
+            user_id: i64,
+            tournament_id: i64,
+            state: TournamentStateKind,
+        ) -> Result<TournamentStateKind, AppError> {
+            let tournament_state = tournament_state_new(
+                self.config.clone(),
+                self.db.clone(),
+                self.auth_service.clone(),
+                request::TournamentStateNewProps {
+                    tournament_id,
+                    state,
+                    api_key: api_key(user_id),
+                    idempotency_key: None,
+                },
+            )
+            .await?;
+            assert_eq!(tournament_state.state, tournament_state.tournament.state);
+            Ok(tournament_state.state)
+        }
+
+        async fn join(&self, user_id: i64, tournament_id: i64) -> Result<(), AppError> {
+            self.membership(user_id, tournament_id, true).await?;
+            Ok(())
+        }
+
+        async fn membership(
+            &self,
+            user_id: i64,
+            tournament_id: i64,
+            active: bool,
+        ) -> Result<response::TournamentMembership, AppError> {
+            tournament_membership_new(
+                self.config.clone(),
+                self.db.clone(),
+                self.auth_service.clone(),
+                request::TournamentMembershipNewProps {
+                    tournament_id,
+                    active,
+                    invite_code: None,
+                    tournament_team_id: None,
+                    api_key: api_key(user_id),
+                    idempotency_key: None,
+                },
+            )
+            .await
+        }
+
+        async fn team(
+            &self,
+            user_id: i64,
+            tournament_id: i64,
+            name: &str,
+        ) -> Result<i64, AppError> {
+            let team = tournament_team_new(
+                self.config.clone(),
+                self.db.clone(),
+                self.auth_service.clone(),
+                request::TournamentTeamNewProps {
+                    tournament_id,
+                    name: name.to_owned(),
+                    api_key: api_key(user_id),
+                    idempotency_key: None,
+                },
+            )
+            .await?;
+            Ok(team.tournament_team_id)
+        }
+
+        async fn join_team(
+            &self,
+            user_id: i64,
+            tournament_id: i64,
+            tournament_team_id: i64,
+        ) -> Result<response::TournamentMembership, AppError> {
+            tournament_membership_new(
+                self.config.clone(),
+                self.db.clone(),
+                self.auth_service.clone(),
+                request::TournamentMembershipNewProps {
+                    tournament_id,
+                    active: true,
+                    invite_code: None,
+                    tournament_team_id: Some(tournament_team_id),
+                    api_key: api_key(user_id),
+                    idempotency_key: None,
+                },
+            )
+            .await
+        }
+
+        async fn approve(
+            &self,
+            user_id: i64,
+            tournament_id: i64,
+            member_user_id: i64,
+            approved: bool,
+        ) -> Result<response::TournamentMembership, AppError> {
+            tournament_membership_approval_new(
+                self.config.clone(),
+                self.db.clone(),
+                self.auth_service.clone(),
+                request::TournamentMembershipApprovalNewProps {
+                    tournament_id,
+                    user_id: member_user_id,
+                    approved,
+                    api_key: api_key(user_id),
+                    idempotency_key: None,
+                },
+            )
+            .await
+        }
+
+        async fn moderate(
+            &self,
+            user_id: i64,
+            tournament_id: i64,
+            member_user_id: i64,
+            action: request::MembershipActionKind,
+        ) -> Result<response::TournamentMembership, AppError> {
+            tournament_membership_action_new(
+                self.config.clone(),
+                self.db.clone(),
+                self.auth_service.clone(),
+                request::TournamentMembershipActionNewProps {
+                    tournament_id,
+                    user_id: member_user_id,
+                    action,
+                    reason: Some("test".to_owned()),
+                    api_key: api_key(user_id),
+                    idempotency_key: None,
+                },
+            )
+            .await
+        }
+
+        async fn grant(
+            &self,
+            user_id: i64,
+            tournament_id: i64,
+            grantee_user_id: i64,
+            role: TournamentRoleKind,
+            active: bool,
+        ) -> Result<response::TournamentRole, AppError> {
+            tournament_role_new(
+                self.config.clone(),
+                self.db.clone(),
+                self.auth_service.clone(),
+                request::TournamentRoleNewProps {
+                    tournament_id,
+                    user_id: grantee_user_id,
+                    role,
+                    active,
+                    api_key: api_key(user_id),
+                    idempotency_key: None,
+                },
+            )
+            .await
+        }
+
+        async fn submit(
+            &self,
+            user_id: i64,
+            tournament_id: i64,
+            amount: i64,
+        ) -> Result<(), AppError> {
+            tournament_submission_new(
+                self.config.clone(),
+                self.db.clone(),
+                self.auth_service.clone(),
+                request::TournamentSubmissionNewProps {
+                    tournament_id,
+                    amount,
+                    api_key: api_key(user_id),
+                    idempotency_key: None,
+                },
+            )
+            .await?;
+            Ok(())
+        }
+
+        async fn advance(&self, user_id: i64, tournament_id: i64) -> Result<i64, AppError> {
+            let year = tournament_year_new(
+                self.config.clone(),
+                self.db.clone(),
+                self.auth_service.clone(),
+                request::TournamentYearNewProps {
+                    tournament_id,
+                    api_key: api_key(user_id),
+                    idempotency_key: None,
+                },
+            )
+            .await?;
+            Ok(year.current_year)
+        }
+
+        async fn finalize(
+            &self,
+            user_id: i64,
+            tournament_id: i64,
+        ) -> Result<Vec<response::TournamentFinalResult>, AppError> {
+            tournament_final_result_new(
+                self.config.clone(),
+                self.db.clone(),
+                self.auth_service.clone(),
+                request::TournamentFinalResultNewProps {
+                    tournament_id,
+                    api_key: api_key(user_id),
+                    idempotency_key: None,
+                },
+            )
+            .await
+        }
+
+        async fn benchmarks(
+            &self,
+            user_id: i64,
+            tournament_id: i64,
+        ) -> Result<Vec<response::TournamentBenchmark>, AppError> {
+            tournament_benchmarks_view(
+                self.config.clone(),
+                self.db.clone(),
+                self.auth_service.clone(),
+                request::TournamentBenchmarksViewProps {
+                    tournament_id,
+                    api_key: api_key(user_id),
+                },
+            )
+            .await
+        }
+
+        async fn final_results(
+            &self,
+            user_id: i64,
+            tournament_id: i64,
+        ) -> Result<Vec<response::TournamentFinalResult>, AppError> {
+            tournament_final_result_view(
+                self.config.clone(),
+                self.db.clone(),
+                self.auth_service.clone(),
+                request::TournamentFinalResultViewProps {
+                    tournament_id,
+                    api_key: api_key(user_id),
+                },
+            )
+            .await
+        }
+
+        async fn years(
+            &self,
+            user_id: i64,
+            tournament_id: i64,
+            practice: Option<bool>,
+        ) -> Vec<response::TournamentYear> {
+            tournament_year_view(
+                self.config.clone(),
+                self.db.clone(),
+                self.auth_service.clone(),
+                request::TournamentYearViewProps {
+                    tournament_year_id: None,
+                    min_creation_time: None,
+                    max_creation_time: None,
+                    creator_user_id: None,
+                    tournament_id: Some(vec![tournament_id]),
+                    practice,
+                    only_recent: false,
+                    api_key: api_key(user_id),
+                },
+            )
+            .await
+            .unwrap()
+        }
+
+        async fn current_year(&self, tournament_id: i64) -> response::TournamentYear {
+            tournament_year_view(
+                self.config.clone(),
+                self.db.clone(),
+                self.auth_service.clone(),
+                request::TournamentYearViewProps {
+                    tournament_year_id: None,
+                    min_creation_time: None,
+                    max_creation_time: None,
+                    creator_user_id: None,
+                    tournament_id: Some(vec![tournament_id]),
+                    practice: None,
+                    only_recent: true,
+                    api_key: api_key(1),
+                },
+            )
+            .await
+            .unwrap()
+            .pop()
+            .unwrap()
+        }
+
+        async fn pauses(&self, tournament_id: i64) -> Vec<response::TournamentPause> {
+            tournament_pause_view(
+                self.config.clone(),
+                self.db.clone(),
+                self.auth_service.clone(),
+                request::TournamentPauseViewProps {
+                    tournament_pause_id: None,
+                    min_creation_time: None,
+                    max_creation_time: None,
+                    tournament_id: Some(vec![tournament_id]),
+                    api_key: api_key(1),
+                },
+            )
+            .await
+            .unwrap()
+        }
+
+        async fn submissions(&self, tournament_id: i64) -> Vec<response::TournamentSubmission> {
+            self.submissions_with(1, tournament_id, None).await
+        }
+
+        async fn submissions_with(
+            &self,
+            user_id: i64,
+            tournament_id: i64,
+            practice: Option<bool>,
+        ) -> Vec<response::TournamentSubmission> {
+            tournament_submission_view(
+                self.config.clone(),
+                self.db.clone(),
+                self.auth_service.clone(),
+                request::TournamentSubmissionViewProps {
+                    tournament_submission_id: None,
+                    min_creation_time: None,
+                    max_creation_time: None,
+                    creator_user_id: None,
+                    tournament_id: Some(vec![tournament_id]),
+                    tournament_team_id: None,
+                    practice,
+                    api_key: api_key(user_id),
+                },
+            )
+            .await
+            .unwrap()
+        }
+
+        async fn listed(&self, user_id: i64, tournament_id: i64, practice: Option<bool>) -> bool {
+            !tournament_data_view(
+                self.config.clone(),
+                self.db.clone(),
+                self.auth_service.clone(),
+                request::TournamentDataViewProps {
+                    tournament_data_id: None,
+                    min_creation_time: None,
+                    max_creation_time: None,
+                    creator_user_id: None,
+                    tournament_id: Some(vec![tournament_id]),
+                    active: None,
+                    practice,
+                    only_recent: true,
+                    api_key: api_key(user_id),
+                },
+            )
+            .await
+            .unwrap()
+            .is_empty()
+        }
+
+        async fn bot(
+            &self,
+            user_id: i64,
+            tournament_id: i64,
+            strategy: BotStrategyKind,
+            quantity: Option<i64>,
+        ) -> Result<response::TournamentBot, AppError> {
+            tournament_bot_new(
+                self.config.clone(),
+                self.db.clone(),
+                self.auth_service.clone(),
+                request::TournamentBotNewProps {
+                    tournament_id,
+                    strategy,
+                    quantity,
+                    api_key: api_key(user_id),
+                    idempotency_key: None,
+                },
+            )
+            .await
+        }
+
+        async fn pseudonyms(
+            &self,
+            user_id: i64,
+            tournament_id: i64,
+        ) -> Result<Vec<response::TournamentPseudonym>, AppError> {
+            tournament_pseudonym_view(
+                self.config.clone(),
+                self.db.clone(),
+                self.auth_service.clone(),
+                request::TournamentPseudonymViewProps {
+                    tournament_id,
+                    api_key: api_key(user_id),
+                },
+            )
+            .await
+        }
+
+        async fn demands(&self, tournament_id: i64) -> Vec<response::TournamentYearDemand> {
+            self.demands_with(1, tournament_id, None).await
+        }
+
+        async fn demands_with(
+            &self,
+            user_id: i64,
+            tournament_id: i64,
+            practice: Option<bool>,
+        ) -> Vec<response::TournamentYearDemand> {
+            tournament_year_demand_view(
+                self.config.clone(),
+                self.db.clone(),
+                self.auth_service.clone(),
+                request::TournamentYearDemandViewProps {
+                    tournament_year_demand_id: None,
+                    min_creation_time: None,
+                    max_creation_time: None,
+                    user_id: None,
+                    tournament_id: Some(vec![tournament_id]),
+                    tournament_team_id: None,
+                    practice,
+                    api_key: api_key(user_id),
+                },
+            )
+            .await
+            .unwrap()
+        }
+    }
+
+    #[tokio::test]
+    async fn tournament_new_validates_props() {
+        let env = env();
+
+        let mut props = tournament_props(1);
+        props.max_years = 1;
+        let err = env.new_tournament(props).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentMaxYearsInvalid));
+
+        let mut props = tournament_props(1);
+        props.incentive_start_year = 1;
+        let err = env.new_tournament(props).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentIncentiveStartYearInvalid));
+
+        let mut props = tournament_props(1);
+        props.year_duration_ms = Some(0);
+        let err = env.new_tournament(props).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentYearDurationInvalid));
+
+        let mut props = tournament_props(1);
+        props.min_players = Some(3);
+        props.max_players = Some(2);
+        let err = env.new_tournament(props).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentPlayerCountInvalid));
+
+        let mut props = tournament_props(1);
+        props.auto_start = Some(true);
+        let err = env.new_tournament(props).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentPlayerCountInvalid));
+
+        let mut props = tournament_props(1);
+        props.api_key = "bogus".to_owned();
+        let err = env.new_tournament(props).await.unwrap_err();
+        assert!(matches!(err, AppError::Unauthorized));
+
+        // nothing was written by the failed attempts
+        assert_eq!(active_tournament_count(&env.db).await.unwrap(), 0);
+        env.new_tournament(tournament_props(1)).await.unwrap();
+        assert_eq!(active_tournament_count(&env.db).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn advancing_a_year_fills_in_missing_submissions() {
+        let env = env();
+        let tournament_id = env.new_tournament(tournament_props(1)).await.unwrap();
+
+        // the creator can't play in their own tournament
+        let err = env.join(1, tournament_id).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentMembershipInvalid));
+
+        env.join(2, tournament_id).await.unwrap();
+        env.join(3, tournament_id).await.unwrap();
+        env.set_state(1, tournament_id, TournamentStateKind::Running)
+            .await
+            .unwrap();
+        env.submit(2, tournament_id, 5).await.unwrap();
+        // only the most recent submission counts
+        env.submit(2, tournament_id, 10).await.unwrap();
+
+        // only members may submit
+        let err = env.submit(4, tournament_id, 10).await.unwrap_err();
+        assert!(matches!(err, AppError::Unauthorized));
+
+        assert_eq!(env.advance(1, tournament_id).await.unwrap(), 1);
+
+        let submissions = env.submissions(tournament_id).await;
+        assert_eq!(submissions.len(), 3);
+        let autogenerated: Vec<_> = submissions.iter().filter(|s| s.autogenerated).collect();
+        assert_eq!(autogenerated.len(), 1);
+        assert_eq!(autogenerated[0].creator_user_id, Some(3));
+        assert_eq!(autogenerated[0].year, 0);
+        assert_eq!(autogenerated[0].amount, 0);
+
+        // one demand per member when joining, and another for the new year
+        let demands = env.demands(tournament_id).await;
+        assert_eq!(demands.len(), 4);
+        assert_eq!(demands.iter().filter(|d| d.year == 1).count(), 2);
+        // incentives only start in year 2
+        assert!(demands.iter().all(|d| d.demand == 0));
+
+        // submissions now go to the new year
+        env.submit(2, tournament_id, 7).await.unwrap();
+        let submissions = env.submissions(tournament_id).await;
+        assert!(submissions.iter().any(|s| s.year == 1 && s.amount == 7));
+    }
+
+    #[tokio::test]
+    async fn only_the_creator_may_advance() {
+        let env = env();
+        let tournament_id = env.new_tournament(tournament_props(1)).await.unwrap();
+        env.join(2, tournament_id).await.unwrap();
+        env.set_state(1, tournament_id, TournamentStateKind::Running)
+            .await
+            .unwrap();
+
+        let err = env.advance(2, tournament_id).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentNonexistent));
+
+        let err = env.advance(1, tournament_id + 1000).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentNonexistent));
+    }
+
+    #[tokio::test]
+    async fn archived_tournaments_are_frozen() {
+        let env = env();
+        let tournament_id = env.new_tournament(tournament_props(1)).await.unwrap();
+        env.join(2, tournament_id).await.unwrap();
+        env.archive(1, tournament_id).await.unwrap();
+
+        let err = env.submit(2, tournament_id, 10).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentArchived));
+        let err = env.advance(1, tournament_id).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentArchived));
+        let err = env.join(3, tournament_id).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentArchived));
+        assert_eq!(active_tournament_count(&env.db).await.unwrap(), 0);
+
+        // archiving is final
+        let err = env
+            .set_state(1, tournament_id, TournamentStateKind::Running)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AppError::TournamentArchived));
+    }
+
+    #[tokio::test]
+    async fn advancing_stops_at_max_years() {
+        let env = env();
+        let tournament_id = env.new_tournament(tournament_props(1)).await.unwrap();
+        env.join(2, tournament_id).await.unwrap();
+        env.set_state(1, tournament_id, TournamentStateKind::Running)
+            .await
+            .unwrap();
+
+        for year in 1..=3 {
+            assert_eq!(env.advance(1, tournament_id).await.unwrap(), year);
+        }
+        let submissions = env.submissions(tournament_id).await.len();
+        let demands = env.demands(tournament_id).await.len();
+
+        let err = env.advance(1, tournament_id).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentMaxYearsAchieved));
+
+        // the failed advance didn't write anything
+        assert_eq!(env.submissions(tournament_id).await.len(), submissions);
+        assert_eq!(env.demands(tournament_id).await.len(), demands);
+    }
+
+    #[tokio::test]
+    async fn lifecycle_gates_joins_and_submissions() {
+        let env = env();
+        let tournament_id = env.new_tournament(tournament_props(1)).await.unwrap();
+        env.join(2, tournament_id).await.unwrap();
+
+        // nothing happens in the lobby but joining
+        let err = env.submit(2, tournament_id, 10).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentNotRunning));
+        let err = env.advance(1, tournament_id).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentNotRunning));
+
+        // only the creator controls the lifecycle
+        let err = env
+            .set_state(2, tournament_id, TournamentStateKind::Running)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AppError::TournamentNonexistent));
+        let err = env
+            .set_state(1, tournament_id, TournamentStateKind::Paused)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AppError::TournamentStateTransitionInvalid));
+
+        env.set_state(1, tournament_id, TournamentStateKind::Running)
+            .await
+            .unwrap();
+        // no late joins
+        let err = env.join(3, tournament_id).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentStarted));
+        env.submit(2, tournament_id, 10).await.unwrap();
+
+        env.set_state(1, tournament_id, TournamentStateKind::Paused)
+            .await
+            .unwrap();
+        let err = env.submit(2, tournament_id, 10).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentNotRunning));
+        let err = env.advance(1, tournament_id).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentNotRunning));
+        let err = env.join(3, tournament_id).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentStarted));
+
+        env.set_state(1, tournament_id, TournamentStateKind::Running)
+            .await
+            .unwrap();
+        // finishing has to go through finalization
+        let err = env
+            .set_state(1, tournament_id, TournamentStateKind::Finished)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AppError::TournamentStateTransitionInvalid));
+        env.finalize(1, tournament_id).await.unwrap();
+        let err = env.submit(2, tournament_id, 10).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentFinished));
+        let err = env
+            .set_state(1, tournament_id, TournamentStateKind::Running)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AppError::TournamentStateTransitionInvalid));
+
+        // archiving through the state also hides the tournament
+        assert_eq!(active_tournament_count(&env.db).await.unwrap(), 1);
+        env.set_state(1, tournament_id, TournamentStateKind::Archived)
+            .await
+            .unwrap();
+        assert_eq!(active_tournament_count(&env.db).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn finalizing_locks_in_rankings() {
+        let env = env();
+        let mut props = tournament_props(1);
+        // no random incentives, so the balances are known
+        props.incentive_multiplier = 0;
+        let tournament_id = env.new_tournament(props).await.unwrap();
+        for user_id in 2..=4 {
+            env.join(user_id, tournament_id).await.unwrap();
+        }
+
+        let err = env.finalize(1, tournament_id).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentNotRunning));
+        env.set_state(1, tournament_id, TournamentStateKind::Running)
+            .await
+            .unwrap();
+        let err = env.finalize(2, tournament_id).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentNonexistent));
+
+        // 60 units sell for 40 each, and cost 10 to make
+        env.submit(2, tournament_id, 20).await.unwrap();
+        env.submit(3, tournament_id, 40).await.unwrap();
+        env.advance(1, tournament_id).await.unwrap();
+        env.submit(2, tournament_id, 30).await.unwrap();
+        env.submit(3, tournament_id, 30).await.unwrap();
+        env.advance(1, tournament_id).await.unwrap();
+        env.advance(1, tournament_id).await.unwrap();
+        // a submission in the last year, which finalizing closes
+        env.submit(4, tournament_id, 100).await.unwrap();
+
+        // a paused game can be finalized too
+        env.set_state(1, tournament_id, TournamentStateKind::Paused)
+            .await
+            .unwrap();
+        let results = env.finalize(1, tournament_id).await.unwrap();
+        let summary: Vec<_> = results
+            .iter()
+            .map(|r| (r.user_id.unwrap(), r.rank, r.balance))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![(3, 1, 2100.0), (2, 2, 1500.0), (4, 3, -1000.0)]
+        );
+        assert!(results
+            .iter()
+            .all(|r| r.tournament.state == TournamentStateKind::Finished));
+
+        // the players who didn't submit in the last year got a 0
+        let submissions = env.submissions(tournament_id).await;
+        assert_eq!(
+            submissions
+                .iter()
+                .filter(|s| s.year == 3 && s.autogenerated)
+                .count(),
+            2
+        );
+
+        // nothing can change the results anymore
+        let err = env.finalize(1, tournament_id).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentFinished));
+        let err = env.submit(2, tournament_id, 10).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentFinished));
+        let err = env.advance(1, tournament_id).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentFinished));
+
+        // anyone can see a public tournament's,
+        // though only the creator and the winner know who won
+        for user_id in 1..=5 {
+            let seen = env.final_results(user_id, tournament_id).await.unwrap();
+            assert_eq!(seen.len(), 3);
+            assert_eq!(seen[0].user_id, [1, 3].contains(&user_id).then_some(3));
+        }
+    }
+
+    #[tokio::test]
+    async fn years_advance_on_their_own_after_the_deadline() {
+        // far enough in the future that every deadline has passed
+        const LATER: i64 = i64::MAX / 2;
+
+        let env = env();
+        let mut props = tournament_props(1);
+        props.year_duration_ms = Some(60_000);
+        let tournament_id = env.new_tournament(props).await.unwrap();
+        env.join(2, tournament_id).await.unwrap();
+
+        // the clock only starts with the game
+        assert_eq!(env.current_year(tournament_id).await.deadline, None);
+        assert_eq!(advance_due_years(&env.db, LATER).await.unwrap(), 0);
+        env.set_state(1, tournament_id, TournamentStateKind::Running)
+            .await
+            .unwrap();
+        let deadline = env.current_year(tournament_id).await.deadline.unwrap();
+
+        assert_eq!(advance_due_years(&env.db, deadline - 1).await.unwrap(), 0);
+        assert_eq!(advance_due_years(&env.db, deadline).await.unwrap(), 1);
+        let year = env.current_year(tournament_id).await;
+        assert_eq!(year.current_year, 1);
+        // player 2 never submitted, so they got a 0 for year 0
+        assert_eq!(env.submissions(tournament_id).await.len(), 1);
+
+        // nothing moves while paused
+        let deadline = year.deadline.unwrap();
+        env.set_state(1, tournament_id, TournamentStateKind::Paused)
+            .await
+            .unwrap();
+        assert_eq!(advance_due_years(&env.db, LATER).await.unwrap(), 0);
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        env.set_state(1, tournament_id, TournamentStateKind::Running)
+            .await
+            .unwrap();
+
+        // the pause was recorded, and the deadline moved back by as long as it lasted
+        let pauses = env.pauses(tournament_id).await;
+        assert_eq!(pauses.len(), 1);
+        let paused_for = pauses[0].end_time - pauses[0].start_time;
+        assert!(paused_for >= 20);
+        let year = env.current_year(tournament_id).await;
+        assert_eq!(year.current_year, 1);
+        assert_eq!(year.deadline, Some(deadline + paused_for));
+
+        // the last year ends the game
+        assert_eq!(advance_due_years(&env.db, LATER).await.unwrap(), 1);
+        assert_eq!(advance_due_years(&env.db, LATER).await.unwrap(), 1);
+        assert_eq!(env.current_year(tournament_id).await.current_year, 3);
+        assert_eq!(advance_due_years(&env.db, LATER).await.unwrap(), 1);
+        let results = env.final_results(2, tournament_id).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].tournament.state, TournamentStateKind::Finished);
+        assert_eq!(advance_due_years(&env.db, LATER).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn one_broken_tournament_does_not_hold_up_the_rest() {
+        let env = env();
+        let mut props = tournament_props(1);
+        props.year_duration_ms = Some(60_000);
+        let tournament_id = env.new_tournament(props).await.unwrap();
+        env.join(2, tournament_id).await.unwrap();
+        env.set_state(1, tournament_id, TournamentStateKind::Running)
+            .await
+            .unwrap();
+        let deadline = env.current_year(tournament_id).await.deadline.unwrap();
+
+        // a running year for a tournament that doesn't exist, due before the real one
+        let mut sp = env.db.begin().await.unwrap();
+        sp.tournament_state_add(1, 999, TournamentStateKind::Running)
+            .await
+            .unwrap();
+        sp.tournament_year_add(1, 999, 0, Some(deadline - 1))
+            .await
+            .unwrap();
+        sp.commit().await.unwrap();
+
+        assert_eq!(advance_due_years(&env.db, deadline).await.unwrap(), 1);
+        assert_eq!(env.current_year(tournament_id).await.current_year, 1);
+    }
+
+    #[tokio::test]
+    async fn late_joins_follow_the_policy() {
+        let env = env();
+        let mut tournament_ids = vec![];
+        for policy in [
+            LateJoinPolicyKind::Forbidden,
+            LateJoinPolicyKind::CatchUp,
+            LateJoinPolicyKind::Observer,
+        ] {
+            let mut props = tournament_props(1);
+            props.late_join_policy = Some(policy);
+            let tournament_id = env.new_tournament(props).await.unwrap();
+            env.join(2, tournament_id).await.unwrap();
+            env.set_state(1, tournament_id, TournamentStateKind::Running)
+                .await
+                .unwrap();
+            env.advance(1, tournament_id).await.unwrap();
+            tournament_ids.push(tournament_id);
+        }
+        let [forbidden, catch_up, observer] = tournament_ids[..] else {
+            unreachable!()
+        };
+
+        let err = env.join(3, forbidden).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentStarted));
+
+        // catching up starts with a demand for the current year, not year 0
+        let membership = env.membership(3, catch_up, true).await.unwrap();
+        assert!(!membership.observer);
+        let demands = env.demands(catch_up).await;
+        let late: Vec<_> = demands.iter().filter(|d| d.user_id == Some(3)).collect();
+        assert_eq!(late.len(), 1);
+        assert_eq!(late[0].year, 1);
+        env.submit(3, catch_up, 10).await.unwrap();
+        env.advance(1, catch_up).await.unwrap();
+        assert_eq!(
+            env.demands(catch_up)
+                .await
+                .iter()
+                .filter(|d| d.user_id == Some(3))
+                .count(),
+            2
+        );
+
+        // observers watch, but never play
+        let membership = env.membership(3, observer, true).await.unwrap();
+        assert!(membership.observer);
+        let err = env.submit(3, observer, 10).await.unwrap_err();
+        assert!(matches!(err, AppError::Unauthorized));
+        env.advance(1, observer).await.unwrap();
+        assert!(env
+            .demands(observer)
+            .await
+            .iter()
+            .all(|d| d.user_id == Some(2)));
+        assert!(env
+            .submissions(observer)
+            .await
+            .iter()
+            .all(|s| s.creator_user_id == Some(2)));
+        let results = env.finalize(1, observer).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(env.final_results(3, observer).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn leaving_follows_the_exit_policy() {
+        let env = env();
+        for policy in [ExitPolicyKind::Forfeit, ExitPolicyKind::KeepBalance] {
+            let mut props = tournament_props(1);
+            props.incentive_multiplier = 0;
+            props.exit_policy = Some(policy);
+            let tournament_id = env.new_tournament(props).await.unwrap();
+            env.join(2, tournament_id).await.unwrap();
+            env.join(3, tournament_id).await.unwrap();
+            env.set_state(1, tournament_id, TournamentStateKind::Running)
+                .await
+                .unwrap();
+
+            env.submit(2, tournament_id, 20).await.unwrap();
+            env.submit(3, tournament_id, 40).await.unwrap();
+            env.advance(1, tournament_id).await.unwrap();
+
+            // leaving mid-game is allowed, but that's the end of playing
+            let membership = env.membership(3, tournament_id, false).await.unwrap();
+            assert!(!membership.active);
+            let err = env.submit(3, tournament_id, 10).await.unwrap_err();
+            assert!(matches!(err, AppError::Unauthorized));
+            env.advance(1, tournament_id).await.unwrap();
+            assert!(!env
+                .demands(tournament_id)
+                .await
+                .iter()
+                .any(|d| d.user_id == Some(3) && d.year == 2));
+            assert!(!env
+                .submissions(tournament_id)
+                .await
+                .iter()
+                .any(|s| s.creator_user_id == Some(3) && s.year > 0));
+
+            let results: Vec<_> = env
+                .finalize(1, tournament_id)
+                .await
+                .unwrap()
+                .iter()
+                .map(|r| (r.user_id.unwrap(), r.rank, r.balance))
+                .collect();
+            match policy {
+                ExitPolicyKind::Forfeit => assert_eq!(results, vec![(2, 1, 600.0)]),
+                ExitPolicyKind::KeepBalance => {
+                    assert_eq!(results, vec![(3, 1, 1200.0), (2, 2, 600.0)])
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn joining_and_leaving_are_idempotent() {
+        let env = env();
+        let tournament_id = env.new_tournament(tournament_props(1)).await.unwrap();
+
+        // can't leave what you never joined
+        let err = env.membership(2, tournament_id, false).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentMembershipInvalid));
+
+        let first = env.membership(2, tournament_id, true).await.unwrap();
+        let second = env.membership(2, tournament_id, true).await.unwrap();
+        assert_eq!(
+            first.tournament_membership_id,
+            second.tournament_membership_id
+        );
+
+        // leaving and rejoining doesn't hand out a second demand for the year
+        env.membership(2, tournament_id, false).await.unwrap();
+        env.membership(2, tournament_id, false).await.unwrap();
+        env.membership(2, tournament_id, true).await.unwrap();
+        assert_eq!(env.demands(tournament_id).await.len(), 1);
+
+        // returning players may rejoin even when late joins are forbidden
+        env.set_state(1, tournament_id, TournamentStateKind::Running)
+            .await
+            .unwrap();
+        env.membership(2, tournament_id, false).await.unwrap();
+        env.advance(1, tournament_id).await.unwrap();
+        env.membership(2, tournament_id, true).await.unwrap();
+        env.submit(2, tournament_id, 10).await.unwrap();
+        let years: Vec<_> = env
+            .demands(tournament_id)
+            .await
+            .iter()
+            .map(|d| d.year)
+            .collect();
+        assert_eq!(years, vec![0, 1]);
+    }
+
+    #[tokio::test]
+    async fn retries_with_the_same_idempotency_key_are_replayed() {
+        let env = env();
+        let mut props = tournament_props(1);
+        props.idempotency_key = Some("create".to_owned());
+        let tournament_id = env.new_tournament(props.clone()).await.unwrap();
+        assert_eq!(env.new_tournament(props).await.unwrap(), tournament_id);
+
+        env.join(2, tournament_id).await.unwrap();
+        env.set_state(1, tournament_id, TournamentStateKind::Running)
+            .await
+            .unwrap();
+
+        let advance = |idempotency_key: &str| {
+            tournament_year_new(
+                env.config.clone(),
+                env.db.clone(),
+                env.auth_service.clone(),
+                request::TournamentYearNewProps {
+                    tournament_id,
+                    api_key: api_key(1),
+                    idempotency_key: Some(idempotency_key.to_owned()),
+                },
+            )
+        };
+
+        // a double click only advances once
+        let first = advance("a").await.unwrap();
+        let second = advance("a").await.unwrap();
+        assert_eq!(first.tournament_year_id, second.tournament_year_id);
+        assert_eq!(second.current_year, 1);
+        assert_eq!(advance("b").await.unwrap().current_year, 2);
+
+        // keys are per user and endpoint, so reusing "a" here is fine
+        // but not for a different request
+        let submit = |user_id: i64, amount: i64| {
+            tournament_submission_new(
+                env.config.clone(),
+                env.db.clone(),
+                env.auth_service.clone(),
+                request::TournamentSubmissionNewProps {
+                    tournament_id,
+                    amount,
+                    api_key: api_key(user_id),
+                    idempotency_key: Some("a".to_owned()),
+                },
+            )
+        };
+        let first = submit(2, 10).await.unwrap();
+        assert!(matches!(
+            submit(2, 20).await.unwrap_err(),
+            AppError::IdempotencyKeyReused
+        ));
+        let second = submit(2, 10).await.unwrap();
+        assert_eq!(
+            first.tournament_submission_id,
+            second.tournament_submission_id
+        );
+        assert_eq!(
+            env.submissions(tournament_id)
+                .await
+                .iter()
+                .filter(|s| !s.autogenerated)
+                .count(),
+            1
+        );
+
+        // once the window has passed the key is forgotten and can be used again
+        let later = utils::current_time_millis() + env.config.idempotency_window_ms + 1;
+        assert_eq!(
+            prune_idempotency_keys(&env.db, &env.config, later)
+                .await
+                .unwrap(),
+            4
+        );
+        assert_eq!(
+            prune_idempotency_keys(&env.db, &env.config, later)
+                .await
+                .unwrap(),
+            0
+        );
+        assert_eq!(advance("a").await.unwrap().current_year, 3);
+    }
+
+    #[tokio::test]
+    async fn organizers_help_run_the_tournament() {
+        let env = env();
+        let tournament_id = env.new_tournament(tournament_props(1)).await.unwrap();
+        env.join(2, tournament_id).await.unwrap();
+
+        // strangers can't run it
+        let err = env
+            .set_state(5, tournament_id, TournamentStateKind::Running)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AppError::TournamentNonexistent));
+
+        let role = env
+            .grant(1, tournament_id, 5, TournamentRoleKind::Organizer, true)
+            .await
+            .unwrap();
+        assert_eq!(role.role, TournamentRoleKind::Organizer);
+        env.grant(1, tournament_id, 6, TournamentRoleKind::Observer, true)
+            .await
+            .unwrap();
+
+        // organizers run the game, but don't decide who else may
+        env.set_state(5, tournament_id, TournamentStateKind::Running)
+            .await
+            .unwrap();
+        assert_eq!(env.advance(5, tournament_id).await.unwrap(), 1);
+        let err = env
+            .grant(5, tournament_id, 7, TournamentRoleKind::Organizer, true)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AppError::TournamentNonexistent));
+
+        // observers only watch, and staff can't play
+        let err = env.advance(6, tournament_id).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentNonexistent));
+        let err = env.join(6, tournament_id).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentMembershipInvalid));
+        assert!(env.final_results(6, tournament_id).await.is_ok());
+
+        // players can't be staff, and nobody can change their own role
+        let err = env
+            .grant(1, tournament_id, 2, TournamentRoleKind::Organizer, true)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AppError::TournamentRoleInvalid));
+        let err = env
+            .grant(1, tournament_id, 1, TournamentRoleKind::Observer, true)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AppError::TournamentRoleInvalid));
+
+        // revoking takes effect right away
+        env.grant(1, tournament_id, 5, TournamentRoleKind::Organizer, false)
+            .await
+            .unwrap();
+        let err = env.advance(5, tournament_id).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentNonexistent));
+        let err = env.pseudonyms(5, tournament_id).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentNonexistent));
+    }
+
+    #[tokio::test]
+    async fn benchmarks_follow_the_tournament_visibility() {
+        let env = env();
+        let public_id = env.new_tournament(tournament_props(1)).await.unwrap();
+        let mut props = tournament_props(1);
+        props.visibility = Some(VisibilityKind::ApprovalRequired);
+        let private_id = env.new_tournament(props).await.unwrap();
+
+        // anyone signed in can see a public tournament's
+        assert!(env.benchmarks(5, public_id).await.is_ok());
+        let err = tournament_benchmarks_view(
+            env.config.clone(),
+            env.db.clone(),
+            env.auth_service.clone(),
+            request::TournamentBenchmarksViewProps {
+                tournament_id: public_id,
+                api_key: "nope".to_owned(),
+            },
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, AppError::Unauthorized));
+
+        // but only members who were let in and organizers a private one's
+        let err = env.benchmarks(5, private_id).await.unwrap_err();
+        assert!(matches!(err, AppError::Unauthorized));
+        env.join(2, private_id).await.unwrap();
+        let err = env.benchmarks(2, private_id).await.unwrap_err();
+        assert!(matches!(err, AppError::Unauthorized));
+        env.approve(1, private_id, 2, true).await.unwrap();
+        assert!(env.benchmarks(2, private_id).await.is_ok());
+        assert!(env.benchmarks(1, private_id).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn private_markets_are_hidden_from_outsiders() {
+        let env = env();
+        let mut props = tournament_props(1);
+        props.visibility = Some(VisibilityKind::ApprovalRequired);
+        let tournament_id = env.new_tournament(props).await.unwrap();
+        for user_id in [2, 3] {
+            env.join(user_id, tournament_id).await.unwrap();
+            env.approve(1, tournament_id, user_id, true).await.unwrap();
+        }
+        // asked to join, but nobody let them in
+        env.join(4, tournament_id).await.unwrap();
+        env.set_state(1, tournament_id, TournamentStateKind::Running)
+            .await
+            .unwrap();
+        env.submit(2, tournament_id, 10).await.unwrap();
+        env.submit(3, tournament_id, 20).await.unwrap();
+        env.moderate(1, tournament_id, 3, request::MembershipActionKind::Ban)
+            .await
+            .unwrap();
+
+        // what each viewer gets from the submission and demand views
+        let seen = |user_id: i64| {
+            let env = &env;
+            async move {
+                let submissions = env.submissions_with(user_id, tournament_id, None).await;
+                let demands = env.demands_with(user_id, tournament_id, None).await;
+                (submissions.len(), demands.len())
+            }
+        };
+        for user_id in [1, 2] {
+            let (submissions, demands) = seen(user_id).await;
+            assert_eq!(submissions, 2);
+            assert!(demands > 0);
+        }
+        // not a member, still waiting, and banned
+        for user_id in [5, 4, 3] {
+            assert_eq!(seen(user_id).await, (0, 0));
+            let err = env.final_results(user_id, tournament_id).await.unwrap_err();
+            assert!(matches!(err, AppError::Unauthorized));
+        }
+    }
+
+    #[tokio::test]
+    async fn invite_codes_gate_first_joins() {
+        let env = env();
+        let mut props = tournament_props(1);
+        props.visibility = Some(VisibilityKind::InviteCode);
+        let tournament_id = env.new_tournament(props).await.unwrap();
+
+        let join = |user_id: i64, invite_code: Option<String>| {
+            tournament_membership_new(
+                env.config.clone(),
+                env.db.clone(),
+                env.auth_service.clone(),
+                request::TournamentMembershipNewProps {
+                    tournament_id,
+                    active: true,
+                    invite_code,
+                    tournament_team_id: None,
+                    api_key: api_key(user_id),
+                    idempotency_key: None,
+                },
+            )
+        };
+        let invite_code = |user_id: i64| {
+            tournament_invite_code_view(
+                env.config.clone(),
+                env.db.clone(),
+                env.auth_service.clone(),
+                request::TournamentInviteCodeViewProps {
+                    tournament_id,
+                    api_key: api_key(user_id),
+                },
+            )
+        };
+        let rotate = |user_id: i64, tournament_id: i64| {
+            tournament_invite_code_new(
+                env.config.clone(),
+                env.db.clone(),
+                env.auth_service.clone(),
+                request::TournamentInviteCodeNewProps {
+                    tournament_id,
+                    api_key: api_key(user_id),
+                    idempotency_key: None,
+                },
+            )
+        };
+
+        // only organizers see the code
+        let err = invite_code(2).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentNonexistent));
+        let old_code = invite_code(1).await.unwrap()[0].invite_code.clone();
+
+        let err = join(2, None).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentInviteCodeInvalid));
+        let err = join(2, Some("wrong".to_owned())).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentInviteCodeInvalid));
+        join(2, Some(old_code.clone())).await.unwrap();
+
+        // rotating locks out the old code, but not those who already joined
+        let new_code = rotate(1, tournament_id).await.unwrap().invite_code;
+        assert_ne!(new_code, old_code);
+        let err = join(3, Some(old_code)).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentInviteCodeInvalid));
+        join(3, Some(new_code)).await.unwrap();
+        env.membership(2, tournament_id, false).await.unwrap();
+        join(2, None).await.unwrap();
+
+        // public tournaments don't have codes
+        let public_id = env.new_tournament(tournament_props(1)).await.unwrap();
+        let err = rotate(1, public_id).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentVisibilityInvalid));
+    }
+
+    #[tokio::test]
+    async fn organizers_approve_members_before_the_start() {
+        let env = env();
+        let mut props = tournament_props(1);
+        props.visibility = Some(VisibilityKind::ApprovalRequired);
+        let tournament_id = env.new_tournament(props).await.unwrap();
+
+        for user_id in [2, 3, 4] {
+            let membership = env.membership(user_id, tournament_id, true).await.unwrap();
+            assert_eq!(membership.approval, ApprovalKind::Pending);
+        }
+        // pending members don't get a demand yet
+        assert!(env.demands(tournament_id).await.is_empty());
+
+        let membership = env.approve(1, tournament_id, 2, true).await.unwrap();
+        assert_eq!(membership.approval, ApprovalKind::Approved);
+        assert!(membership.active);
+        let membership = env.approve(1, tournament_id, 3, false).await.unwrap();
+        assert_eq!(membership.approval, ApprovalKind::Rejected);
+        assert!(!membership.active);
+        let err = env.join(3, tournament_id).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentMembershipRejected));
+
+        // only organizers decide, and only once per member
+        let err = env.approve(2, tournament_id, 4, true).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentNonexistent));
+        let err = env.approve(1, tournament_id, 2, false).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentMembershipInvalid));
+
+        let demands: Vec<_> = env
+            .demands(tournament_id)
+            .await
+            .iter()
+            .map(|d| d.user_id.unwrap())
+            .collect();
+        assert_eq!(demands, vec![2]);
+
+        // once the game starts, the queue is closed
+        env.set_state(1, tournament_id, TournamentStateKind::Running)
+            .await
+            .unwrap();
+        let err = env.approve(1, tournament_id, 4, true).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentStarted));
+        let err = env.join(5, tournament_id).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentStarted));
+        let err = env.submit(4, tournament_id, 10).await.unwrap_err();
+        assert!(matches!(err, AppError::Unauthorized));
+
+        env.submit(2, tournament_id, 10).await.unwrap();
+        let results = env.finalize(1, tournament_id).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].user_id, Some(2));
+    }
+
+    #[tokio::test]
+    async fn organizers_remove_ban_and_reinstate_members() {
+        use request::MembershipActionKind::*;
+
+        let env = env();
+        let mut props = tournament_props(1);
+        props.incentive_multiplier = 0;
+        let tournament_id = env.new_tournament(props).await.unwrap();
+        for user_id in [2, 3, 4] {
+            env.join(user_id, tournament_id).await.unwrap();
+        }
+        env.set_state(1, tournament_id, TournamentStateKind::Running)
+            .await
+            .unwrap();
+        for user_id in [2, 3, 4] {
+            env.submit(user_id, tournament_id, 10).await.unwrap();
+        }
+        env.advance(1, tournament_id).await.unwrap();
+
+        // players can't moderate each other
+        let err = env.moderate(2, tournament_id, 3, Remove).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentNonexistent));
+
+        let membership = env.moderate(1, tournament_id, 3, Remove).await.unwrap();
+        assert!(membership.removed && !membership.active && !membership.banned);
+        assert_eq!(membership.actor_user_id, Some(1));
+        assert_eq!(membership.reason.as_deref(), Some("test"));
+        let err = env.moderate(1, tournament_id, 3, Remove).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentMembershipInvalid));
+        let err = env.submit(3, tournament_id, 10).await.unwrap_err();
+        assert!(matches!(err, AppError::Unauthorized));
+
+        // banned members can't come back on their own
+        env.moderate(1, tournament_id, 4, Ban).await.unwrap();
+        let err = env.join(4, tournament_id).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentMembershipBanned));
+
+        // nor can removed ones, until they're reinstated and catch up from the current year
+        let err = env.join(3, tournament_id).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentMembershipRemoved));
+        let membership = env.moderate(1, tournament_id, 3, Reinstate).await.unwrap();
+        assert!(membership.active && !membership.removed);
+        env.submit(3, tournament_id, 10).await.unwrap();
+        env.moderate(1, tournament_id, 3, Remove).await.unwrap();
+
+        // removed players get no demand, and aren't ranked even though balances are kept
+        env.advance(1, tournament_id).await.unwrap();
+        assert!(env
+            .demands(tournament_id)
+            .await
+            .iter()
+            .all(|d| d.year < 2 || d.user_id == Some(2)));
+        let results: Vec<_> = env
+            .finalize(1, tournament_id)
+            .await
+            .unwrap()
+            .iter()
+            .map(|r| r.user_id.unwrap())
+            .collect();
+        assert_eq!(results, vec![2]);
+    }
+
+    #[tokio::test]
+    async fn lobbies_fill_up_and_start_on_their_own() {
+        use request::MembershipActionKind::*;
+
+        let env = env();
+        let mut props = tournament_props(1);
+        props.min_players = Some(2);
+        props.max_players = Some(3);
+        let tournament_id = env.new_tournament(props.clone()).await.unwrap();
+
+        // without auto start, the organizer still starts it, but not too early
+        env.join(2, tournament_id).await.unwrap();
+        let err = env
+            .set_state(1, tournament_id, TournamentStateKind::Running)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AppError::TournamentNotEnoughPlayers));
+        env.join(3, tournament_id).await.unwrap();
+        env.join(4, tournament_id).await.unwrap();
+
+        // full lobbies turn players away, but leaving frees a seat
+        let err = env.join(5, tournament_id).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentFull));
+        env.membership(4, tournament_id, false).await.unwrap();
+        env.join(5, tournament_id).await.unwrap();
+        let err = env.join(4, tournament_id).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentFull));
+        env.moderate(1, tournament_id, 5, Remove).await.unwrap();
+        env.join(4, tournament_id).await.unwrap();
+        let err = env
+            .moderate(1, tournament_id, 5, Reinstate)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AppError::TournamentFull));
+        env.set_state(1, tournament_id, TournamentStateKind::Running)
+            .await
+            .unwrap();
+
+        // with auto start, the player who fills the minimum starts the game
+        props.auto_start = Some(true);
+        props.late_join_policy = Some(LateJoinPolicyKind::CatchUp);
+        let tournament_id = env.new_tournament(props).await.unwrap();
+        let membership = env.membership(2, tournament_id, true).await.unwrap();
+        assert_eq!(membership.tournament.state, TournamentStateKind::Lobby);
+        let membership = env.membership(3, tournament_id, true).await.unwrap();
+        assert_eq!(membership.tournament.state, TournamentStateKind::Running);
+        env.submit(2, tournament_id, 10).await.unwrap();
+        assert_eq!(env.advance(1, tournament_id).await.unwrap(), 1);
+
+        // the cap still holds for players joining late
+        env.join(4, tournament_id).await.unwrap();
+        let err = env.join(5, tournament_id).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentFull));
+    }
+    #[tokio::test]
+    async fn teams_play_as_one_firm() {
+        let env = env();
+        let mut props = tournament_props(1);
+        props.incentive_multiplier = 0;
+        let tournament_id = env.new_tournament(props).await.unwrap();
+        let other_id = env.new_tournament(tournament_props(1)).await.unwrap();
+
+        // organizers set up the teams, and players pick one when they join
+        let err = env.team(2, tournament_id, "red").await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentNonexistent));
+        let red = env.team(1, tournament_id, "red").await.unwrap();
+        let blue = env.team(1, other_id, "blue").await.unwrap();
+        let err = env.join_team(2, tournament_id, blue).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentTeamInvalid));
+        let membership = env.join_team(2, tournament_id, red).await.unwrap();
+        assert_eq!(membership.tournament_team_id, Some(red));
+        env.join_team(3, tournament_id, red).await.unwrap();
+        env.join(4, tournament_id).await.unwrap();
+
+        // one demand per firm
+        let demands = env.demands(tournament_id).await;
+        assert_eq!(demands.len(), 2);
+        assert!(demands
+            .iter()
+            .any(|d| d.tournament_team_id == Some(red) && d.user_id.is_none()));
+
+        // teammates overwrite each other, and the submission says who acted
+        env.set_state(1, tournament_id, TournamentStateKind::Running)
+            .await
+            .unwrap();
+        env.submit(2, tournament_id, 10).await.unwrap();
+        env.submit(3, tournament_id, 20).await.unwrap();
+        env.submit(4, tournament_id, 20).await.unwrap();
+        env.advance(1, tournament_id).await.unwrap();
+        let submissions = env.submissions(tournament_id).await;
+        let team_submissions: Vec<_> = submissions
+            .iter()
+            .filter(|s| s.tournament_team_id == Some(red))
+            .map(|s| (s.creator_user_id, s.amount, s.autogenerated))
+            .collect();
+        assert_eq!(
+            team_submissions,
+            vec![(Some(2), 10, false), (Some(3), 20, false)]
+        );
+
+        // the team stays on the market while anyone still plays for it
+        env.membership(2, tournament_id, false).await.unwrap();
+        let err = env.join_team(2, tournament_id, blue).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentTeamInvalid));
+        let results = env.finalize(1, tournament_id).await.unwrap();
+        let results: Vec<_> = results
+            .iter()
+            .map(|r| (r.user_id, r.tournament_team_id, r.rank))
+            .collect();
+        assert_eq!(results, vec![(None, Some(red), 1), (Some(4), None, 1)]);
+        assert_eq!(
+            env.submissions(tournament_id)
+                .await
+                .iter()
+                .filter(|s| s.autogenerated && s.tournament_team_id == Some(red))
+                .count(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn pseudonyms_hide_who_plays() {
+        let env = env();
+        let tournament_id = env.new_tournament(tournament_props(1)).await.unwrap();
+        let red = env.team(1, tournament_id, "red").await.unwrap();
+        let first = env.membership(2, tournament_id, true).await.unwrap();
+        env.join(3, tournament_id).await.unwrap();
+        env.join_team(4, tournament_id, red).await.unwrap();
+
+        // every member gets a distinct name, and keeps it on rejoin
+        let pseudonym = first.pseudonym.unwrap();
+        assert!(pseudonym.starts_with("Firm "));
+        env.membership(2, tournament_id, false).await.unwrap();
+        let rejoined = env.membership(2, tournament_id, true).await.unwrap();
+        assert_eq!(rejoined.pseudonym, Some(pseudonym.clone()));
+        let names: BTreeSet<_> = env
+            .pseudonyms(1, tournament_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|p| p.pseudonym)
+            .collect();
+        assert_eq!(names.len(), 3);
+
+        // only organizers can see who is behind a name
+        let err = env.pseudonyms(2, tournament_id).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentNonexistent));
+
+        env.set_state(1, tournament_id, TournamentStateKind::Running)
+            .await
+            .unwrap();
+        env.submit(2, tournament_id, 10).await.unwrap();
+        let submissions = env.submissions(tournament_id).await;
+        assert_eq!(submissions[0].pseudonym, Some(pseudonym.clone()));
+
+        // teams are listed under their own name
+        let results = env.finalize(1, tournament_id).await.unwrap();
+        let red_result = results
+            .iter()
+            .find(|r| r.tournament_team_id == Some(red))
+            .unwrap();
+        assert_eq!(red_result.pseudonym.as_deref(), Some("red"));
+        assert!(results
+            .iter()
+            .any(|r| r.user_id == Some(2) && r.pseudonym == Some(pseudonym.clone())));
+    }
+
+    #[tokio::test]
+    async fn only_organizers_see_who_is_behind_a_pseudonym() {
+        let env = env();
+        let tournament_id = env.new_tournament(tournament_props(1)).await.unwrap();
+        env.join(2, tournament_id).await.unwrap();
+        env.join(3, tournament_id).await.unwrap();
+        env.set_state(1, tournament_id, TournamentStateKind::Running)
+            .await
+            .unwrap();
+        env.submit(2, tournament_id, 10).await.unwrap();
+        env.submit(3, tournament_id, 20).await.unwrap();
+        env.advance(1, tournament_id).await.unwrap();
+        env.finalize(1, tournament_id).await.unwrap();
+
+        // the user ids in what each of the views shows the viewer
+        let seen = |viewer: i64, creator_user_id: Option<Vec<i64>>| {
+            let env = &env;
+            async move {
+                let mut ids = vec![];
+                let memberships = tournament_membership_view(
+                    env.config.clone(),
+                    env.db.clone(),
+                    env.auth_service.clone(),
+                    request::TournamentMembershipViewProps {
+                        tournament_membership_id: None,
+                        min_creation_time: None,
+                        max_creation_time: None,
+                        creator_user_id: creator_user_id.clone(),
+                        tournament_id: Some(vec![tournament_id]),
+                        active: None,
+                        approval: None,
+                        only_recent: false,
+                        api_key: api_key(viewer),
+                    },
+                )
+                .await
+                .unwrap();
+                for m in memberships {
+                    ids.extend(m.creator_user_id);
+                    ids.extend(m.actor_user_id);
+                }
+                let submissions = tournament_submission_view(
+                    env.config.clone(),
+                    env.db.clone(),
+                    env.auth_service.clone(),
+                    request::TournamentSubmissionViewProps {
+                        tournament_submission_id: None,
+                        min_creation_time: None,
+                        max_creation_time: None,
+                        creator_user_id: creator_user_id.clone(),
+                        tournament_id: Some(vec![tournament_id]),
+                        tournament_team_id: None,
+                        practice: None,
+                        api_key: api_key(viewer),
+                    },
+                )
+                .await
+                .unwrap();
+                ids.extend(submissions.into_iter().filter_map(|s| s.creator_user_id));
+                let demands = tournament_year_demand_view(
+                    env.config.clone(),
+                    env.db.clone(),
+                    env.auth_service.clone(),
+                    request::TournamentYearDemandViewProps {
+                        tournament_year_demand_id: None,
+                        min_creation_time: None,
+                        max_creation_time: None,
+                        user_id: creator_user_id.clone(),
+                        tournament_id: Some(vec![tournament_id]),
+                        tournament_team_id: None,
+                        practice: None,
+                        api_key: api_key(viewer),
+                    },
+                )
+                .await
+                .unwrap();
+                ids.extend(demands.into_iter().filter_map(|d| d.user_id));
+                if let Ok(results) = tournament_final_result_view(
+                    env.config.clone(),
+                    env.db.clone(),
+                    env.auth_service.clone(),
+                    request::TournamentFinalResultViewProps {
+                        tournament_id,
+                        api_key: api_key(viewer),
+                    },
+                )
+                .await
+                {
+                    ids.extend(results.into_iter().filter_map(|r| r.user_id));
+                }
+                ids.into_iter().collect::<BTreeSet<i64>>()
+            }
+        };
+
+        assert_eq!(seen(1, None).await, BTreeSet::from([2, 3]));
+        // players see their own id, but nobody else's
+        assert_eq!(seen(2, None).await, BTreeSet::from([2]));
+        assert_eq!(seen(5, None).await, BTreeSet::new());
+        // and can't look someone up by id either
+        assert!(!seen(2, Some(vec![3])).await.contains(&3));
+        assert!(seen(1, Some(vec![3])).await.contains(&3));
+    }
+
+    #[test]
+    fn gen_pseudonym_numbers_names_once_colors_run_out() {
+        let mut taken = BTreeSet::new();
+        for _ in 0..PSEUDONYM_COLORS.len() + 1 {
+            taken.insert(gen_pseudonym(&taken));
+        }
+        assert_eq!(taken.len(), PSEUDONYM_COLORS.len() + 1);
+        assert_eq!(taken.iter().filter(|p| p.ends_with(" 2")).count(), 1);
+    }
+
+    #[tokio::test]
+    async fn bots_submit_before_the_year_closes() {
+        let env = env();
+        let mut props = tournament_props(1);
+        props.incentive_multiplier = 0;
+        let tournament_id = env.new_tournament(props).await.unwrap();
+
+        let err = env
+            .bot(2, tournament_id, BotStrategyKind::Nash, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AppError::TournamentNonexistent));
+        let err = env
+            .bot(1, tournament_id, BotStrategyKind::FixedQuantity, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AppError::TournamentBotInvalid));
+        let err = env
+            .bot(1, tournament_id, BotStrategyKind::Nash, Some(5))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AppError::TournamentBotInvalid));
+
+        let nash = env
+            .bot(1, tournament_id, BotStrategyKind::Nash, None)
+            .await
+            .unwrap();
+        let fixed = env
+            .bot(1, tournament_id, BotStrategyKind::FixedQuantity, Some(10))
+            .await
+            .unwrap();
+        assert_ne!(nash.name, fixed.name);
+        env.join(2, tournament_id).await.unwrap();
+        assert_eq!(env.demands(tournament_id).await.len(), 3);
+
+        // price is 100 - Q and costs are 10, so three firms make 22.5 each
+        env.set_state(1, tournament_id, TournamentStateKind::Running)
+            .await
+            .unwrap();
+        env.submit(2, tournament_id, 30).await.unwrap();
+        env.advance(1, tournament_id).await.unwrap();
+        let bot_submissions: Vec<_> = env
+            .submissions(tournament_id)
+            .await
+            .into_iter()
+            .filter_map(|s| Some((s.tournament_bot_id?, s.amount, s.pseudonym?)))
+            .collect();
+        assert_eq!(
+            bot_submissions,
+            vec![
+                (nash.tournament_bot_id, 23, nash.name.clone()),
+                (fixed.tournament_bot_id, 10, fixed.name.clone()),
+            ]
+        );
+
+        // bots keep playing until the end, and are ranked under their name
+        let results = env.finalize(1, tournament_id).await.unwrap();
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().any(|r| {
+            r.tournament_bot_id == Some(nash.tournament_bot_id)
+                && r.pseudonym == Some(nash.name.clone())
+        }));
+        assert_eq!(
+            env.submissions(tournament_id)
+                .await
+                .iter()
+                .filter(|s| s.tournament_bot_id.is_some())
+                .count(),
+            4
+        );
+    }
+
+    #[tokio::test]
+    async fn practice_games_run_on_their_own() {
+        let env = env();
+        let mut props = tournament_props(2);
+        props.practice = Some(true);
+        props.min_players = Some(2);
+        let err = env.new_tournament(props).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentPlayerCountInvalid));
+        let mut props = tournament_props(2);
+        props.practice = Some(true);
+        props.visibility = Some(VisibilityKind::InviteCode);
+        let err = env.new_tournament(props).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentVisibilityInvalid));
+
+        // the student starts out playing against the bots
+        let mut props = tournament_props(2);
+        props.practice = Some(true);
+        let tournament_id = env.new_tournament(props).await.unwrap();
+        assert_eq!(
+            env.demands_with(2, tournament_id, Some(true)).await.len(),
+            1 + PRACTICE_BOTS.len()
+        );
+        let err = env.join(3, tournament_id).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentPractice));
+
+        // every submission closes the year, the last one ends the game
+        for _ in 0..=3 {
+            env.submit(2, tournament_id, 20).await.unwrap();
+        }
+        let err = env.submit(2, tournament_id, 20).await.unwrap_err();
+        assert!(matches!(err, AppError::TournamentFinished));
+        let submissions = env.submissions_with(2, tournament_id, Some(true)).await;
+        assert_eq!(submissions.len(), 4 * (1 + PRACTICE_BOTS.len()));
+
+        // the student finds it alongside their other games, or on its own
+        for practice in [None, Some(true)] {
+            assert!(env.listed(2, tournament_id, practice).await);
+            assert_eq!(env.years(2, tournament_id, practice).await.len(), 4);
+        }
+        assert!(!env.listed(2, tournament_id, Some(false)).await);
+        assert!(env.years(2, tournament_id, Some(false)).await.is_empty());
+        // and nobody else sees it at all
+        for practice in [None, Some(true)] {
+            assert!(!env.listed(1, tournament_id, practice).await);
+            assert!(env.years(1, tournament_id, practice).await.is_empty());
+            assert!(env
+                .submissions_with(1, tournament_id, practice)
+                .await
+                .is_empty());
+            assert!(env
+                .demands_with(1, tournament_id, practice)
+                .await
+                .is_empty());
+        }
+        let other = env.new_tournament(tournament_props(1)).await.unwrap();
+        assert_eq!(env.years(1, other, None).await.len(), 1);
+        assert!(env.years(1, other, Some(true)).await.is_empty());
+
+        // the clock doesn't move practice games on, only the student does
+        let mut props = tournament_props(2);
+        props.practice = Some(true);
+        props.year_duration_ms = Some(60_000);
+        let timed = env.new_tournament(props).await.unwrap();
+        assert_eq!(advance_due_years(&env.db, i64::MAX / 2).await.unwrap(), 0);
+        let years = env.years(2, timed, None).await;
+        assert!(years.iter().any(|y| y.deadline.is_some()));
+        assert!(years.iter().all(|y| y.current_year == 0));
+
+        // and practice games don't count as active
+        assert_eq!(active_tournament_count(&env.db).await.unwrap(), 1);
+    }
 }
@@ -0,0 +1,122 @@
+use super::db_types::*;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for IdempotencyKey {
+    // select * from idempotency_key order only, otherwise it will fail
+    fn from(row: tokio_postgres::Row) -> IdempotencyKey {
+        IdempotencyKey {
+            idempotency_key_id: row.get("idempotency_key_id"),
+            creation_time: row.get("creation_time"),
+            creator_user_id: row.get("creator_user_id"),
+            endpoint: row.get("endpoint"),
+            idempotency_key: row.get("idempotency_key"),
+            request_hash: row.get("request_hash"),
+            response: row.get("response"),
+        }
+    }
+}
+
+// Returns None if the key is already taken by a use inside the window, usually a request
+// that was sent twice at once. A use from before the window is replaced.
+#[tracing::instrument(level = "debug", skip(con, response))]
+pub async fn add(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    endpoint: String,
+    idempotency_key: String,
+    request_hash: String,
+    response: String,
+    min_creation_time: i64,
+) -> Result<Option<IdempotencyKey>, tokio_postgres::Error> {
+    let row = con
+        .query_opt(
+            "INSERT INTO
+             idempotency_key(
+                 creator_user_id,
+                 endpoint,
+                 idempotency_key,
+                 request_hash,
+                 response
+             )
+             VALUES ($1, $2, $3, $4, $5)
+             ON CONFLICT (creator_user_id, endpoint, idempotency_key) DO UPDATE
+             SET creation_time = EXCLUDED.creation_time,
+                 request_hash = EXCLUDED.request_hash,
+                 response = EXCLUDED.response
+             WHERE idempotency_key.creation_time < $6
+             RETURNING idempotency_key_id, creation_time
+            ",
+            &[
+                &creator_user_id,
+                &endpoint,
+                &idempotency_key,
+                &request_hash,
+                &response,
+                &min_creation_time,
+            ],
+        )
+        .await?;
+
+    // return idempotency_key
+    Ok(row.map(|row| IdempotencyKey {
+        idempotency_key_id: row.get(0),
+        creation_time: row.get(1),
+        creator_user_id,
+        endpoint,
+        idempotency_key,
+        request_hash,
+        response,
+    }))
+}
+
+// the most recent use of the key that's still inside the window
+#[tracing::instrument(level = "debug", skip(con))]
+pub async fn get_recent(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    endpoint: &str,
+    idempotency_key: &str,
+    min_creation_time: i64,
+) -> Result<Option<IdempotencyKey>, tokio_postgres::Error> {
+    let sql = [
+        "SELECT ik.* FROM idempotency_key ik",
+        "WHERE 1 = 1",
+        "AND ik.creator_user_id = $1",
+        "AND ik.endpoint = $2",
+        "AND ik.idempotency_key = $3",
+        "AND ik.creation_time >= $4",
+        "ORDER BY ik.idempotency_key_id DESC",
+        "LIMIT 1",
+    ]
+    .join("\n");
+
+    let stmnt = con.prepare(&sql).await?;
+
+    let results = con
+        .query_opt(
+            &stmnt,
+            &[
+                &creator_user_id,
+                &endpoint,
+                &idempotency_key,
+                &min_creation_time,
+            ],
+        )
+        .await?
+        .map(|row| row.into());
+
+    Ok(results)
+}
+
+// forgets every use from before the window, returning how many there were
+#[tracing::instrument(level = "debug", skip(con))]
+pub async fn delete_expired(
+    con: &mut impl GenericClient,
+    min_creation_time: i64,
+) -> Result<u64, tokio_postgres::Error> {
+    con.execute(
+        "DELETE FROM idempotency_key WHERE creation_time < $1",
+        &[&min_creation_time],
+    )
+    .await
+}
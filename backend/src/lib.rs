@@ -0,0 +1,62 @@
+use std::sync::Arc;
+
+mod utils;
+
+// authentication
+pub mod auth_provider;
+pub mod auth_client;
+pub mod dev_auth;
+
+// response and request
+pub mod request;
+pub mod response;
+
+// db web stuff
+mod tournament_data_service;
+mod tournament_state_service;
+mod tournament_pause_service;
+mod tournament_year_service;
+mod tournament_year_demand_service;
+mod tournament_service;
+mod tournament_submission_service;
+mod tournament_membership_service;
+mod tournament_role_service;
+mod tournament_invite_code_service;
+mod tournament_bot_service;
+mod tournament_team_service;
+mod tournament_pseudonym_service;
+mod tournament_final_result_service;
+mod idempotency_key_service;
+
+// storage
+pub mod store;
+mod pg_store;
+#[cfg(test)]
+mod memory_store;
+
+pub mod api;
+pub mod db;
+pub mod db_types;
+mod analytics;
+mod bots;
+mod collusion;
+mod handlers;
+mod market;
+mod metrics;
+pub mod scheduler;
+
+#[cfg(test)]
+mod test_utils;
+
+static SERVICE_NAME: &str = "production-minigame-service";
+
+#[derive(Clone)]
+pub struct Config {
+    pub site_external_url: String,
+    // how long responses are kept around for requests retried with the same idempotency key
+    pub idempotency_window_ms: i64,
+}
+
+pub type Db = Arc<dyn store::Store>;
+
+pub type Auth = Arc<dyn auth_provider::AuthProvider>;
@@ -1,32 +1,20 @@
 use clap::Parser;
-use std::error::Error;
 use std::sync::Arc;
-use tokio_postgres::{Client, NoTls};
+use std::time::Duration;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::{oneshot, watch};
+use tracing_subscriber::EnvFilter;
 use warp::Filter;
 
-use tokio::sync::Mutex;
-
-mod utils;
-
 use auth_service_api::client::AuthService;
 
-// response and request
-mod request;
-mod response;
-
-// db web stuff
-mod tournament_data_service;
-mod tournament_year_service;
-mod tournament_year_demand_service;
-mod tournament_service;
-mod tournament_submission_service;
-mod tournament_membership_service;
+use production_minigame_service::{api, auth_client, db, dev_auth, scheduler, Auth, Config, Db};
 
-mod api;
-mod db_types;
-mod handlers;
-
-static SERVICE_NAME: &str = "production-minigame-service";
+#[derive(clap::ArgEnum, Clone)]
+enum LogFormat {
+    Json,
+    Text,
+}
 
 #[derive(Parser, Clone)]
 struct Opts {
@@ -34,71 +22,164 @@ struct Opts {
     site_external_url: String,
     #[clap(long)]
     database_url: String,
-    #[clap(long)]
-    auth_service_url: String,
+    // how many transactions can run at once
+    #[clap(long, default_value = "8")]
+    database_pool_size: usize,
+    // not needed when authenticating with --dev-auth-config
+    #[clap(long, required_unless_present = "dev-auth-config")]
+    auth_service_url: Option<String>,
+    // a JSON file mapping api keys to user ids, used instead of the auth service
+    // only for local development and CI
+    #[clap(long, conflicts_with = "auth-service-url")]
+    dev_auth_config: Option<String>,
     #[clap(long)]
     port: u16,
+    #[clap(long, default_value = "2000")]
+    auth_service_timeout_ms: u64,
+    #[clap(long, default_value = "2")]
+    auth_service_retries: u32,
+    #[clap(long, default_value = "100")]
+    auth_service_retry_backoff_ms: u64,
+    #[clap(long, default_value = "5")]
+    auth_service_failure_threshold: u32,
+    #[clap(long, default_value = "30000")]
+    auth_service_reset_timeout_ms: u64,
+    // a tracing filter directive, like "info" or "production_minigame_service=debug"
+    #[clap(long, default_value = "info")]
+    log_level: String,
+    #[clap(long, arg_enum, default_value = "json")]
+    log_format: LogFormat,
+    // how long in-flight requests get to finish after a shutdown signal
+    #[clap(long, default_value = "30")]
+    shutdown_timeout_secs: u64,
+    // how often to check for tournament years past their deadline
+    #[clap(long, default_value = "1000")]
+    scheduler_interval_ms: u64,
+    // how long to remember responses, so requests retried with the same key aren't redone
+    #[clap(long, default_value = "86400000")]
+    idempotency_window_ms: i64,
 }
 
-#[derive(Clone)]
-pub struct Config {
-    pub site_external_url: String,
-}
-
-pub type Db = Arc<Mutex<Client>>;
-
 #[tokio::main]
 async fn main() {
     let Opts {
         database_url,
+        database_pool_size,
         site_external_url,
         auth_service_url,
+        dev_auth_config,
         port,
+        auth_service_timeout_ms,
+        auth_service_retries,
+        auth_service_retry_backoff_ms,
+        auth_service_failure_threshold,
+        auth_service_reset_timeout_ms,
+        log_level,
+        log_format,
+        shutdown_timeout_secs,
+        scheduler_interval_ms,
+        idempotency_window_ms,
     } = Opts::parse();
 
-    let (client, connection) = loop {
-        match tokio_postgres::connect(&database_url, NoTls).await {
-            Ok(v) => break v,
-            Err(e) => utils::log(utils::Event {
-                msg: e.to_string(),
-                source: e.source().map(|x| x.to_string()),
-                severity: utils::SeverityKind::Error,
-            }),
+    let filter = EnvFilter::try_new(&log_level).expect("invalid log level");
+    match log_format {
+        LogFormat::Json => tracing_subscriber::fmt()
+            .json()
+            .with_env_filter(filter)
+            .init(),
+        LogFormat::Text => tracing_subscriber::fmt().with_env_filter(filter).init(),
+    }
+
+    // tells background tasks to stop, once all in-flight requests are done
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+    // blocks until the database is reachable, and reconnects whenever a connection drops
+    let (database, db_supervisor) =
+        db::Database::connect(database_url, database_pool_size, shutdown_rx.clone()).await;
+    let db: Db = database;
+
+    let auth_service: Auth = match (dev_auth_config, auth_service_url) {
+        (Some(dev_auth_config), _) => {
+            tracing::warn!(
+                path = dev_auth_config.as_str(),
+                "using development api keys instead of the auth service"
+            );
+            Arc::new(
+                dev_auth::DevAuth::from_file(&dev_auth_config)
+                    .expect("couldn't read dev auth config"),
+            )
         }
-
-        // sleep for 5 seconds
-        std::thread::sleep(std::time::Duration::from_secs(5));
+        // open connection to auth service
+        (None, Some(auth_service_url)) => Arc::new(auth_client::AuthClient::new(
+            AuthService::new(&auth_service_url).await,
+            auth_client::AuthClientConfig {
+                timeout: Duration::from_millis(auth_service_timeout_ms),
+                max_retries: auth_service_retries,
+                retry_backoff: Duration::from_millis(auth_service_retry_backoff_ms),
+                failure_threshold: auth_service_failure_threshold,
+                reset_timeout: Duration::from_millis(auth_service_reset_timeout_ms),
+            },
+        )),
+        (None, None) => unreachable!("clap requires one of them"),
     };
 
-    // The connection object performs the actual communication with the database,
-    // so spawn it off to run on its own.
-    tokio::spawn(async move {
-        if let Err(e) = connection.await {
-            eprintln!("connection error: {}", e);
-        }
+    let log = warp::log::custom(|info| {
+        tracing::info!(
+            method = %info.method(),
+            path = info.path(),
+            status = info.status().as_u16(),
+            elapsed_ms = info.elapsed().as_millis() as u64,
+            "handled request"
+        );
     });
 
-    let db: Db = Arc::new(Mutex::new(client));
+    let config = Config {
+        site_external_url,
+        idempotency_window_ms,
+    };
+
+    let scheduler = scheduler::spawn(
+        db.clone(),
+        config.clone(),
+        Duration::from_millis(scheduler_interval_ms),
+        shutdown_rx.clone(),
+    );
 
-    // open connection to auth service
-    let auth_service = AuthService::new(&auth_service_url).await;
+    let api = api::api(config, db, auth_service);
 
-    let log = warp::log::custom(|info| {
-        // Use a log macro, or slog, or println, or whatever!
-        utils::log(utils::Event {
-            msg: info.method().to_string(),
-            source: Some(info.path().to_string()),
-            severity: utils::SeverityKind::Info,
+    // stop accepting new connections once this fires, but let in-flight requests finish
+    let (drain_tx, drain_rx) = oneshot::channel::<()>();
+    let (_, server) =
+        warp::serve(api.with(log)).bind_with_graceful_shutdown(([0, 0, 0, 0], port), async {
+            drain_rx.await.ok();
         });
-    });
+    let mut server = tokio::spawn(server);
+
+    tokio::select! {
+        _ = &mut server => {}
+        _ = shutdown_signal() => {
+            tracing::info!("shutdown signal received, draining in-flight requests");
+            drain_tx.send(()).ok();
+            match tokio::time::timeout(Duration::from_secs(shutdown_timeout_secs), server).await {
+                Ok(_) => tracing::info!("all in-flight requests finished"),
+                // unfinished transactions are rolled back when their connection goes away
+                Err(_) => tracing::warn!("shutdown deadline exceeded, abandoning in-flight requests"),
+            }
+        }
+    }
 
-    let api = api::api(
-        Config {
-            site_external_url,
-        },
-        db,
-        auth_service,
-    );
+    shutdown_tx.send(true).ok();
+    scheduler.await.ok();
+    db_supervisor.await.ok();
+
+    tracing::info!("shutdown complete");
+}
 
-    warp::serve(api.with(log)).run(([0, 0, 0, 0], port)).await;
+// resolves on ctrl-c or SIGTERM
+async fn shutdown_signal() {
+    let mut sigterm = signal(SignalKind::terminate()).expect("failed to install SIGTERM handler");
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = sigterm.recv() => {}
+    }
 }
@@ -0,0 +1,214 @@
+// The economics of the game.
+// Every year all players sell into one market, the more everyone produces the lower the price.
+// The web frontend does the same math to show how each year went.
+use super::db_types::*;
+use std::collections::HashMap;
+
+// price per unit for a year, read off the demand curve shifted up by everyone's incentives
+pub fn price(tournament: &Tournament, total_incentive: i64, total_production: i64) -> f64 {
+    // a curve without width would divide by zero, nobody buys anything there
+    if tournament.demand_xintercept == 0 {
+        return 0.0;
+    }
+    let slope = -(tournament.demand_yintercept as f64) / tournament.demand_xintercept as f64;
+    let intercept = (tournament.demand_yintercept + total_incentive) as f64;
+    f64::max(slope * total_production as f64 + intercept, 0.0)
+}
+
+pub fn profit(tournament: &Tournament, price: f64, amount: i64) -> f64 {
+    amount as f64 * (price - tournament.cost_per_unit as f64)
+}
+
+// the sum of every firm's profits over all years
+// expects only the most recent submission per firm and year
+pub fn balances(
+    tournament: &Tournament,
+    submissions: &[TournamentSubmission],
+    demands: &[TournamentYearDemand],
+) -> HashMap<Firm, f64> {
+    let mut production = HashMap::new();
+    for s in submissions {
+        *production.entry(s.year).or_insert(0) += s.amount;
+    }
+    let mut incentive = HashMap::new();
+    for d in demands {
+        *incentive.entry(d.year).or_insert(0) += d.demand;
+    }
+
+    let mut balances = HashMap::new();
+    for s in submissions {
+        let price = price(
+            tournament,
+            incentive.get(&s.year).copied().unwrap_or(0),
+            production[&s.year],
+        );
+        *balances.entry(s.firm()).or_insert(0.0) += profit(tournament, price, s.amount);
+    }
+    balances
+}
+
+// orders firms from highest to lowest balance, returning (firm, rank, balance)
+// tied firms share a rank, and the next rank is skipped (1, 1, 3)
+pub fn rank<K: Copy + Ord>(balances: &HashMap<K, f64>) -> Vec<(K, i64, f64)> {
+    let mut sorted: Vec<(K, f64)> = balances.iter().map(|(&u, &b)| (u, b)).collect();
+    sorted.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    let mut ranked: Vec<(K, i64, f64)> = Vec::with_capacity(sorted.len());
+    for (i, (firm, balance)) in sorted.into_iter().enumerate() {
+        let rank = match ranked.last() {
+            Some(&(_, last_rank, last_balance)) if last_balance == balance => last_rank,
+            _ => i as i64 + 1,
+        };
+        ranked.push((firm, rank, balance));
+    }
+    ranked
+}
+
+// how a year goes if every firm plays by the textbook
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Outcome {
+    // what each firm produces
+    pub quantity: f64,
+    pub total_quantity: f64,
+    pub price: f64,
+    // what each firm earns
+    pub profit: f64,
+}
+
+// the outcome of `firms` firms splitting `total_quantity` evenly
+fn outcome(
+    tournament: &Tournament,
+    total_incentive: i64,
+    firms: i64,
+    total_quantity: f64,
+) -> Outcome {
+    // the curve is linear, so this is the same as price() without rounding to whole units
+    let price = match tournament.demand_xintercept {
+        0 => 0.0,
+        x => f64::max(
+            (tournament.demand_yintercept + total_incentive) as f64
+                - tournament.demand_yintercept as f64 / x as f64 * total_quantity,
+            0.0,
+        ),
+    };
+    let quantity = total_quantity / firms as f64;
+    Outcome {
+        quantity,
+        total_quantity,
+        price,
+        profit: quantity * (price - tournament.cost_per_unit as f64),
+    }
+}
+
+// what the market takes before the price falls to cost, nothing on a flat or missing curve
+fn competitive_quantity(tournament: &Tournament, total_incentive: i64) -> f64 {
+    if tournament.demand_xintercept == 0 || tournament.demand_yintercept <= 0 {
+        return 0.0;
+    }
+    let margin = (tournament.demand_yintercept + total_incentive - tournament.cost_per_unit) as f64;
+    f64::max(
+        margin * tournament.demand_xintercept as f64 / tournament.demand_yintercept as f64,
+        0.0,
+    )
+}
+
+// the most profitable quantity when everyone else sells `others` in total
+// whatever the others leave of the market, halved
+pub fn best_response(tournament: &Tournament, total_incentive: i64, others: f64) -> f64 {
+    f64::max(
+        (competitive_quantity(tournament, total_incentive) - others) / 2.0,
+        0.0,
+    )
+}
+
+// every firm best responds to the others
+pub fn cournot(tournament: &Tournament, total_incentive: i64, firms: i64) -> Outcome {
+    let total =
+        competitive_quantity(tournament, total_incentive) * firms as f64 / (firms + 1) as f64;
+    outcome(tournament, total_incentive, firms, total)
+}
+
+// the firms act as one monopolist and split its output
+pub fn monopoly(tournament: &Tournament, total_incentive: i64, firms: i64) -> Outcome {
+    let total = competitive_quantity(tournament, total_incentive) / 2.0;
+    outcome(tournament, total_incentive, firms, total)
+}
+
+// firms undercut each other until the price is down to cost
+pub fn competitive(tournament: &Tournament, total_incentive: i64, firms: i64) -> Outcome {
+    let total = competitive_quantity(tournament, total_incentive);
+    outcome(tournament, total_incentive, firms, total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{demand, submission, tournament};
+
+    #[test]
+    fn price_follows_the_demand_curve() {
+        let t = tournament();
+        assert_eq!(price(&t, 0, 0), 100.0);
+        assert_eq!(price(&t, 0, 40), 60.0);
+        assert_eq!(price(&t, 6, 40), 66.0);
+        // never negative
+        assert_eq!(price(&t, 0, 500), 0.0);
+        assert_eq!(profit(&t, 60.0, 5), 250.0);
+        assert_eq!(profit(&t, 0.0, 5), -50.0);
+    }
+
+    #[test]
+    fn balances_sum_every_year() {
+        let t = tournament();
+        let submissions = [
+            submission(2, 0, 20),
+            submission(3, 0, 20),
+            submission(2, 1, 50),
+            submission(3, 1, 0),
+        ];
+        let demands = [demand(2, 1, 3), demand(3, 1, -3)];
+        let balances = balances(&t, &submissions, &demands);
+        // year 0 sells at 60, year 1 at 50
+        assert_eq!(balances[&Firm::User(2)], 20.0 * 50.0 + 50.0 * 40.0);
+        assert_eq!(balances[&Firm::User(3)], 20.0 * 50.0);
+    }
+
+    #[test]
+    fn ties_share_a_rank() {
+        let balances = [(2, 10.0), (3, 30.0), (4, 10.0), (5, -5.0)]
+            .into_iter()
+            .collect();
+        assert_eq!(
+            rank(&balances),
+            vec![(3, 1, 30.0), (2, 2, 10.0), (4, 2, 10.0), (5, 4, -5.0)]
+        );
+    }
+
+    #[test]
+    fn benchmarks_follow_the_textbook() {
+        // price is 100 - Q and costs are 10
+        let t = tournament();
+        let nash = cournot(&t, 0, 2);
+        assert_eq!(
+            (nash.quantity, nash.price, nash.profit),
+            (30.0, 40.0, 900.0)
+        );
+        let cartel = monopoly(&t, 0, 2);
+        assert_eq!(
+            (cartel.total_quantity, cartel.price, cartel.profit),
+            (45.0, 55.0, 1012.5)
+        );
+        let perfect = competitive(&t, 0, 2);
+        assert_eq!(
+            (perfect.quantity, perfect.price, perfect.profit),
+            (45.0, 10.0, 0.0)
+        );
+        // incentives raise the curve
+        assert_eq!(cournot(&t, 30, 2).quantity, 40.0);
+        // nobody produces when costs are above what anyone would pay
+        let mut t = tournament();
+        t.cost_per_unit = 200;
+        assert_eq!(cournot(&t, 0, 2).total_quantity, 0.0);
+        assert_eq!(competitive(&t, 0, 2).price, 100.0);
+    }
+}
@@ -0,0 +1,1138 @@
+use super::db_types::*;
+use super::request;
+use super::response::AppError;
+use super::store::{Store, StoreTransaction};
+use super::utils;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Arc;
+use tokio::sync::{Mutex, OwnedMutexGuard};
+
+// A store that keeps everything in memory.
+// Used for tests, so that handlers can be exercised without a live postgres.
+#[derive(Clone, Default)]
+pub struct MemoryStore {
+    tables: Arc<Mutex<Tables>>,
+}
+
+#[derive(Clone, Default)]
+struct Tables {
+    // shared by all tables, like a bigserial that never repeats
+    last_id: i64,
+    tournament: Vec<Tournament>,
+    tournament_data: Vec<TournamentData>,
+    tournament_state: Vec<TournamentState>,
+    tournament_year: Vec<TournamentYear>,
+    tournament_pause: Vec<TournamentPause>,
+    tournament_year_demand: Vec<TournamentYearDemand>,
+    tournament_membership: Vec<TournamentMembership>,
+    tournament_invite_code: Vec<TournamentInviteCode>,
+    tournament_team: Vec<TournamentTeam>,
+    tournament_bot: Vec<TournamentBot>,
+    tournament_pseudonym: Vec<TournamentPseudonym>,
+    tournament_role: Vec<TournamentRole>,
+    tournament_submission: Vec<TournamentSubmission>,
+    tournament_final_result: Vec<TournamentFinalResult>,
+    idempotency_key: Vec<IdempotencyKey>,
+}
+
+impl Tables {
+    fn next_id(&mut self) -> i64 {
+        self.last_id += 1;
+        self.last_id
+    }
+}
+
+impl MemoryStore {
+    pub fn new() -> MemoryStore {
+        MemoryStore::default()
+    }
+}
+
+// keeps only the row with the highest id for every key, like the recent_* views
+fn recent<T: Clone, K: Eq + Hash>(
+    rows: &[T],
+    key: impl Fn(&T) -> K,
+    id: impl Fn(&T) -> i64,
+) -> Vec<T> {
+    let mut max_ids: HashMap<K, i64> = HashMap::new();
+    for row in rows {
+        let max_id = max_ids.entry(key(row)).or_insert(i64::MIN);
+        *max_id = std::cmp::max(*max_id, id(row));
+    }
+    rows.iter()
+        .filter(|row| max_ids.get(&key(row)) == Some(&id(row)))
+        .cloned()
+        .collect()
+}
+
+// mirrors "$1::bigint[] IS NULL OR x = ANY($1)"
+fn any(filter: &Option<Vec<i64>>, x: i64) -> bool {
+    filter.iter().all(|v| v.contains(&x))
+}
+
+// a null column only passes when there is no filter
+fn any_opt(filter: &Option<Vec<i64>>, x: Option<i64>) -> bool {
+    match x {
+        Some(x) => any(filter, x),
+        None => filter.is_none(),
+    }
+}
+
+fn in_range(min: Option<i64>, max: Option<i64>, x: i64) -> bool {
+    min.iter().all(|&min| x >= min) && max.iter().all(|&max| x <= max)
+}
+
+// without a filter practice tournaments are listed alongside the rest
+fn in_practice(tournaments: &[Tournament], practice: Option<bool>, tournament_id: i64) -> bool {
+    tournaments
+        .iter()
+        .any(|t| t.tournament_id == tournament_id && practice.is_none_or(|p| t.practice == p))
+}
+
+#[async_trait]
+impl Store for MemoryStore {
+    async fn begin(&self) -> Result<Box<dyn StoreTransaction>, AppError> {
+        let guard = self.tables.clone().lock_owned().await;
+        let working = guard.clone();
+        Ok(Box::new(MemoryTransaction { guard, working }))
+    }
+
+    async fn ping(&self) -> bool {
+        true
+    }
+}
+
+// Works on a copy of the tables, which replaces the originals on commit.
+pub struct MemoryTransaction {
+    guard: OwnedMutexGuard<Tables>,
+    working: Tables,
+}
+
+#[async_trait]
+impl StoreTransaction for MemoryTransaction {
+    async fn commit(mut self: Box<Self>) -> Result<(), AppError> {
+        *self.guard = std::mem::take(&mut self.working);
+        Ok(())
+    }
+
+    async fn tournament_add(
+        &mut self,
+        creator_user_id: i64,
+        cost_per_unit: i64,
+        demand_xintercept: i64,
+        demand_yintercept: i64,
+        incentive_multiplier: i64,
+        incentive_start_year: i64,
+        max_years: i64,
+        year_duration_ms: Option<i64>,
+        late_join_policy: LateJoinPolicyKind,
+        exit_policy: ExitPolicyKind,
+        visibility: VisibilityKind,
+        min_players: Option<i64>,
+        max_players: Option<i64>,
+        auto_start: bool,
+        practice: bool,
+    ) -> Result<Tournament, AppError> {
+        let tournament = Tournament {
+            tournament_id: self.working.next_id(),
+            creation_time: utils::current_time_millis(),
+            creator_user_id,
+            cost_per_unit,
+            demand_xintercept,
+            demand_yintercept,
+            incentive_multiplier,
+            incentive_start_year,
+            max_years,
+            year_duration_ms,
+            late_join_policy,
+            exit_policy,
+            visibility,
+            min_players,
+            max_players,
+            auto_start,
+            practice,
+        };
+        self.working.tournament.push(tournament.clone());
+        Ok(tournament)
+    }
+
+    async fn tournament_get(&mut self, tournament_id: i64) -> Result<Option<Tournament>, AppError> {
+        Ok(self
+            .working
+            .tournament
+            .iter()
+            .find(|x| x.tournament_id == tournament_id)
+            .cloned())
+    }
+
+    async fn tournament_data_add(
+        &mut self,
+        creator_user_id: i64,
+        tournament_id: i64,
+        title: String,
+        active: bool,
+    ) -> Result<TournamentData, AppError> {
+        let tournament_data = TournamentData {
+            tournament_data_id: self.working.next_id(),
+            creation_time: utils::current_time_millis(),
+            creator_user_id,
+            tournament_id,
+            title,
+            active,
+        };
+        self.working.tournament_data.push(tournament_data.clone());
+        Ok(tournament_data)
+    }
+
+    async fn tournament_data_get_recent(
+        &mut self,
+        tournament_id: i64,
+    ) -> Result<Option<TournamentData>, AppError> {
+        Ok(self
+            .working
+            .tournament_data
+            .iter()
+            .filter(|x| x.tournament_id == tournament_id)
+            .max_by_key(|x| x.tournament_data_id)
+            .cloned())
+    }
+
+    async fn tournament_data_count_active(&mut self) -> Result<i64, AppError> {
+        let recent = recent(
+            &self.working.tournament_data,
+            |x| x.tournament_id,
+            |x| x.tournament_data_id,
+        );
+        Ok(recent
+            .iter()
+            .filter(|x| {
+                x.active && in_practice(&self.working.tournament, Some(false), x.tournament_id)
+            })
+            .count() as i64)
+    }
+
+    async fn tournament_data_query(
+        &mut self,
+        props: request::TournamentDataViewProps,
+    ) -> Result<Vec<TournamentData>, AppError> {
+        let rows = if props.only_recent {
+            recent(
+                &self.working.tournament_data,
+                |x| x.tournament_id,
+                |x| x.tournament_data_id,
+            )
+        } else {
+            self.working.tournament_data.clone()
+        };
+        Ok(rows
+            .into_iter()
+            .filter(|x| any(&props.tournament_data_id, x.tournament_data_id))
+            .filter(|x| {
+                in_range(
+                    props.min_creation_time,
+                    props.max_creation_time,
+                    x.creation_time,
+                )
+            })
+            .filter(|x| any(&props.creator_user_id, x.creator_user_id))
+            .filter(|x| any(&props.tournament_id, x.tournament_id))
+            .filter(|x| props.active.iter().all(|&a| x.active == a))
+            .filter(|x| in_practice(&self.working.tournament, props.practice, x.tournament_id))
+            .collect())
+    }
+
+    async fn tournament_state_add(
+        &mut self,
+        creator_user_id: i64,
+        tournament_id: i64,
+        state: TournamentStateKind,
+    ) -> Result<TournamentState, AppError> {
+        let tournament_state = TournamentState {
+            tournament_state_id: self.working.next_id(),
+            creation_time: utils::current_time_millis(),
+            creator_user_id,
+            tournament_id,
+            state,
+        };
+        self.working.tournament_state.push(tournament_state.clone());
+        Ok(tournament_state)
+    }
+
+    async fn tournament_state_get_recent(
+        &mut self,
+        tournament_id: i64,
+    ) -> Result<Option<TournamentState>, AppError> {
+        Ok(self
+            .working
+            .tournament_state
+            .iter()
+            .filter(|x| x.tournament_id == tournament_id)
+            .max_by_key(|x| x.tournament_state_id)
+            .cloned())
+    }
+
+    async fn tournament_year_add(
+        &mut self,
+        creator_user_id: i64,
+        tournament_id: i64,
+        current_year: i64,
+        deadline: Option<i64>,
+    ) -> Result<TournamentYear, AppError> {
+        let tournament_year = TournamentYear {
+            tournament_year_id: self.working.next_id(),
+            creation_time: utils::current_time_millis(),
+            creator_user_id,
+            tournament_id,
+            current_year,
+            deadline,
+        };
+        self.working.tournament_year.push(tournament_year.clone());
+        Ok(tournament_year)
+    }
+
+    async fn tournament_year_get_recent(
+        &mut self,
+        tournament_id: i64,
+    ) -> Result<Option<TournamentYear>, AppError> {
+        Ok(self
+            .working
+            .tournament_year
+            .iter()
+            .filter(|x| x.tournament_id == tournament_id)
+            .max_by_key(|x| x.tournament_year_id)
+            .cloned())
+    }
+
+    async fn tournament_year_get_due(&mut self, now: i64) -> Result<Vec<TournamentYear>, AppError> {
+        let running: Vec<i64> = recent(
+            &self.working.tournament_state,
+            |x| x.tournament_id,
+            |x| x.tournament_state_id,
+        )
+        .into_iter()
+        .filter(|x| x.state == TournamentStateKind::Running)
+        .map(|x| x.tournament_id)
+        .collect();
+        let mut due: Vec<_> = recent(
+            &self.working.tournament_year,
+            |x| x.tournament_id,
+            |x| x.tournament_year_id,
+        )
+        .into_iter()
+        .filter(|x| running.contains(&x.tournament_id))
+        .filter(|x| in_practice(&self.working.tournament, Some(false), x.tournament_id))
+        .filter(|x| x.deadline.is_some_and(|d| d <= now))
+        .collect();
+        due.sort_by_key(|x| x.deadline);
+        Ok(due)
+    }
+
+    async fn tournament_year_query(
+        &mut self,
+        props: request::TournamentYearViewProps,
+    ) -> Result<Vec<TournamentYear>, AppError> {
+        let rows = if props.only_recent {
+            recent(
+                &self.working.tournament_year,
+                |x| x.tournament_id,
+                |x| x.tournament_year_id,
+            )
+        } else {
+            self.working.tournament_year.clone()
+        };
+        Ok(rows
+            .into_iter()
+            .filter(|x| any(&props.tournament_year_id, x.tournament_year_id))
+            .filter(|x| {
+                in_range(
+                    props.min_creation_time,
+                    props.max_creation_time,
+                    x.creation_time,
+                )
+            })
+            .filter(|x| any(&props.creator_user_id, x.creator_user_id))
+            .filter(|x| any(&props.tournament_id, x.tournament_id))
+            .filter(|x| in_practice(&self.working.tournament, props.practice, x.tournament_id))
+            .collect())
+    }
+
+    async fn tournament_pause_add(
+        &mut self,
+        creator_user_id: i64,
+        tournament_id: i64,
+        start_time: i64,
+        end_time: i64,
+    ) -> Result<TournamentPause, AppError> {
+        let tournament_pause = TournamentPause {
+            tournament_pause_id: self.working.next_id(),
+            creation_time: utils::current_time_millis(),
+            creator_user_id,
+            tournament_id,
+            start_time,
+            end_time,
+        };
+        self.working.tournament_pause.push(tournament_pause.clone());
+        Ok(tournament_pause)
+    }
+
+    async fn tournament_pause_query(
+        &mut self,
+        props: request::TournamentPauseViewProps,
+    ) -> Result<Vec<TournamentPause>, AppError> {
+        Ok(self
+            .working
+            .tournament_pause
+            .iter()
+            .filter(|x| any(&props.tournament_pause_id, x.tournament_pause_id))
+            .filter(|x| {
+                in_range(
+                    props.min_creation_time,
+                    props.max_creation_time,
+                    x.creation_time,
+                )
+            })
+            .filter(|x| any(&props.tournament_id, x.tournament_id))
+            .cloned()
+            .collect())
+    }
+
+    async fn tournament_year_demand_add(
+        &mut self,
+        firm: Firm,
+        tournament_id: i64,
+        year: i64,
+        demand: i64,
+    ) -> Result<TournamentYearDemand, AppError> {
+        let tournament_year_demand = TournamentYearDemand {
+            tournament_year_demand_id: self.working.next_id(),
+            creation_time: utils::current_time_millis(),
+            tournament_id,
+            user_id: firm.user_id(),
+            tournament_team_id: firm.tournament_team_id(),
+            tournament_bot_id: firm.tournament_bot_id(),
+            year,
+            demand,
+        };
+        // mirrors the unique constraints
+        if self.working.tournament_year_demand.iter().any(|x| {
+            x.tournament_id == tournament_id
+                && x.firm() == tournament_year_demand.firm()
+                && x.year == year
+        }) {
+            return Err(AppError::InternalServerError);
+        }
+        self.working
+            .tournament_year_demand
+            .push(tournament_year_demand.clone());
+        Ok(tournament_year_demand)
+    }
+
+    async fn tournament_year_demand_get_by_tournament(
+        &mut self,
+        tournament_id: i64,
+    ) -> Result<Vec<TournamentYearDemand>, AppError> {
+        Ok(self
+            .working
+            .tournament_year_demand
+            .iter()
+            .filter(|x| x.tournament_id == tournament_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn tournament_year_demand_query(
+        &mut self,
+        props: request::TournamentYearDemandViewProps,
+    ) -> Result<Vec<TournamentYearDemand>, AppError> {
+        Ok(self
+            .working
+            .tournament_year_demand
+            .iter()
+            .filter(|x| {
+                any(
+                    &props.tournament_year_demand_id,
+                    x.tournament_year_demand_id,
+                )
+            })
+            .filter(|x| {
+                in_range(
+                    props.min_creation_time,
+                    props.max_creation_time,
+                    x.creation_time,
+                )
+            })
+            .filter(|x| any_opt(&props.user_id, x.user_id))
+            .filter(|x| any(&props.tournament_id, x.tournament_id))
+            .filter(|x| any_opt(&props.tournament_team_id, x.tournament_team_id))
+            .filter(|x| in_practice(&self.working.tournament, props.practice, x.tournament_id))
+            .cloned()
+            .collect())
+    }
+
+    async fn tournament_membership_add(
+        &mut self,
+        creator_user_id: i64,
+        tournament_id: i64,
+        active: bool,
+        observer: bool,
+        approval: ApprovalKind,
+        removed: bool,
+        banned: bool,
+        actor_user_id: i64,
+        reason: Option<String>,
+        tournament_team_id: Option<i64>,
+    ) -> Result<TournamentMembership, AppError> {
+        let tournament_membership = TournamentMembership {
+            tournament_membership_id: self.working.next_id(),
+            creation_time: utils::current_time_millis(),
+            creator_user_id,
+            tournament_id,
+            active,
+            observer,
+            approval,
+            removed,
+            banned,
+            actor_user_id,
+            reason,
+            tournament_team_id,
+        };
+        self.working
+            .tournament_membership
+            .push(tournament_membership.clone());
+        Ok(tournament_membership)
+    }
+
+    async fn tournament_membership_get_recent_by_tournament(
+        &mut self,
+        tournament_id: i64,
+    ) -> Result<Vec<TournamentMembership>, AppError> {
+        Ok(recent(
+            &self.working.tournament_membership,
+            |x| (x.tournament_id, x.creator_user_id),
+            |x| x.tournament_membership_id,
+        )
+        .into_iter()
+        .filter(|x| x.tournament_id == tournament_id)
+        .collect())
+    }
+
+    async fn tournament_membership_get_recent_by_tournament_user(
+        &mut self,
+        tournament_id: i64,
+        user_id: i64,
+    ) -> Result<Option<TournamentMembership>, AppError> {
+        Ok(self
+            .working
+            .tournament_membership
+            .iter()
+            .filter(|x| x.tournament_id == tournament_id && x.creator_user_id == user_id)
+            .max_by_key(|x| x.tournament_membership_id)
+            .cloned())
+    }
+
+    async fn tournament_membership_query(
+        &mut self,
+        props: request::TournamentMembershipViewProps,
+    ) -> Result<Vec<TournamentMembership>, AppError> {
+        let rows = if props.only_recent {
+            recent(
+                &self.working.tournament_membership,
+                |x| (x.tournament_id, x.creator_user_id),
+                |x| x.tournament_membership_id,
+            )
+        } else {
+            self.working.tournament_membership.clone()
+        };
+        Ok(rows
+            .into_iter()
+            .filter(|x| any(&props.tournament_membership_id, x.tournament_membership_id))
+            .filter(|x| {
+                in_range(
+                    props.min_creation_time,
+                    props.max_creation_time,
+                    x.creation_time,
+                )
+            })
+            .filter(|x| any(&props.creator_user_id, x.creator_user_id))
+            .filter(|x| any(&props.tournament_id, x.tournament_id))
+            .filter(|x| props.active.iter().all(|&a| x.active == a))
+            .filter(|x| props.approval.iter().all(|&a| x.approval == a))
+            .collect())
+    }
+
+    async fn tournament_invite_code_add(
+        &mut self,
+        creator_user_id: i64,
+        tournament_id: i64,
+        invite_code: String,
+    ) -> Result<TournamentInviteCode, AppError> {
+        let tournament_invite_code = TournamentInviteCode {
+            tournament_invite_code_id: self.working.next_id(),
+            creation_time: utils::current_time_millis(),
+            creator_user_id,
+            tournament_id,
+            invite_code,
+        };
+        self.working
+            .tournament_invite_code
+            .push(tournament_invite_code.clone());
+        Ok(tournament_invite_code)
+    }
+
+    async fn tournament_invite_code_get_recent(
+        &mut self,
+        tournament_id: i64,
+    ) -> Result<Option<TournamentInviteCode>, AppError> {
+        Ok(self
+            .working
+            .tournament_invite_code
+            .iter()
+            .filter(|x| x.tournament_id == tournament_id)
+            .max_by_key(|x| x.tournament_invite_code_id)
+            .cloned())
+    }
+
+    async fn tournament_team_add(
+        &mut self,
+        creator_user_id: i64,
+        tournament_id: i64,
+        name: String,
+    ) -> Result<TournamentTeam, AppError> {
+        let tournament_team = TournamentTeam {
+            tournament_team_id: self.working.next_id(),
+            creation_time: utils::current_time_millis(),
+            creator_user_id,
+            tournament_id,
+            name,
+        };
+        self.working.tournament_team.push(tournament_team.clone());
+        Ok(tournament_team)
+    }
+
+    async fn tournament_team_get(
+        &mut self,
+        tournament_team_id: i64,
+    ) -> Result<Option<TournamentTeam>, AppError> {
+        Ok(self
+            .working
+            .tournament_team
+            .iter()
+            .find(|x| x.tournament_team_id == tournament_team_id)
+            .cloned())
+    }
+
+    async fn tournament_team_query(
+        &mut self,
+        props: request::TournamentTeamViewProps,
+    ) -> Result<Vec<TournamentTeam>, AppError> {
+        Ok(self
+            .working
+            .tournament_team
+            .iter()
+            .filter(|x| any(&props.tournament_team_id, x.tournament_team_id))
+            .filter(|x| {
+                in_range(
+                    props.min_creation_time,
+                    props.max_creation_time,
+                    x.creation_time,
+                )
+            })
+            .filter(|x| any(&props.tournament_id, x.tournament_id))
+            .cloned()
+            .collect())
+    }
+
+    async fn tournament_bot_add(
+        &mut self,
+        creator_user_id: i64,
+        tournament_id: i64,
+        strategy: BotStrategyKind,
+        quantity: Option<i64>,
+        name: String,
+    ) -> Result<TournamentBot, AppError> {
+        let tournament_bot = TournamentBot {
+            tournament_bot_id: self.working.next_id(),
+            creation_time: utils::current_time_millis(),
+            creator_user_id,
+            tournament_id,
+            strategy,
+            quantity,
+            name,
+        };
+        self.working.tournament_bot.push(tournament_bot.clone());
+        Ok(tournament_bot)
+    }
+
+    async fn tournament_bot_get(
+        &mut self,
+        tournament_bot_id: i64,
+    ) -> Result<Option<TournamentBot>, AppError> {
+        Ok(self
+            .working
+            .tournament_bot
+            .iter()
+            .find(|x| x.tournament_bot_id == tournament_bot_id)
+            .cloned())
+    }
+
+    async fn tournament_bot_get_by_tournament(
+        &mut self,
+        tournament_id: i64,
+    ) -> Result<Vec<TournamentBot>, AppError> {
+        Ok(self
+            .working
+            .tournament_bot
+            .iter()
+            .filter(|x| x.tournament_id == tournament_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn tournament_bot_query(
+        &mut self,
+        props: request::TournamentBotViewProps,
+    ) -> Result<Vec<TournamentBot>, AppError> {
+        Ok(self
+            .working
+            .tournament_bot
+            .iter()
+            .filter(|x| any(&props.tournament_bot_id, x.tournament_bot_id))
+            .filter(|x| {
+                in_range(
+                    props.min_creation_time,
+                    props.max_creation_time,
+                    x.creation_time,
+                )
+            })
+            .filter(|x| any(&props.tournament_id, x.tournament_id))
+            .cloned()
+            .collect())
+    }
+
+    async fn tournament_pseudonym_add(
+        &mut self,
+        creator_user_id: i64,
+        tournament_id: i64,
+        user_id: i64,
+        pseudonym: String,
+    ) -> Result<Option<TournamentPseudonym>, AppError> {
+        // mirrors the unique constraints, only the one on the user is expected to be hit
+        let same_tournament = self
+            .working
+            .tournament_pseudonym
+            .iter()
+            .filter(|x| x.tournament_id == tournament_id);
+        for x in same_tournament {
+            if x.user_id == user_id {
+                return Ok(None);
+            }
+            if x.pseudonym == pseudonym {
+                return Err(AppError::InternalServerError);
+            }
+        }
+        let tournament_pseudonym = TournamentPseudonym {
+            tournament_pseudonym_id: self.working.next_id(),
+            creation_time: utils::current_time_millis(),
+            creator_user_id,
+            tournament_id,
+            user_id,
+            pseudonym,
+        };
+        self.working
+            .tournament_pseudonym
+            .push(tournament_pseudonym.clone());
+        Ok(Some(tournament_pseudonym))
+    }
+
+    async fn tournament_pseudonym_get_by_tournament_user(
+        &mut self,
+        tournament_id: i64,
+        user_id: i64,
+    ) -> Result<Option<TournamentPseudonym>, AppError> {
+        Ok(self
+            .working
+            .tournament_pseudonym
+            .iter()
+            .find(|x| x.tournament_id == tournament_id && x.user_id == user_id)
+            .cloned())
+    }
+
+    async fn tournament_pseudonym_get_by_tournament(
+        &mut self,
+        tournament_id: i64,
+    ) -> Result<Vec<TournamentPseudonym>, AppError> {
+        Ok(self
+            .working
+            .tournament_pseudonym
+            .iter()
+            .filter(|x| x.tournament_id == tournament_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn tournament_role_add(
+        &mut self,
+        creator_user_id: i64,
+        tournament_id: i64,
+        user_id: i64,
+        role: TournamentRoleKind,
+        active: bool,
+    ) -> Result<TournamentRole, AppError> {
+        let tournament_role = TournamentRole {
+            tournament_role_id: self.working.next_id(),
+            creation_time: utils::current_time_millis(),
+            creator_user_id,
+            tournament_id,
+            user_id,
+            role,
+            active,
+        };
+        self.working.tournament_role.push(tournament_role.clone());
+        Ok(tournament_role)
+    }
+
+    async fn tournament_role_get_recent_by_tournament_user(
+        &mut self,
+        tournament_id: i64,
+        user_id: i64,
+    ) -> Result<Option<TournamentRole>, AppError> {
+        Ok(self
+            .working
+            .tournament_role
+            .iter()
+            .filter(|x| x.tournament_id == tournament_id && x.user_id == user_id)
+            .max_by_key(|x| x.tournament_role_id)
+            .cloned())
+    }
+
+    async fn tournament_role_query(
+        &mut self,
+        props: request::TournamentRoleViewProps,
+    ) -> Result<Vec<TournamentRole>, AppError> {
+        let rows = if props.only_recent {
+            recent(
+                &self.working.tournament_role,
+                |x| (x.tournament_id, x.user_id),
+                |x| x.tournament_role_id,
+            )
+        } else {
+            self.working.tournament_role.clone()
+        };
+        Ok(rows
+            .into_iter()
+            .filter(|x| any(&props.tournament_role_id, x.tournament_role_id))
+            .filter(|x| {
+                in_range(
+                    props.min_creation_time,
+                    props.max_creation_time,
+                    x.creation_time,
+                )
+            })
+            .filter(|x| any(&props.tournament_id, x.tournament_id))
+            .filter(|x| any(&props.user_id, x.user_id))
+            .filter(|x| props.active.iter().all(|&a| x.active == a))
+            .collect())
+    }
+
+    async fn tournament_submission_add(
+        &mut self,
+        creator_user_id: i64,
+        firm: Firm,
+        tournament_id: i64,
+        year: i64,
+        amount: i64,
+        autogenerated: bool,
+    ) -> Result<TournamentSubmission, AppError> {
+        let tournament_submission = TournamentSubmission {
+            tournament_submission_id: self.working.next_id(),
+            creation_time: utils::current_time_millis(),
+            creator_user_id,
+            tournament_team_id: firm.tournament_team_id(),
+            tournament_bot_id: firm.tournament_bot_id(),
+            tournament_id,
+            year,
+            amount,
+            autogenerated,
+        };
+        self.working
+            .tournament_submission
+            .push(tournament_submission.clone());
+        Ok(tournament_submission)
+    }
+
+    async fn tournament_submission_get_recent_by_tournament(
+        &mut self,
+        tournament_id: i64,
+    ) -> Result<Vec<TournamentSubmission>, AppError> {
+        Ok(recent(
+            &self.working.tournament_submission,
+            |x| (x.tournament_id, x.firm(), x.year),
+            |x| x.tournament_submission_id,
+        )
+        .into_iter()
+        .filter(|x| x.tournament_id == tournament_id)
+        .collect())
+    }
+
+    async fn tournament_submission_query(
+        &mut self,
+        props: request::TournamentSubmissionViewProps,
+    ) -> Result<Vec<TournamentSubmission>, AppError> {
+        Ok(self
+            .working
+            .tournament_submission
+            .iter()
+            .filter(|x| any(&props.tournament_submission_id, x.tournament_submission_id))
+            .filter(|x| {
+                in_range(
+                    props.min_creation_time,
+                    props.max_creation_time,
+                    x.creation_time,
+                )
+            })
+            .filter(|x| any(&props.creator_user_id, x.creator_user_id))
+            .filter(|x| any(&props.tournament_id, x.tournament_id))
+            .filter(|x| any_opt(&props.tournament_team_id, x.tournament_team_id))
+            .filter(|x| in_practice(&self.working.tournament, props.practice, x.tournament_id))
+            .cloned()
+            .collect())
+    }
+
+    async fn tournament_final_result_add(
+        &mut self,
+        creator_user_id: i64,
+        tournament_id: i64,
+        firm: Firm,
+        rank: i64,
+        balance: f64,
+    ) -> Result<TournamentFinalResult, AppError> {
+        let tournament_final_result = TournamentFinalResult {
+            tournament_final_result_id: self.working.next_id(),
+            creation_time: utils::current_time_millis(),
+            creator_user_id,
+            tournament_id,
+            user_id: firm.user_id(),
+            tournament_team_id: firm.tournament_team_id(),
+            tournament_bot_id: firm.tournament_bot_id(),
+            rank,
+            balance,
+        };
+        // mirrors the unique constraints
+        if self
+            .working
+            .tournament_final_result
+            .iter()
+            .any(|x| x.tournament_id == tournament_id && x.firm() == tournament_final_result.firm())
+        {
+            return Err(AppError::InternalServerError);
+        }
+        self.working
+            .tournament_final_result
+            .push(tournament_final_result.clone());
+        Ok(tournament_final_result)
+    }
+
+    async fn tournament_final_result_get_by_tournament(
+        &mut self,
+        tournament_id: i64,
+    ) -> Result<Vec<TournamentFinalResult>, AppError> {
+        let mut results: Vec<_> = self
+            .working
+            .tournament_final_result
+            .iter()
+            .filter(|x| x.tournament_id == tournament_id)
+            .cloned()
+            .collect();
+        results.sort_by_key(|x| (x.rank, x.user_id, x.tournament_team_id, x.tournament_bot_id));
+        Ok(results)
+    }
+
+    async fn idempotency_key_add(
+        &mut self,
+        creator_user_id: i64,
+        endpoint: String,
+        idempotency_key: String,
+        request_hash: String,
+        response: String,
+        min_creation_time: i64,
+    ) -> Result<Option<IdempotencyKey>, AppError> {
+        let same_key = |x: &IdempotencyKey| {
+            x.creator_user_id == creator_user_id
+                && x.endpoint == endpoint
+                && x.idempotency_key == idempotency_key
+        };
+        if self
+            .working
+            .idempotency_key
+            .iter()
+            .any(|x| same_key(x) && x.creation_time >= min_creation_time)
+        {
+            return Ok(None);
+        }
+        self.working.idempotency_key.retain(|x| !same_key(x));
+        let row = IdempotencyKey {
+            idempotency_key_id: self.working.next_id(),
+            creation_time: utils::current_time_millis(),
+            creator_user_id,
+            endpoint,
+            idempotency_key,
+            request_hash,
+            response,
+        };
+        self.working.idempotency_key.push(row.clone());
+        Ok(Some(row))
+    }
+
+    async fn idempotency_key_get_recent(
+        &mut self,
+        creator_user_id: i64,
+        endpoint: &str,
+        idempotency_key: &str,
+        min_creation_time: i64,
+    ) -> Result<Option<IdempotencyKey>, AppError> {
+        Ok(self
+            .working
+            .idempotency_key
+            .iter()
+            .filter(|x| {
+                x.creator_user_id == creator_user_id
+                    && x.endpoint == endpoint
+                    && x.idempotency_key == idempotency_key
+                    && x.creation_time >= min_creation_time
+            })
+            .max_by_key(|x| x.idempotency_key_id)
+            .cloned())
+    }
+
+    async fn idempotency_key_delete_expired(
+        &mut self,
+        min_creation_time: i64,
+    ) -> Result<u64, AppError> {
+        let before = self.working.idempotency_key.len();
+        self.working
+            .idempotency_key
+            .retain(|x| x.creation_time >= min_creation_time);
+        Ok((before - self.working.idempotency_key.len()) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn uncommitted_transactions_are_discarded() {
+        let store = MemoryStore::new();
+
+        let mut sp = store.begin().await.unwrap();
+        sp.tournament_add(
+            1,
+            10,
+            100,
+            100,
+            5,
+            2,
+            3,
+            None,
+            LateJoinPolicyKind::Forbidden,
+            ExitPolicyKind::KeepBalance,
+            VisibilityKind::Public,
+            None,
+            None,
+            false,
+            false,
+        )
+        .await
+        .unwrap();
+        drop(sp);
+
+        let mut sp = store.begin().await.unwrap();
+        assert!(sp.tournament_get(1).await.unwrap().is_none());
+        let tournament = sp
+            .tournament_add(
+                1,
+                10,
+                100,
+                100,
+                5,
+                2,
+                3,
+                None,
+                LateJoinPolicyKind::Forbidden,
+                ExitPolicyKind::KeepBalance,
+                VisibilityKind::Public,
+                None,
+                None,
+                false,
+                false,
+            )
+            .await
+            .unwrap();
+        sp.commit().await.unwrap();
+
+        let mut sp = store.begin().await.unwrap();
+        assert!(sp
+            .tournament_get(tournament.tournament_id)
+            .await
+            .unwrap()
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn only_recent_keeps_latest_row_per_key() {
+        let store = MemoryStore::new();
+        let mut sp = store.begin().await.unwrap();
+        sp.tournament_membership_add(
+            2,
+            1,
+            true,
+            false,
+            ApprovalKind::Approved,
+            false,
+            false,
+            2,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        sp.tournament_membership_add(
+            3,
+            1,
+            true,
+            false,
+            ApprovalKind::Approved,
+            false,
+            false,
+            3,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        sp.tournament_membership_add(
+            2,
+            1,
+            false,
+            false,
+            ApprovalKind::Approved,
+            false,
+            false,
+            2,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let recent = sp
+            .tournament_membership_get_recent_by_tournament(1)
+            .await
+            .unwrap();
+        assert_eq!(recent.len(), 2);
+        assert!(recent.iter().any(|m| m.creator_user_id == 2 && !m.active));
+        assert!(recent.iter().any(|m| m.creator_user_id == 3 && m.active));
+    }
+}
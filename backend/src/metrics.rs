@@ -0,0 +1,187 @@
+use super::response::AppError;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+// upper bounds (in seconds) of the latency histogram buckets
+const BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+struct Histogram {
+    // non cumulative, the counts are summed up when rendering
+    buckets: [u64; BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    const fn new() -> Histogram {
+        Histogram {
+            buckets: [0; BUCKETS.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        if let Some(i) = BUCKETS.iter().position(|&le| secs <= le) {
+            self.buckets[i] += 1;
+        }
+        self.sum += secs;
+        self.count += 1;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let sep = if labels.is_empty() { "" } else { "," };
+        let mut cumulative = 0;
+        for (le, n) in BUCKETS.iter().zip(self.buckets.iter()) {
+            cumulative += n;
+            let _ = writeln!(
+                out,
+                "{name}_bucket{{{labels}{sep}le=\"{le}\"}} {cumulative}"
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{name}_bucket{{{labels}{sep}le=\"+Inf\"}} {}",
+            self.count
+        );
+        let labels = if labels.is_empty() {
+            String::new()
+        } else {
+            format!("{{{labels}}}")
+        };
+        let _ = writeln!(out, "{name}_sum{labels} {}", self.sum);
+        let _ = writeln!(out, "{name}_count{labels} {}", self.count);
+    }
+}
+
+struct Registry {
+    // keyed by (route, status code)
+    requests: BTreeMap<(String, u16), u64>,
+    request_durations: BTreeMap<String, Histogram>,
+    errors: BTreeMap<String, u64>,
+    year_advance_durations: Histogram,
+    db_pool_waits: Histogram,
+}
+
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry {
+    requests: BTreeMap::new(),
+    request_durations: BTreeMap::new(),
+    errors: BTreeMap::new(),
+    year_advance_durations: Histogram::new(),
+    db_pool_waits: Histogram::new(),
+});
+
+pub fn record_request(route: &str, status: u16, elapsed: Duration) {
+    let mut registry = REGISTRY.lock().unwrap();
+    *registry
+        .requests
+        .entry((route.to_owned(), status))
+        .or_insert(0) += 1;
+    registry
+        .request_durations
+        .entry(route.to_owned())
+        .or_insert_with(Histogram::new)
+        .observe(elapsed);
+}
+
+pub fn record_error(e: &AppError) {
+    let mut registry = REGISTRY.lock().unwrap();
+    *registry.errors.entry(e.as_ref().to_owned()).or_insert(0) += 1;
+}
+
+pub fn record_year_advance(elapsed: Duration) {
+    REGISTRY
+        .lock()
+        .unwrap()
+        .year_advance_durations
+        .observe(elapsed);
+}
+
+pub fn record_db_pool_wait(elapsed: Duration) {
+    REGISTRY.lock().unwrap().db_pool_waits.observe(elapsed);
+}
+
+// renders all metrics in the prometheus text exposition format
+pub fn render(active_tournaments: i64) -> String {
+    let registry = REGISTRY.lock().unwrap();
+    let mut out = String::new();
+
+    out.push_str("# HELP http_requests_total Requests handled, by route and status code.\n");
+    out.push_str("# TYPE http_requests_total counter\n");
+    for ((route, status), n) in registry.requests.iter() {
+        let _ = writeln!(
+            out,
+            "http_requests_total{{route=\"{route}\",status=\"{status}\"}} {n}"
+        );
+    }
+
+    out.push_str("# HELP http_request_duration_seconds Request latency, by route.\n");
+    out.push_str("# TYPE http_request_duration_seconds histogram\n");
+    for (route, h) in registry.request_durations.iter() {
+        h.render(
+            &mut out,
+            "http_request_duration_seconds",
+            &format!("route=\"{route}\""),
+        );
+    }
+
+    out.push_str("# HELP app_errors_total Errors returned to clients, by kind.\n");
+    out.push_str("# TYPE app_errors_total counter\n");
+    for (kind, n) in registry.errors.iter() {
+        let _ = writeln!(out, "app_errors_total{{error=\"{kind}\"}} {n}");
+    }
+
+    out.push_str("# HELP active_tournaments Tournaments that have not been archived.\n");
+    out.push_str("# TYPE active_tournaments gauge\n");
+    let _ = writeln!(out, "active_tournaments {active_tournaments}");
+
+    out.push_str("# HELP tournament_year_advance_duration_seconds Time taken to advance a tournament year.\n");
+    out.push_str("# TYPE tournament_year_advance_duration_seconds histogram\n");
+    registry.year_advance_durations.render(
+        &mut out,
+        "tournament_year_advance_duration_seconds",
+        "",
+    );
+
+    out.push_str(
+        "# HELP db_pool_wait_seconds Time spent waiting for a free database connection.\n",
+    );
+    out.push_str("# TYPE db_pool_wait_seconds histogram\n");
+    registry
+        .db_pool_waits
+        .render(&mut out, "db_pool_wait_seconds", "");
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histograms_are_cumulative() {
+        let mut h = Histogram::new();
+        h.observe(Duration::from_millis(1));
+        h.observe(Duration::from_millis(20));
+        h.observe(Duration::from_secs(60));
+
+        let mut out = String::new();
+        h.render(&mut out, "x", "route=\"/a\"");
+        assert!(out.contains("x_bucket{route=\"/a\",le=\"0.005\"} 1\n"));
+        assert!(out.contains("x_bucket{route=\"/a\",le=\"0.025\"} 2\n"));
+        assert!(out.contains("x_bucket{route=\"/a\",le=\"10\"} 2\n"));
+        // the slow one only shows up in +Inf
+        assert!(out.contains("x_bucket{route=\"/a\",le=\"+Inf\"} 3\n"));
+        assert!(out.contains("x_count{route=\"/a\"} 3\n"));
+
+        let mut out = String::new();
+        h.render(&mut out, "y", "");
+        assert!(out.contains("y_bucket{le=\"+Inf\"} 3\n"));
+        assert!(out.contains("y_sum 60.021\n"));
+    }
+}
@@ -0,0 +1,654 @@
+use super::db::{Database, PooledClient};
+use super::db_types::*;
+use super::request;
+use super::response::AppError;
+use super::store::{Store, StoreTransaction};
+use super::utils;
+use async_trait::async_trait;
+use std::error::Error;
+use tokio_postgres::error::SqlState;
+use tokio_postgres::Client;
+
+use super::idempotency_key_service;
+use super::tournament_bot_service;
+use super::tournament_data_service;
+use super::tournament_final_result_service;
+use super::tournament_invite_code_service;
+use super::tournament_membership_service;
+use super::tournament_pause_service;
+use super::tournament_pseudonym_service;
+use super::tournament_role_service;
+use super::tournament_service;
+use super::tournament_state_service;
+use super::tournament_submission_service;
+use super::tournament_team_service;
+use super::tournament_year_demand_service;
+use super::tournament_year_service;
+
+pub fn report_postgres_err(e: tokio_postgres::Error) -> AppError {
+    utils::log(utils::Event {
+        msg: e.to_string(),
+        source: e.source().map(|e| e.to_string()),
+        severity: utils::SeverityKind::Error,
+    });
+    // postgres tells us before it drops the connection when it shuts down or kills our backend
+    let shutting_down = matches!(
+        e.code(),
+        Some(&SqlState::ADMIN_SHUTDOWN)
+            | Some(&SqlState::CRASH_SHUTDOWN)
+            | Some(&SqlState::CANNOT_CONNECT_NOW)
+    );
+    if e.is_closed() || shutting_down {
+        AppError::DatabaseUnavailable
+    } else {
+        AppError::InternalServerError
+    }
+}
+
+#[async_trait]
+impl Store for Database {
+    async fn begin(&self) -> Result<Box<dyn StoreTransaction>, AppError> {
+        let client = self.lock().await?;
+        client
+            .batch_execute("BEGIN")
+            .await
+            .map_err(report_postgres_err)?;
+        Ok(Box::new(PgTransaction {
+            client: Some(client),
+        }))
+    }
+
+    async fn ping(&self) -> bool {
+        Database::ping(self).await
+    }
+}
+
+// We hold on to a pooled connection for the whole transaction, so no one else can interleave
+// queries. Other transactions use the other connections in the pool.
+pub struct PgTransaction {
+    // only None after commit
+    client: Option<PooledClient>,
+}
+
+impl PgTransaction {
+    fn con(&mut self) -> &mut Client {
+        self.client.as_mut().expect("transaction already finished")
+    }
+}
+
+impl Drop for PgTransaction {
+    // roll back if we never committed, keeping the client locked until that's done
+    fn drop(&mut self) {
+        if let Some(client) = self.client.take() {
+            tokio::spawn(async move {
+                if let Err(e) = client.batch_execute("ROLLBACK").await {
+                    report_postgres_err(e);
+                }
+            });
+        }
+    }
+}
+
+#[async_trait]
+impl StoreTransaction for PgTransaction {
+    async fn commit(mut self: Box<Self>) -> Result<(), AppError> {
+        let client = self.client.take().expect("transaction already finished");
+        client
+            .batch_execute("COMMIT")
+            .await
+            .map_err(report_postgres_err)
+    }
+
+    async fn tournament_add(
+        &mut self,
+        creator_user_id: i64,
+        cost_per_unit: i64,
+        demand_xintercept: i64,
+        demand_yintercept: i64,
+        incentive_multiplier: i64,
+        incentive_start_year: i64,
+        max_years: i64,
+        year_duration_ms: Option<i64>,
+        late_join_policy: LateJoinPolicyKind,
+        exit_policy: ExitPolicyKind,
+        visibility: VisibilityKind,
+        min_players: Option<i64>,
+        max_players: Option<i64>,
+        auto_start: bool,
+        practice: bool,
+    ) -> Result<Tournament, AppError> {
+        tournament_service::add(
+            self.con(),
+            creator_user_id,
+            cost_per_unit,
+            demand_xintercept,
+            demand_yintercept,
+            incentive_multiplier,
+            incentive_start_year,
+            max_years,
+            year_duration_ms,
+            late_join_policy,
+            exit_policy,
+            visibility,
+            min_players,
+            max_players,
+            auto_start,
+            practice,
+        )
+        .await
+        .map_err(report_postgres_err)
+    }
+
+    async fn tournament_get(&mut self, tournament_id: i64) -> Result<Option<Tournament>, AppError> {
+        tournament_service::get_by_tournament_id(self.con(), tournament_id)
+            .await
+            .map_err(report_postgres_err)
+    }
+
+    async fn tournament_data_add(
+        &mut self,
+        creator_user_id: i64,
+        tournament_id: i64,
+        title: String,
+        active: bool,
+    ) -> Result<TournamentData, AppError> {
+        tournament_data_service::add(self.con(), creator_user_id, tournament_id, title, active)
+            .await
+            .map_err(report_postgres_err)
+    }
+
+    async fn tournament_data_get_recent(
+        &mut self,
+        tournament_id: i64,
+    ) -> Result<Option<TournamentData>, AppError> {
+        tournament_data_service::get_recent_by_tournament_id(self.con(), tournament_id)
+            .await
+            .map_err(report_postgres_err)
+    }
+
+    async fn tournament_data_count_active(&mut self) -> Result<i64, AppError> {
+        tournament_data_service::count_active(self.con())
+            .await
+            .map_err(report_postgres_err)
+    }
+
+    async fn tournament_data_query(
+        &mut self,
+        props: request::TournamentDataViewProps,
+    ) -> Result<Vec<TournamentData>, AppError> {
+        tournament_data_service::query(self.con(), props)
+            .await
+            .map_err(report_postgres_err)
+    }
+
+    async fn tournament_state_add(
+        &mut self,
+        creator_user_id: i64,
+        tournament_id: i64,
+        state: TournamentStateKind,
+    ) -> Result<TournamentState, AppError> {
+        tournament_state_service::add(self.con(), creator_user_id, tournament_id, state)
+            .await
+            .map_err(report_postgres_err)
+    }
+
+    async fn tournament_state_get_recent(
+        &mut self,
+        tournament_id: i64,
+    ) -> Result<Option<TournamentState>, AppError> {
+        tournament_state_service::get_recent_by_tournament_id(self.con(), tournament_id)
+            .await
+            .map_err(report_postgres_err)
+    }
+
+    async fn tournament_year_add(
+        &mut self,
+        creator_user_id: i64,
+        tournament_id: i64,
+        current_year: i64,
+        deadline: Option<i64>,
+    ) -> Result<TournamentYear, AppError> {
+        tournament_year_service::add(
+            self.con(),
+            creator_user_id,
+            tournament_id,
+            current_year,
+            deadline,
+        )
+        .await
+        .map_err(report_postgres_err)
+    }
+
+    async fn tournament_year_get_recent(
+        &mut self,
+        tournament_id: i64,
+    ) -> Result<Option<TournamentYear>, AppError> {
+        tournament_year_service::get_recent_by_tournament_id(self.con(), tournament_id)
+            .await
+            .map_err(report_postgres_err)
+    }
+
+    async fn tournament_year_get_due(&mut self, now: i64) -> Result<Vec<TournamentYear>, AppError> {
+        tournament_year_service::get_due(self.con(), now)
+            .await
+            .map_err(report_postgres_err)
+    }
+
+    async fn tournament_year_query(
+        &mut self,
+        props: request::TournamentYearViewProps,
+    ) -> Result<Vec<TournamentYear>, AppError> {
+        tournament_year_service::query(self.con(), props)
+            .await
+            .map_err(report_postgres_err)
+    }
+
+    async fn tournament_pause_add(
+        &mut self,
+        creator_user_id: i64,
+        tournament_id: i64,
+        start_time: i64,
+        end_time: i64,
+    ) -> Result<TournamentPause, AppError> {
+        tournament_pause_service::add(
+            self.con(),
+            creator_user_id,
+            tournament_id,
+            start_time,
+            end_time,
+        )
+        .await
+        .map_err(report_postgres_err)
+    }
+
+    async fn tournament_pause_query(
+        &mut self,
+        props: request::TournamentPauseViewProps,
+    ) -> Result<Vec<TournamentPause>, AppError> {
+        tournament_pause_service::query(self.con(), props)
+            .await
+            .map_err(report_postgres_err)
+    }
+
+    async fn tournament_year_demand_add(
+        &mut self,
+        firm: Firm,
+        tournament_id: i64,
+        year: i64,
+        demand: i64,
+    ) -> Result<TournamentYearDemand, AppError> {
+        tournament_year_demand_service::add(self.con(), firm, tournament_id, year, demand)
+            .await
+            .map_err(report_postgres_err)
+    }
+
+    async fn tournament_year_demand_get_by_tournament(
+        &mut self,
+        tournament_id: i64,
+    ) -> Result<Vec<TournamentYearDemand>, AppError> {
+        tournament_year_demand_service::get_by_tournament_id(self.con(), tournament_id)
+            .await
+            .map_err(report_postgres_err)
+    }
+
+    async fn tournament_year_demand_query(
+        &mut self,
+        props: request::TournamentYearDemandViewProps,
+    ) -> Result<Vec<TournamentYearDemand>, AppError> {
+        tournament_year_demand_service::query(self.con(), props)
+            .await
+            .map_err(report_postgres_err)
+    }
+
+    async fn tournament_membership_add(
+        &mut self,
+        creator_user_id: i64,
+        tournament_id: i64,
+        active: bool,
+        observer: bool,
+        approval: ApprovalKind,
+        removed: bool,
+        banned: bool,
+        actor_user_id: i64,
+        reason: Option<String>,
+        tournament_team_id: Option<i64>,
+    ) -> Result<TournamentMembership, AppError> {
+        tournament_membership_service::add(
+            self.con(),
+            creator_user_id,
+            tournament_id,
+            active,
+            observer,
+            approval,
+            removed,
+            banned,
+            actor_user_id,
+            reason,
+            tournament_team_id,
+        )
+        .await
+        .map_err(report_postgres_err)
+    }
+
+    async fn tournament_membership_get_recent_by_tournament(
+        &mut self,
+        tournament_id: i64,
+    ) -> Result<Vec<TournamentMembership>, AppError> {
+        tournament_membership_service::get_recent_by_tournament(self.con(), tournament_id)
+            .await
+            .map_err(report_postgres_err)
+    }
+
+    async fn tournament_membership_get_recent_by_tournament_user(
+        &mut self,
+        tournament_id: i64,
+        user_id: i64,
+    ) -> Result<Option<TournamentMembership>, AppError> {
+        tournament_membership_service::get_recent_by_tournament_user(
+            self.con(),
+            tournament_id,
+            user_id,
+        )
+        .await
+        .map_err(report_postgres_err)
+    }
+
+    async fn tournament_membership_query(
+        &mut self,
+        props: request::TournamentMembershipViewProps,
+    ) -> Result<Vec<TournamentMembership>, AppError> {
+        tournament_membership_service::query(self.con(), props)
+            .await
+            .map_err(report_postgres_err)
+    }
+
+    async fn tournament_submission_add(
+        &mut self,
+        creator_user_id: i64,
+        firm: Firm,
+        tournament_id: i64,
+        year: i64,
+        amount: i64,
+        autogenerated: bool,
+    ) -> Result<TournamentSubmission, AppError> {
+        tournament_submission_service::add(
+            self.con(),
+            creator_user_id,
+            firm,
+            tournament_id,
+            year,
+            amount,
+            autogenerated,
+        )
+        .await
+        .map_err(report_postgres_err)
+    }
+
+    async fn tournament_submission_get_recent_by_tournament(
+        &mut self,
+        tournament_id: i64,
+    ) -> Result<Vec<TournamentSubmission>, AppError> {
+        tournament_submission_service::get_recent_by_tournament(self.con(), tournament_id)
+            .await
+            .map_err(report_postgres_err)
+    }
+
+    async fn tournament_submission_query(
+        &mut self,
+        props: request::TournamentSubmissionViewProps,
+    ) -> Result<Vec<TournamentSubmission>, AppError> {
+        tournament_submission_service::query(self.con(), props)
+            .await
+            .map_err(report_postgres_err)
+    }
+
+    async fn tournament_invite_code_add(
+        &mut self,
+        creator_user_id: i64,
+        tournament_id: i64,
+        invite_code: String,
+    ) -> Result<TournamentInviteCode, AppError> {
+        tournament_invite_code_service::add(self.con(), creator_user_id, tournament_id, invite_code)
+            .await
+            .map_err(report_postgres_err)
+    }
+
+    async fn tournament_invite_code_get_recent(
+        &mut self,
+        tournament_id: i64,
+    ) -> Result<Option<TournamentInviteCode>, AppError> {
+        tournament_invite_code_service::get_recent_by_tournament_id(self.con(), tournament_id)
+            .await
+            .map_err(report_postgres_err)
+    }
+
+    async fn tournament_team_add(
+        &mut self,
+        creator_user_id: i64,
+        tournament_id: i64,
+        name: String,
+    ) -> Result<TournamentTeam, AppError> {
+        tournament_team_service::add(self.con(), creator_user_id, tournament_id, name)
+            .await
+            .map_err(report_postgres_err)
+    }
+
+    async fn tournament_team_get(
+        &mut self,
+        tournament_team_id: i64,
+    ) -> Result<Option<TournamentTeam>, AppError> {
+        tournament_team_service::get_by_tournament_team_id(self.con(), tournament_team_id)
+            .await
+            .map_err(report_postgres_err)
+    }
+
+    async fn tournament_team_query(
+        &mut self,
+        props: request::TournamentTeamViewProps,
+    ) -> Result<Vec<TournamentTeam>, AppError> {
+        tournament_team_service::query(self.con(), props)
+            .await
+            .map_err(report_postgres_err)
+    }
+
+    async fn tournament_bot_add(
+        &mut self,
+        creator_user_id: i64,
+        tournament_id: i64,
+        strategy: BotStrategyKind,
+        quantity: Option<i64>,
+        name: String,
+    ) -> Result<TournamentBot, AppError> {
+        tournament_bot_service::add(
+            self.con(),
+            creator_user_id,
+            tournament_id,
+            strategy,
+            quantity,
+            name,
+        )
+        .await
+        .map_err(report_postgres_err)
+    }
+
+    async fn tournament_bot_get(
+        &mut self,
+        tournament_bot_id: i64,
+    ) -> Result<Option<TournamentBot>, AppError> {
+        tournament_bot_service::get_by_tournament_bot_id(self.con(), tournament_bot_id)
+            .await
+            .map_err(report_postgres_err)
+    }
+
+    async fn tournament_bot_get_by_tournament(
+        &mut self,
+        tournament_id: i64,
+    ) -> Result<Vec<TournamentBot>, AppError> {
+        tournament_bot_service::get_by_tournament(self.con(), tournament_id)
+            .await
+            .map_err(report_postgres_err)
+    }
+
+    async fn tournament_bot_query(
+        &mut self,
+        props: request::TournamentBotViewProps,
+    ) -> Result<Vec<TournamentBot>, AppError> {
+        tournament_bot_service::query(self.con(), props)
+            .await
+            .map_err(report_postgres_err)
+    }
+
+    async fn tournament_pseudonym_add(
+        &mut self,
+        creator_user_id: i64,
+        tournament_id: i64,
+        user_id: i64,
+        pseudonym: String,
+    ) -> Result<Option<TournamentPseudonym>, AppError> {
+        tournament_pseudonym_service::add(
+            self.con(),
+            creator_user_id,
+            tournament_id,
+            user_id,
+            pseudonym,
+        )
+        .await
+        .map_err(report_postgres_err)
+    }
+
+    async fn tournament_pseudonym_get_by_tournament_user(
+        &mut self,
+        tournament_id: i64,
+        user_id: i64,
+    ) -> Result<Option<TournamentPseudonym>, AppError> {
+        tournament_pseudonym_service::get_by_tournament_user(self.con(), tournament_id, user_id)
+            .await
+            .map_err(report_postgres_err)
+    }
+
+    async fn tournament_pseudonym_get_by_tournament(
+        &mut self,
+        tournament_id: i64,
+    ) -> Result<Vec<TournamentPseudonym>, AppError> {
+        tournament_pseudonym_service::get_by_tournament(self.con(), tournament_id)
+            .await
+            .map_err(report_postgres_err)
+    }
+
+    async fn tournament_role_add(
+        &mut self,
+        creator_user_id: i64,
+        tournament_id: i64,
+        user_id: i64,
+        role: TournamentRoleKind,
+        active: bool,
+    ) -> Result<TournamentRole, AppError> {
+        tournament_role_service::add(
+            self.con(),
+            creator_user_id,
+            tournament_id,
+            user_id,
+            role,
+            active,
+        )
+        .await
+        .map_err(report_postgres_err)
+    }
+
+    async fn tournament_role_get_recent_by_tournament_user(
+        &mut self,
+        tournament_id: i64,
+        user_id: i64,
+    ) -> Result<Option<TournamentRole>, AppError> {
+        tournament_role_service::get_recent_by_tournament_user(self.con(), tournament_id, user_id)
+            .await
+            .map_err(report_postgres_err)
+    }
+
+    async fn tournament_role_query(
+        &mut self,
+        props: request::TournamentRoleViewProps,
+    ) -> Result<Vec<TournamentRole>, AppError> {
+        tournament_role_service::query(self.con(), props)
+            .await
+            .map_err(report_postgres_err)
+    }
+
+    async fn tournament_final_result_add(
+        &mut self,
+        creator_user_id: i64,
+        tournament_id: i64,
+        firm: Firm,
+        rank: i64,
+        balance: f64,
+    ) -> Result<TournamentFinalResult, AppError> {
+        tournament_final_result_service::add(
+            self.con(),
+            creator_user_id,
+            tournament_id,
+            firm,
+            rank,
+            balance,
+        )
+        .await
+        .map_err(report_postgres_err)
+    }
+
+    async fn tournament_final_result_get_by_tournament(
+        &mut self,
+        tournament_id: i64,
+    ) -> Result<Vec<TournamentFinalResult>, AppError> {
+        tournament_final_result_service::get_by_tournament_id(self.con(), tournament_id)
+            .await
+            .map_err(report_postgres_err)
+    }
+
+    async fn idempotency_key_add(
+        &mut self,
+        creator_user_id: i64,
+        endpoint: String,
+        idempotency_key: String,
+        request_hash: String,
+        response: String,
+        min_creation_time: i64,
+    ) -> Result<Option<IdempotencyKey>, AppError> {
+        idempotency_key_service::add(
+            self.con(),
+            creator_user_id,
+            endpoint,
+            idempotency_key,
+            request_hash,
+            response,
+            min_creation_time,
+        )
+        .await
+        .map_err(report_postgres_err)
+    }
+
+    async fn idempotency_key_get_recent(
+        &mut self,
+        creator_user_id: i64,
+        endpoint: &str,
+        idempotency_key: &str,
+        min_creation_time: i64,
+    ) -> Result<Option<IdempotencyKey>, AppError> {
+        idempotency_key_service::get_recent(
+            self.con(),
+            creator_user_id,
+            endpoint,
+            idempotency_key,
+            min_creation_time,
+        )
+        .await
+        .map_err(report_postgres_err)
+    }
+
+    async fn idempotency_key_delete_expired(
+        &mut self,
+        min_creation_time: i64,
+    ) -> Result<u64, AppError> {
+        idempotency_key_service::delete_expired(self.con(), min_creation_time)
+            .await
+            .map_err(report_postgres_err)
+    }
+}
@@ -1,3 +1,7 @@
+use super::db_types::{
+    ApprovalKind, BotStrategyKind, ExitPolicyKind, LateJoinPolicyKind, TournamentRoleKind,
+    TournamentStateKind, VisibilityKind,
+};
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -11,6 +15,24 @@ pub struct TournamentNewProps {
     pub incentive_multiplier: i64,
     pub incentive_start_year: i64,
     pub max_years: i64,
+    // leave out to only advance years by hand
+    pub year_duration_ms: Option<i64>,
+    // defaults to FORBIDDEN
+    pub late_join_policy: Option<LateJoinPolicyKind>,
+    // defaults to KEEP_BALANCE
+    pub exit_policy: Option<ExitPolicyKind>,
+    // defaults to PUBLIC
+    pub visibility: Option<VisibilityKind>,
+    // leave out to start with any number of players
+    pub min_players: Option<i64>,
+    // leave out to let anyone join
+    pub max_players: Option<i64>,
+    // start as soon as min_players have joined, defaults to false
+    pub auto_start: Option<bool>,
+    // play alone against bots, starting right away, defaults to false
+    pub practice: Option<bool>,
+    // retrying with the same key returns the first response instead of doing it again
+    pub idempotency_key: Option<String>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -20,6 +42,18 @@ pub struct TournamentDataNewProps {
     pub title: String,
     pub active: bool,
     pub api_key: String,
+    // retrying with the same key returns the first response instead of doing it again
+    pub idempotency_key: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentStateNewProps {
+    pub tournament_id: i64,
+    pub state: TournamentStateKind,
+    pub api_key: String,
+    // retrying with the same key returns the first response instead of doing it again
+    pub idempotency_key: Option<String>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -27,6 +61,8 @@ pub struct TournamentDataNewProps {
 pub struct TournamentYearNewProps {
     pub tournament_id: i64,
     pub api_key: String,
+    // retrying with the same key returns the first response instead of doing it again
+    pub idempotency_key: Option<String>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -34,7 +70,95 @@ pub struct TournamentYearNewProps {
 pub struct TournamentMembershipNewProps {
     pub tournament_id: i64,
     pub active: bool,
+    // needed to join invite only tournaments the first time
+    pub invite_code: Option<String>,
+    // play for a team instead of on your own, only when joining the first time
+    pub tournament_team_id: Option<i64>,
+    pub api_key: String,
+    // retrying with the same key returns the first response instead of doing it again
+    pub idempotency_key: Option<String>,
+}
+
+// lets a pending member in, or turns them away
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentMembershipApprovalNewProps {
+    pub tournament_id: i64,
+    pub user_id: i64,
+    pub approved: bool,
+    pub api_key: String,
+    // retrying with the same key returns the first response instead of doing it again
+    pub idempotency_key: Option<String>,
+}
+
+// what an organizer does to a member
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum MembershipActionKind {
+    // takes them out of the game until an organizer reinstates them
+    Remove,
+    // takes them out of the game for good
+    Ban,
+    // brings a removed or banned member back
+    Reinstate,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentMembershipActionNewProps {
+    pub tournament_id: i64,
+    pub user_id: i64,
+    pub action: MembershipActionKind,
+    // shown to the member, and kept in the membership history
+    pub reason: Option<String>,
+    pub api_key: String,
+    // retrying with the same key returns the first response instead of doing it again
+    pub idempotency_key: Option<String>,
+}
+
+// replaces the invite code, so the old one stops working
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentInviteCodeNewProps {
+    pub tournament_id: i64,
+    pub api_key: String,
+    // retrying with the same key returns the first response instead of doing it again
+    pub idempotency_key: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentTeamNewProps {
+    pub tournament_id: i64,
+    pub name: String,
+    pub api_key: String,
+    // retrying with the same key returns the first response instead of doing it again
+    pub idempotency_key: Option<String>,
+}
+
+// adds a computer player, quantity is what a FIXED_QUANTITY bot submits every year
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentBotNewProps {
+    pub tournament_id: i64,
+    pub strategy: BotStrategyKind,
+    pub quantity: Option<i64>,
+    pub api_key: String,
+    // retrying with the same key returns the first response instead of doing it again
+    pub idempotency_key: Option<String>,
+}
+
+// grants a role, or revokes it when active is false
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentRoleNewProps {
+    pub tournament_id: i64,
+    pub user_id: i64,
+    pub role: TournamentRoleKind,
+    pub active: bool,
     pub api_key: String,
+    // retrying with the same key returns the first response instead of doing it again
+    pub idempotency_key: Option<String>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -43,6 +167,17 @@ pub struct TournamentSubmissionNewProps {
     pub tournament_id: i64,
     pub amount: i64,
     pub api_key: String,
+    // retrying with the same key returns the first response instead of doing it again
+    pub idempotency_key: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentFinalResultNewProps {
+    pub tournament_id: i64,
+    pub api_key: String,
+    // retrying with the same key returns the first response instead of doing it again
+    pub idempotency_key: Option<String>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -54,6 +189,9 @@ pub struct TournamentDataViewProps {
     pub creator_user_id: Option<Vec<i64>>,
     pub tournament_id: Option<Vec<i64>>,
     pub active: Option<bool>,
+    // true lists only practice tournaments and false leaves them out,
+    // by default your own are listed alongside the rest
+    pub practice: Option<bool>,
     pub only_recent: bool,
     pub api_key: String,
 }
@@ -66,6 +204,9 @@ pub struct TournamentYearViewProps {
     pub max_creation_time: Option<i64>,
     pub creator_user_id: Option<Vec<i64>>,
     pub tournament_id: Option<Vec<i64>>,
+    // true lists only practice tournaments and false leaves them out,
+    // by default your own are listed alongside the rest
+    pub practice: Option<bool>,
     pub only_recent: bool,
     pub api_key: String,
 }
@@ -79,6 +220,55 @@ pub struct TournamentMembershipViewProps {
     pub creator_user_id: Option<Vec<i64>>,
     pub tournament_id: Option<Vec<i64>>,
     pub active: Option<bool>,
+    // PENDING lists the members waiting for approval
+    pub approval: Option<ApprovalKind>,
+    pub only_recent: bool,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentInviteCodeViewProps {
+    pub tournament_id: i64,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentPseudonymViewProps {
+    pub tournament_id: i64,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentTeamViewProps {
+    pub tournament_team_id: Option<Vec<i64>>,
+    pub min_creation_time: Option<i64>,
+    pub max_creation_time: Option<i64>,
+    pub tournament_id: Option<Vec<i64>>,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentBotViewProps {
+    pub tournament_bot_id: Option<Vec<i64>>,
+    pub min_creation_time: Option<i64>,
+    pub max_creation_time: Option<i64>,
+    pub tournament_id: Option<Vec<i64>>,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentRoleViewProps {
+    pub tournament_role_id: Option<Vec<i64>>,
+    pub min_creation_time: Option<i64>,
+    pub max_creation_time: Option<i64>,
+    pub tournament_id: Option<Vec<i64>>,
+    pub user_id: Option<Vec<i64>>,
+    pub active: Option<bool>,
     pub only_recent: bool,
     pub api_key: String,
 }
@@ -91,6 +281,10 @@ pub struct TournamentSubmissionViewProps {
     pub max_creation_time: Option<i64>,
     pub creator_user_id: Option<Vec<i64>>,
     pub tournament_id: Option<Vec<i64>>,
+    pub tournament_team_id: Option<Vec<i64>>,
+    // true lists only practice tournaments and false leaves them out,
+    // by default your own are listed alongside the rest
+    pub practice: Option<bool>,
     pub api_key: String,
 }
 
@@ -102,5 +296,96 @@ pub struct TournamentYearDemandViewProps {
     pub max_creation_time: Option<i64>,
     pub user_id: Option<Vec<i64>>,
     pub tournament_id: Option<Vec<i64>>,
+    pub tournament_team_id: Option<Vec<i64>>,
+    // true lists only practice tournaments and false leaves them out,
+    // by default your own are listed alongside the rest
+    pub practice: Option<bool>,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentFinalResultViewProps {
+    pub tournament_id: i64,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentAnalyticsViewProps {
+    pub tournament_id: i64,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentCollusionViewProps {
+    pub tournament_id: i64,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentBenchmarksViewProps {
+    pub tournament_id: i64,
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentPauseViewProps {
+    pub tournament_pause_id: Option<Vec<i64>>,
+    pub min_creation_time: Option<i64>,
+    pub max_creation_time: Option<i64>,
+    pub tournament_id: Option<Vec<i64>>,
     pub api_key: String,
 }
+
+// requests that can be retried with an idempotency key
+pub trait Idempotent: Serialize {
+    fn idempotency_key(&self) -> Option<&str>;
+
+    // identifies what was asked for, so a key can't be used again for something else
+    // the api key is left out since it changes without the request changing
+    fn fingerprint(&self) -> String {
+        let mut value = serde_json::to_value(self).unwrap_or(serde_json::Value::Null);
+        if let Some(fields) = value.as_object_mut() {
+            fields.remove("apiKey");
+            fields.remove("idempotencyKey");
+        }
+        // 64 bit FNV-1a, which unlike std's hasher is the same across releases
+        let hash = value
+            .to_string()
+            .bytes()
+            .fold(0xcbf29ce484222325u64, |h, b| {
+                (h ^ b as u64).wrapping_mul(0x100000001b3)
+            });
+        format!("{:016x}", hash)
+    }
+}
+
+macro_rules! idempotent {
+    ($($props:ty),*) => {
+        $(impl Idempotent for $props {
+            fn idempotency_key(&self) -> Option<&str> {
+                self.idempotency_key.as_deref()
+            }
+        })*
+    };
+}
+
+idempotent!(
+    TournamentNewProps,
+    TournamentDataNewProps,
+    TournamentStateNewProps,
+    TournamentYearNewProps,
+    TournamentMembershipNewProps,
+    TournamentMembershipApprovalNewProps,
+    TournamentMembershipActionNewProps,
+    TournamentInviteCodeNewProps,
+    TournamentTeamNewProps,
+    TournamentBotNewProps,
+    TournamentRoleNewProps,
+    TournamentSubmissionNewProps,
+    TournamentFinalResultNewProps
+);
@@ -1,3 +1,7 @@
+use super::db_types::{
+    ApprovalKind, BotStrategyKind, ExitPolicyKind, LateJoinPolicyKind, TournamentRoleKind,
+    TournamentStateKind, VisibilityKind,
+};
 use serde::{Deserialize, Serialize};
 use strum::AsRefStr;
 
@@ -14,8 +18,27 @@ pub enum AppError {
     TournamentStarted,
     TournamentMembershipInvalid,
     TournamentMaxYearsAchieved,
+    TournamentNotRunning,
+    TournamentFinished,
+    TournamentStateTransitionInvalid,
+    TournamentYearDurationInvalid,
+    TournamentRoleInvalid,
+    TournamentVisibilityInvalid,
+    TournamentInviteCodeInvalid,
+    TournamentMembershipRejected,
+    TournamentMembershipBanned,
+    TournamentMembershipRemoved,
+    TournamentPlayerCountInvalid,
+    TournamentFull,
+    TournamentNotEnoughPlayers,
+    TournamentTeamInvalid,
+    TournamentBotInvalid,
+    TournamentPractice,
+    IdempotencyKeyReused,
     DecodeError,
     InternalServerError,
+    AuthServiceUnavailable,
+    DatabaseUnavailable,
     MethodNotAllowed,
     Unauthorized,
     BadRequest,
@@ -32,6 +55,15 @@ impl std::fmt::Display for AppError {
 
 impl std::error::Error for AppError {}
 
+// what a failed request gets back
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Error {
+    pub error: AppError,
+    // quote this when reporting a problem, it's in our logs too
+    pub request_id: String,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Tournament {
@@ -44,6 +76,15 @@ pub struct Tournament {
     pub incentive_multiplier: i64,
     pub incentive_start_year: i64,
     pub max_years: i64,
+    pub year_duration_ms: Option<i64>,
+    pub late_join_policy: LateJoinPolicyKind,
+    pub exit_policy: ExitPolicyKind,
+    pub visibility: VisibilityKind,
+    pub min_players: Option<i64>,
+    pub max_players: Option<i64>,
+    pub auto_start: bool,
+    pub practice: bool,
+    pub state: TournamentStateKind,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -57,6 +98,16 @@ pub struct TournamentData {
     pub active: bool,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentState {
+    pub tournament_state_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub tournament: Tournament,
+    pub state: TournamentStateKind,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TournamentYear {
@@ -65,6 +116,18 @@ pub struct TournamentYear {
     pub creator_user_id: i64,
     pub tournament: Tournament,
     pub current_year: i64,
+    pub deadline: Option<i64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentPause {
+    pub tournament_pause_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub tournament: Tournament,
+    pub start_time: i64,
+    pub end_time: i64,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -72,7 +135,10 @@ pub struct TournamentYear {
 pub struct TournamentYearDemand {
     pub tournament_year_demand_id: i64,
     pub creation_time: i64,
-    pub user_id: i64,
+    // only shown to organizers, and to the player themselves
+    pub user_id: Option<i64>,
+    pub tournament_team_id: Option<i64>,
+    pub tournament_bot_id: Option<i64>,
     pub tournament: Tournament,
     pub year: i64,
     pub demand: i64,
@@ -84,8 +150,72 @@ pub struct TournamentYearDemand {
 pub struct TournamentMembership {
     pub tournament_membership_id: i64,
     pub creation_time: i64,
+    // user ids are only shown to organizers, and to the user themselves
+    pub creator_user_id: Option<i64>,
+    pub tournament: Tournament,
+    pub active: bool,
+    pub observer: bool,
+    pub approval: ApprovalKind,
+    pub removed: bool,
+    pub banned: bool,
+    pub actor_user_id: Option<i64>,
+    pub reason: Option<String>,
+    pub tournament_team_id: Option<i64>,
+    pub pseudonym: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentInviteCode {
+    pub tournament_invite_code_id: i64,
+    pub creation_time: i64,
     pub creator_user_id: i64,
     pub tournament: Tournament,
+    pub invite_code: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentPseudonym {
+    pub tournament_pseudonym_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub tournament: Tournament,
+    pub user_id: i64,
+    pub pseudonym: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentTeam {
+    pub tournament_team_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub tournament: Tournament,
+    pub name: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentBot {
+    pub tournament_bot_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub tournament: Tournament,
+    pub strategy: BotStrategyKind,
+    pub quantity: Option<i64>,
+    pub name: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentRole {
+    pub tournament_role_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub tournament: Tournament,
+    pub user_id: i64,
+    pub role: TournamentRoleKind,
     pub active: bool,
 }
 
@@ -94,13 +224,165 @@ pub struct TournamentMembership {
 pub struct TournamentSubmission {
     pub tournament_submission_id: i64,
     pub creation_time: i64,
-    pub creator_user_id: i64,
+    // only shown to organizers, and to whoever submitted
+    pub creator_user_id: Option<i64>,
+    pub tournament_team_id: Option<i64>,
+    pub tournament_bot_id: Option<i64>,
+    // the pseudonym of whoever submitted
+    pub pseudonym: Option<String>,
     pub tournament: Tournament,
     pub amount: i64,
     pub year: i64,
     pub autogenerated: bool,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentFinalResult {
+    pub tournament_final_result_id: i64,
+    pub creation_time: i64,
+    pub creator_user_id: i64,
+    pub tournament: Tournament,
+    // only shown to organizers, and to the player themselves
+    pub user_id: Option<i64>,
+    pub tournament_team_id: Option<i64>,
+    pub tournament_bot_id: Option<i64>,
+    // the player's pseudonym, or the team's or bot's name
+    pub pseudonym: Option<String>,
+    pub rank: i64,
+    pub balance: f64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BenchmarkOutcome {
+    pub quantity: f64,
+    pub total_quantity: f64,
+    pub price: f64,
+    pub profit: f64,
+}
+
+// what textbook play would have looked like in one year
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentBenchmark {
+    pub tournament_id: i64,
+    pub year: i64,
+    // firms selling that year
+    pub firms: i64,
+    pub total_incentive: i64,
+    pub nash: BenchmarkOutcome,
+    pub monopoly: BenchmarkOutcome,
+    pub competitive: BenchmarkOutcome,
+}
+
+// one firm in one year
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalyticsFirmYear {
+    pub user_id: Option<i64>,
+    pub tournament_team_id: Option<i64>,
+    pub tournament_bot_id: Option<i64>,
+    pub pseudonym: Option<String>,
+    pub quantity: i64,
+    // of the year's total, between 0 and 1
+    pub share: f64,
+    // what would have earned the most, given what everyone else did
+    pub best_response: f64,
+    // positive when the firm produced more than its best response
+    pub deviation: f64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalyticsYear {
+    pub year: i64,
+    pub total_quantity: i64,
+    pub price: f64,
+    pub nash_price: f64,
+    // positive when the market sold above the Nash price
+    pub nash_price_gap: f64,
+    // Herfindahl-Hirschman index from 0 to 10000, null if nobody produced anything
+    pub hhi: Option<f64>,
+    pub firms: Vec<AnalyticsFirmYear>,
+}
+
+// how one firm played over the whole game
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalyticsFirm {
+    pub user_id: Option<i64>,
+    pub tournament_team_id: Option<i64>,
+    pub tournament_bot_id: Option<i64>,
+    pub pseudonym: Option<String>,
+    pub mean_quantity: f64,
+    // low when the firm stuck to the same quantity every year
+    pub quantity_std_dev: f64,
+    // average distance from the best response
+    pub mean_deviation: f64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentAnalytics {
+    pub tournament: Tournament,
+    // only years that are over
+    pub years: Vec<AnalyticsYear>,
+    pub firms: Vec<AnalyticsFirm>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CollusionYear {
+    pub year: i64,
+    pub total_quantity: i64,
+    pub nash_quantity: f64,
+    pub monopoly_quantity: f64,
+    // 0 at the Nash equilibrium, 1 at the monopoly output, null if the two are the same
+    pub collusion_index: Option<f64>,
+    pub near_monopoly: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CollusionFirm {
+    pub user_id: Option<i64>,
+    pub tournament_team_id: Option<i64>,
+    pub tournament_bot_id: Option<i64>,
+    pub pseudonym: Option<String>,
+}
+
+// how two firms played against each other
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CollusionPair {
+    pub firms: Vec<CollusionFirm>,
+    pub years_together: i64,
+    pub parallel_moves: i64,
+    pub opposite_moves: i64,
+    // the two mostly changed their quantities in the same direction
+    pub parallel: bool,
+    // one left the cartel while the other kept to its share
+    pub defections: i64,
+    // the other left it too the year after
+    pub punishments: i64,
+    pub punishing: bool,
+    // most years in a row both kept to their share of the monopoly output
+    pub longest_cooperation: i64,
+    pub sustained_cooperation: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentCollusion {
+    pub tournament: Tournament,
+    // only years that are over
+    pub years: Vec<CollusionYear>,
+    pub longest_near_monopoly: i64,
+    pub sustained_near_monopoly: bool,
+    pub pairs: Vec<CollusionPair>,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Info {
@@ -109,3 +391,10 @@ pub struct Info {
     pub version_minor: i64,
     pub version_rev: i64,
 }
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Readiness {
+    pub database: bool,
+    pub auth_service: bool,
+}
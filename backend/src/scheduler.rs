@@ -0,0 +1,51 @@
+use super::handlers;
+use super::response::AppError;
+use super::utils;
+use super::Config;
+use super::Db;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+// Advances tournament years once their deadline passes, and forgets expired idempotency keys.
+// Runs every `interval` until shutdown is signalled.
+pub fn spawn(
+    db: Db,
+    config: Config,
+    interval: Duration,
+    mut shutdown: watch::Receiver<bool>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(interval) => {}
+                _ = shutdown.changed() => break,
+            }
+            let now = utils::current_time_millis();
+            match tick(&db, now).await {
+                Ok(0) => {}
+                Ok(n) => {
+                    tracing::info!(tournaments = n, "advanced tournaments past their deadline")
+                }
+                // the next tick retries whatever failed
+                Err(e) => tracing::warn!(error = %e, "couldn't advance tournaments"),
+            }
+            match prune(&db, &config, now).await {
+                Ok(0) => {}
+                Ok(n) => tracing::debug!(keys = n, "pruned expired idempotency keys"),
+                Err(e) => tracing::warn!(error = %e, "couldn't prune idempotency keys"),
+            }
+        }
+        tracing::info!("scheduler stopped");
+    })
+}
+
+// one pass over every tournament whose deadline is at or before now
+pub async fn tick(db: &Db, now: i64) -> Result<usize, AppError> {
+    handlers::advance_due_years(db, now).await
+}
+
+// deletes idempotency keys that are too old to be replayed
+pub async fn prune(db: &Db, config: &Config, now: i64) -> Result<u64, AppError> {
+    handlers::prune_idempotency_keys(db, config, now).await
+}
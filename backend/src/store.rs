@@ -0,0 +1,324 @@
+use super::db_types::*;
+use super::request;
+use super::response::AppError;
+use async_trait::async_trait;
+
+// A place to keep tournaments.
+// Handlers only talk to storage through this trait, so they can run against postgres
+// in production and against memory in tests.
+#[async_trait]
+pub trait Store: Send + Sync {
+    // starts a transaction, which is rolled back unless it is committed
+    async fn begin(&self) -> Result<Box<dyn StoreTransaction>, AppError>;
+
+    // checks that the store can actually serve queries right now
+    async fn ping(&self) -> bool;
+}
+
+// Every read and write happens inside a transaction.
+// The methods mirror the functions in the *_service modules.
+#[async_trait]
+#[allow(clippy::too_many_arguments)]
+pub trait StoreTransaction: Send {
+    async fn commit(self: Box<Self>) -> Result<(), AppError>;
+
+    // tournament
+    async fn tournament_add(
+        &mut self,
+        creator_user_id: i64,
+        cost_per_unit: i64,
+        demand_xintercept: i64,
+        demand_yintercept: i64,
+        incentive_multiplier: i64,
+        incentive_start_year: i64,
+        max_years: i64,
+        year_duration_ms: Option<i64>,
+        late_join_policy: LateJoinPolicyKind,
+        exit_policy: ExitPolicyKind,
+        visibility: VisibilityKind,
+        min_players: Option<i64>,
+        max_players: Option<i64>,
+        auto_start: bool,
+        practice: bool,
+    ) -> Result<Tournament, AppError>;
+
+    async fn tournament_get(&mut self, tournament_id: i64) -> Result<Option<Tournament>, AppError>;
+
+    // tournament_data
+    async fn tournament_data_add(
+        &mut self,
+        creator_user_id: i64,
+        tournament_id: i64,
+        title: String,
+        active: bool,
+    ) -> Result<TournamentData, AppError>;
+
+    async fn tournament_data_get_recent(
+        &mut self,
+        tournament_id: i64,
+    ) -> Result<Option<TournamentData>, AppError>;
+
+    async fn tournament_data_count_active(&mut self) -> Result<i64, AppError>;
+
+    async fn tournament_data_query(
+        &mut self,
+        props: request::TournamentDataViewProps,
+    ) -> Result<Vec<TournamentData>, AppError>;
+
+    // tournament_state
+    async fn tournament_state_add(
+        &mut self,
+        creator_user_id: i64,
+        tournament_id: i64,
+        state: TournamentStateKind,
+    ) -> Result<TournamentState, AppError>;
+
+    async fn tournament_state_get_recent(
+        &mut self,
+        tournament_id: i64,
+    ) -> Result<Option<TournamentState>, AppError>;
+
+    // tournament_year
+    async fn tournament_year_add(
+        &mut self,
+        creator_user_id: i64,
+        tournament_id: i64,
+        current_year: i64,
+        deadline: Option<i64>,
+    ) -> Result<TournamentYear, AppError>;
+
+    async fn tournament_year_get_recent(
+        &mut self,
+        tournament_id: i64,
+    ) -> Result<Option<TournamentYear>, AppError>;
+
+    async fn tournament_year_get_due(&mut self, now: i64) -> Result<Vec<TournamentYear>, AppError>;
+
+    async fn tournament_year_query(
+        &mut self,
+        props: request::TournamentYearViewProps,
+    ) -> Result<Vec<TournamentYear>, AppError>;
+
+    // tournament_pause
+    async fn tournament_pause_add(
+        &mut self,
+        creator_user_id: i64,
+        tournament_id: i64,
+        start_time: i64,
+        end_time: i64,
+    ) -> Result<TournamentPause, AppError>;
+
+    async fn tournament_pause_query(
+        &mut self,
+        props: request::TournamentPauseViewProps,
+    ) -> Result<Vec<TournamentPause>, AppError>;
+
+    // tournament_year_demand
+    async fn tournament_year_demand_add(
+        &mut self,
+        firm: Firm,
+        tournament_id: i64,
+        year: i64,
+        demand: i64,
+    ) -> Result<TournamentYearDemand, AppError>;
+
+    async fn tournament_year_demand_get_by_tournament(
+        &mut self,
+        tournament_id: i64,
+    ) -> Result<Vec<TournamentYearDemand>, AppError>;
+
+    async fn tournament_year_demand_query(
+        &mut self,
+        props: request::TournamentYearDemandViewProps,
+    ) -> Result<Vec<TournamentYearDemand>, AppError>;
+
+    // tournament_membership
+    async fn tournament_membership_add(
+        &mut self,
+        creator_user_id: i64,
+        tournament_id: i64,
+        active: bool,
+        observer: bool,
+        approval: ApprovalKind,
+        removed: bool,
+        banned: bool,
+        actor_user_id: i64,
+        reason: Option<String>,
+        tournament_team_id: Option<i64>,
+    ) -> Result<TournamentMembership, AppError>;
+
+    async fn tournament_membership_get_recent_by_tournament(
+        &mut self,
+        tournament_id: i64,
+    ) -> Result<Vec<TournamentMembership>, AppError>;
+
+    async fn tournament_membership_get_recent_by_tournament_user(
+        &mut self,
+        tournament_id: i64,
+        user_id: i64,
+    ) -> Result<Option<TournamentMembership>, AppError>;
+
+    async fn tournament_membership_query(
+        &mut self,
+        props: request::TournamentMembershipViewProps,
+    ) -> Result<Vec<TournamentMembership>, AppError>;
+
+    // tournament_submission
+    async fn tournament_submission_add(
+        &mut self,
+        creator_user_id: i64,
+        firm: Firm,
+        tournament_id: i64,
+        year: i64,
+        amount: i64,
+        autogenerated: bool,
+    ) -> Result<TournamentSubmission, AppError>;
+
+    async fn tournament_submission_get_recent_by_tournament(
+        &mut self,
+        tournament_id: i64,
+    ) -> Result<Vec<TournamentSubmission>, AppError>;
+
+    async fn tournament_submission_query(
+        &mut self,
+        props: request::TournamentSubmissionViewProps,
+    ) -> Result<Vec<TournamentSubmission>, AppError>;
+
+    // tournament_invite_code
+    async fn tournament_invite_code_add(
+        &mut self,
+        creator_user_id: i64,
+        tournament_id: i64,
+        invite_code: String,
+    ) -> Result<TournamentInviteCode, AppError>;
+
+    async fn tournament_invite_code_get_recent(
+        &mut self,
+        tournament_id: i64,
+    ) -> Result<Option<TournamentInviteCode>, AppError>;
+
+    // tournament_team
+    async fn tournament_team_add(
+        &mut self,
+        creator_user_id: i64,
+        tournament_id: i64,
+        name: String,
+    ) -> Result<TournamentTeam, AppError>;
+
+    async fn tournament_team_get(
+        &mut self,
+        tournament_team_id: i64,
+    ) -> Result<Option<TournamentTeam>, AppError>;
+
+    async fn tournament_team_query(
+        &mut self,
+        props: request::TournamentTeamViewProps,
+    ) -> Result<Vec<TournamentTeam>, AppError>;
+
+    // tournament_bot
+    async fn tournament_bot_add(
+        &mut self,
+        creator_user_id: i64,
+        tournament_id: i64,
+        strategy: BotStrategyKind,
+        quantity: Option<i64>,
+        name: String,
+    ) -> Result<TournamentBot, AppError>;
+
+    async fn tournament_bot_get(
+        &mut self,
+        tournament_bot_id: i64,
+    ) -> Result<Option<TournamentBot>, AppError>;
+
+    async fn tournament_bot_get_by_tournament(
+        &mut self,
+        tournament_id: i64,
+    ) -> Result<Vec<TournamentBot>, AppError>;
+
+    async fn tournament_bot_query(
+        &mut self,
+        props: request::TournamentBotViewProps,
+    ) -> Result<Vec<TournamentBot>, AppError>;
+
+    // tournament_pseudonym
+    // None if the user already goes by a pseudonym in the tournament
+    async fn tournament_pseudonym_add(
+        &mut self,
+        creator_user_id: i64,
+        tournament_id: i64,
+        user_id: i64,
+        pseudonym: String,
+    ) -> Result<Option<TournamentPseudonym>, AppError>;
+
+    async fn tournament_pseudonym_get_by_tournament_user(
+        &mut self,
+        tournament_id: i64,
+        user_id: i64,
+    ) -> Result<Option<TournamentPseudonym>, AppError>;
+
+    async fn tournament_pseudonym_get_by_tournament(
+        &mut self,
+        tournament_id: i64,
+    ) -> Result<Vec<TournamentPseudonym>, AppError>;
+
+    // tournament_role
+    async fn tournament_role_add(
+        &mut self,
+        creator_user_id: i64,
+        tournament_id: i64,
+        user_id: i64,
+        role: TournamentRoleKind,
+        active: bool,
+    ) -> Result<TournamentRole, AppError>;
+
+    async fn tournament_role_get_recent_by_tournament_user(
+        &mut self,
+        tournament_id: i64,
+        user_id: i64,
+    ) -> Result<Option<TournamentRole>, AppError>;
+
+    async fn tournament_role_query(
+        &mut self,
+        props: request::TournamentRoleViewProps,
+    ) -> Result<Vec<TournamentRole>, AppError>;
+
+    // tournament_final_result
+    async fn tournament_final_result_add(
+        &mut self,
+        creator_user_id: i64,
+        tournament_id: i64,
+        firm: Firm,
+        rank: i64,
+        balance: f64,
+    ) -> Result<TournamentFinalResult, AppError>;
+
+    async fn tournament_final_result_get_by_tournament(
+        &mut self,
+        tournament_id: i64,
+    ) -> Result<Vec<TournamentFinalResult>, AppError>;
+
+    // idempotency_key
+    // None if the key was already used inside the window
+    async fn idempotency_key_add(
+        &mut self,
+        creator_user_id: i64,
+        endpoint: String,
+        idempotency_key: String,
+        request_hash: String,
+        response: String,
+        min_creation_time: i64,
+    ) -> Result<Option<IdempotencyKey>, AppError>;
+
+    async fn idempotency_key_get_recent(
+        &mut self,
+        creator_user_id: i64,
+        endpoint: &str,
+        idempotency_key: &str,
+        min_creation_time: i64,
+    ) -> Result<Option<IdempotencyKey>, AppError>;
+
+    async fn idempotency_key_delete_expired(
+        &mut self,
+        min_creation_time: i64,
+    ) -> Result<u64, AppError>;
+}
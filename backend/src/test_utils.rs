@@ -0,0 +1,143 @@
+use super::auth_client::{AuthClient, AuthClientConfig};
+use super::db_types::{
+    ExitPolicyKind, LateJoinPolicyKind, Tournament, TournamentSubmission, TournamentYearDemand,
+    VisibilityKind,
+};
+use super::dev_auth::DevAuth;
+use super::Auth;
+use auth_service_api::client::AuthService;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use warp::Filter;
+
+// A minimal stand-in for the auth service that answers get_user_by_api_key_if_valid.
+// The api key "key-<n>" belongs to user n, everything else is rejected.
+pub struct StubAuthService {
+    pub addr: SocketAddr,
+    pub hits: Arc<AtomicUsize>,
+    // when set, every answer takes a few seconds
+    pub slow: Arc<AtomicBool>,
+}
+
+pub fn api_key(user_id: i64) -> String {
+    format!("key-{}", user_id)
+}
+
+pub async fn stub_auth_service() -> StubAuthService {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let slow = Arc::new(AtomicBool::new(false));
+
+    let filter = {
+        let hits = hits.clone();
+        let slow = slow.clone();
+        warp::post()
+            .and(warp::path::tail())
+            .and(warp::body::json())
+            .and_then(move |tail: warp::path::Tail, api_key: String| {
+                let hits = hits.clone();
+                let slow = slow.clone();
+                async move {
+                    if !tail.as_str().ends_with("get_user_by_api_key_if_valid") {
+                        return Err(warp::reject::not_found());
+                    }
+                    hits.fetch_add(1, Ordering::SeqCst);
+                    if slow.load(Ordering::SeqCst) {
+                        tokio::time::sleep(Duration::from_secs(5)).await;
+                    }
+                    let user_id = api_key
+                        .strip_prefix("key-")
+                        .and_then(|id| id.parse::<i64>().ok());
+                    let resp = match user_id {
+                        Some(user_id) => {
+                            serde_json::json!({"Ok": {"userId": user_id, "creationTime": 0}})
+                        }
+                        None => serde_json::json!({"Err": "API_KEY_NONEXISTENT"}),
+                    };
+                    Ok::<_, warp::Rejection>(warp::reply::json(&resp))
+                }
+            })
+    };
+
+    let (addr, server) = warp::serve(filter).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    StubAuthService { addr, hits, slow }
+}
+
+pub fn auth_client_config() -> AuthClientConfig {
+    AuthClientConfig {
+        timeout: Duration::from_millis(200),
+        max_retries: 2,
+        retry_backoff: Duration::from_millis(10),
+        failure_threshold: 2,
+        reset_timeout: Duration::from_millis(300),
+    }
+}
+
+pub async fn auth_client_for(addr: SocketAddr) -> AuthClient {
+    AuthClient::new(
+        AuthService::new(&format!("http://{}", addr)).await,
+        auth_client_config(),
+    )
+}
+
+// accepts the same keys as the stub auth service, without going over the network
+pub fn dev_auth() -> Auth {
+    Arc::new(DevAuth::new((1..=20).map(|id| (api_key(id), id)).collect()))
+}
+
+// Rows for testing the game math without a store. Tests change whatever fields they care about.
+
+// tournament 1: price is 100 - Q, costs are 10 and there are no incentives
+pub fn tournament() -> Tournament {
+    Tournament {
+        tournament_id: 1,
+        creation_time: 0,
+        creator_user_id: 1,
+        cost_per_unit: 10,
+        demand_xintercept: 100,
+        demand_yintercept: 100,
+        incentive_multiplier: 0,
+        incentive_start_year: 2,
+        max_years: 3,
+        year_duration_ms: None,
+        late_join_policy: LateJoinPolicyKind::Forbidden,
+        exit_policy: ExitPolicyKind::KeepBalance,
+        visibility: VisibilityKind::Public,
+        min_players: None,
+        max_players: None,
+        auto_start: false,
+        practice: false,
+    }
+}
+
+// what a player sold in tournament 1
+pub fn submission(user_id: i64, year: i64, amount: i64) -> TournamentSubmission {
+    TournamentSubmission {
+        tournament_submission_id: 0,
+        creation_time: 0,
+        creator_user_id: user_id,
+        tournament_team_id: None,
+        tournament_bot_id: None,
+        tournament_id: 1,
+        year,
+        amount,
+        autogenerated: false,
+    }
+}
+
+// the demand a player was handed in tournament 1
+pub fn demand(user_id: i64, year: i64, demand: i64) -> TournamentYearDemand {
+    TournamentYearDemand {
+        tournament_year_demand_id: 0,
+        creation_time: 0,
+        tournament_id: 1,
+        user_id: Some(user_id),
+        tournament_team_id: None,
+        tournament_bot_id: None,
+        year,
+        demand,
+    }
+}
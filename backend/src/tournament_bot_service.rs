@@ -0,0 +1,138 @@
+use super::db_types::*;
+use super::request;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for TournamentBot {
+    // select * from tournament_bot order only, otherwise it will fail
+    fn from(row: tokio_postgres::Row) -> TournamentBot {
+        TournamentBot {
+            tournament_bot_id: row.get("tournament_bot_id"),
+            creation_time: row.get("creation_time"),
+            creator_user_id: row.get("creator_user_id"),
+            tournament_id: row.get("tournament_id"),
+            strategy: BotStrategyKind::try_from(row.get::<_, i64>("strategy")).unwrap(),
+            quantity: row.get("quantity"),
+            name: row.get("name"),
+        }
+    }
+}
+
+#[tracing::instrument(level = "debug", skip(con))]
+pub async fn add(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    tournament_id: i64,
+    strategy: BotStrategyKind,
+    quantity: Option<i64>,
+    name: String,
+) -> Result<TournamentBot, tokio_postgres::Error> {
+    let row = con
+        .query_one(
+            "INSERT INTO
+             tournament_bot(
+                 creator_user_id,
+                 tournament_id,
+                 strategy,
+                 quantity,
+                 name
+             )
+             VALUES ($1, $2, $3, $4, $5)
+             RETURNING tournament_bot_id, creation_time
+            ",
+            &[
+                &creator_user_id,
+                &tournament_id,
+                &(strategy as i64),
+                &quantity,
+                &name,
+            ],
+        )
+        .await?;
+
+    // return tournament_bot
+    Ok(TournamentBot {
+        tournament_bot_id: row.get(0),
+        creation_time: row.get(1),
+        creator_user_id,
+        tournament_id,
+        strategy,
+        quantity,
+        name,
+    })
+}
+
+#[tracing::instrument(level = "debug", skip(con))]
+pub async fn get_by_tournament_bot_id(
+    con: &mut impl GenericClient,
+    tournament_bot_id: i64,
+) -> Result<Option<TournamentBot>, tokio_postgres::Error> {
+    let result = con
+        .query_opt(
+            "SELECT * FROM tournament_bot WHERE tournament_bot_id=$1",
+            &[&tournament_bot_id],
+        )
+        .await?
+        .map(|x| x.into());
+    Ok(result)
+}
+
+#[tracing::instrument(level = "debug", skip(con))]
+pub async fn get_by_tournament(
+    con: &mut impl GenericClient,
+    tournament_id: i64,
+) -> Result<Vec<TournamentBot>, tokio_postgres::Error> {
+    let sql = [
+        "SELECT tb.* FROM tournament_bot tb",
+        " WHERE 1 = 1",
+        " AND tb.tournament_id = $1",
+        " ORDER BY tb.tournament_bot_id",
+    ]
+    .join("\n");
+
+    let stmnt = con.prepare(&sql).await?;
+
+    let results = con
+        .query(&stmnt, &[&tournament_id])
+        .await?
+        .into_iter()
+        .map(|row| row.into())
+        .collect();
+
+    Ok(results)
+}
+
+#[tracing::instrument(level = "debug", skip(con, props))]
+pub async fn query(
+    con: &mut impl GenericClient,
+    props: request::TournamentBotViewProps,
+) -> Result<Vec<TournamentBot>, tokio_postgres::Error> {
+    let sql = [
+        "SELECT tb.* FROM tournament_bot tb",
+        " WHERE 1 = 1",
+        " AND ($1::bigint[] IS NULL OR tb.tournament_bot_id = ANY($1))",
+        " AND ($2::bigint   IS NULL OR tb.creation_time >= $2)",
+        " AND ($3::bigint   IS NULL OR tb.creation_time <= $3)",
+        " AND ($4::bigint[] IS NULL OR tb.tournament_id = ANY($4))",
+        " ORDER BY tb.tournament_bot_id",
+    ]
+    .join("\n");
+
+    let stmnt = con.prepare(&sql).await?;
+
+    let results = con
+        .query(
+            &stmnt,
+            &[
+                &props.tournament_bot_id,
+                &props.min_creation_time,
+                &props.max_creation_time,
+                &props.tournament_id,
+            ],
+        )
+        .await?
+        .into_iter()
+        .map(|row| row.into())
+        .collect();
+
+    Ok(results)
+}
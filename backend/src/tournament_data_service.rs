@@ -16,6 +16,7 @@ impl From<tokio_postgres::row::Row> for TournamentData {
     }
 }
 
+#[tracing::instrument(level = "debug", skip(con))]
 pub async fn add(
     con: &mut impl GenericClient,
     creator_user_id: i64,
@@ -35,12 +36,7 @@ pub async fn add(
        VALUES ($1, $2, $3, $4)
        RETURNING tournament_data_id, creation_time
       ",
-            &[
-                &creator_user_id,
-                &tournament_id,
-                &title,
-                &active,
-            ],
+            &[&creator_user_id, &tournament_id, &title, &active],
         )
         .await?;
 
@@ -55,6 +51,7 @@ pub async fn add(
     })
 }
 
+#[tracing::instrument(level = "debug", skip(con))]
 pub async fn get_recent_by_tournament_id(
     con: &mut impl GenericClient,
     tournament_id: i64,
@@ -77,6 +74,21 @@ pub async fn get_recent_by_tournament_id(
     Ok(results)
 }
 
+#[tracing::instrument(level = "debug", skip(con))]
+pub async fn count_active(con: &mut impl GenericClient) -> Result<i64, tokio_postgres::Error> {
+    let row = con
+        .query_one(
+            "SELECT COUNT(*) FROM recent_tournament_data td
+             INNER JOIN tournament t ON t.tournament_id = td.tournament_id
+             WHERE td.active AND NOT t.practice",
+            &[],
+        )
+        .await?;
+
+    Ok(row.get(0))
+}
+
+#[tracing::instrument(level = "debug", skip(con, props))]
 pub async fn query(
     con: &mut impl GenericClient,
     props: request::TournamentDataViewProps,
@@ -87,6 +99,7 @@ pub async fn query(
         } else {
             "SELECT td.* FROM tournament_data td"
         },
+        " INNER JOIN tournament t ON t.tournament_id = td.tournament_id",
         " WHERE 1 = 1",
         " AND ($1::bigint[]  IS NULL OR td.tournament_data_id = ANY($1))",
         " AND ($2::bigint    IS NULL OR td.creation_time >= $2)",
@@ -94,6 +107,7 @@ pub async fn query(
         " AND ($4::bigint[]  IS NULL OR td.creator_user_id = ANY($4))",
         " AND ($5::bigint[]  IS NULL OR td.tournament_id = ANY($5))",
         " AND ($6::bool      IS NULL OR td.active = $6)",
+        " AND ($7::bool      IS NULL OR t.practice = $7)",
         " ORDER BY td.tournament_data_id",
     ]
     .join("\n");
@@ -110,6 +124,7 @@ pub async fn query(
                 &props.creator_user_id,
                 &props.tournament_id,
                 &props.active,
+                &props.practice,
             ],
         )
         .await?
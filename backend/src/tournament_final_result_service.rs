@@ -0,0 +1,97 @@
+use super::db_types::*;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for TournamentFinalResult {
+    // select * from tournament_final_result order only, otherwise it will fail
+    fn from(row: tokio_postgres::Row) -> TournamentFinalResult {
+        TournamentFinalResult {
+            tournament_final_result_id: row.get("tournament_final_result_id"),
+            creation_time: row.get("creation_time"),
+            creator_user_id: row.get("creator_user_id"),
+            tournament_id: row.get("tournament_id"),
+            user_id: row.get("user_id"),
+            tournament_team_id: row.get("tournament_team_id"),
+            tournament_bot_id: row.get("tournament_bot_id"),
+            rank: row.get("rank"),
+            balance: row.get("balance"),
+        }
+    }
+}
+
+#[tracing::instrument(level = "debug", skip(con))]
+pub async fn add(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    tournament_id: i64,
+    firm: Firm,
+    rank: i64,
+    balance: f64,
+) -> Result<TournamentFinalResult, tokio_postgres::Error> {
+    let user_id = firm.user_id();
+    let tournament_team_id = firm.tournament_team_id();
+    let tournament_bot_id = firm.tournament_bot_id();
+    let row = con
+        .query_one(
+            "INSERT INTO
+             tournament_final_result(
+                 creator_user_id,
+                 tournament_id,
+                 user_id,
+                 tournament_team_id,
+                 tournament_bot_id,
+                 rank,
+                 balance
+             )
+             VALUES ($1, $2, $3, $4, $5, $6, $7)
+             RETURNING tournament_final_result_id, creation_time
+            ",
+            &[
+                &creator_user_id,
+                &tournament_id,
+                &user_id,
+                &tournament_team_id,
+                &tournament_bot_id,
+                &rank,
+                &balance,
+            ],
+        )
+        .await?;
+
+    // return tournament_final_result
+    Ok(TournamentFinalResult {
+        tournament_final_result_id: row.get(0),
+        creation_time: row.get(1),
+        creator_user_id,
+        tournament_id,
+        user_id,
+        tournament_team_id,
+        tournament_bot_id,
+        rank,
+        balance,
+    })
+}
+
+#[tracing::instrument(level = "debug", skip(con))]
+pub async fn get_by_tournament_id(
+    con: &mut impl GenericClient,
+    tournament_id: i64,
+) -> Result<Vec<TournamentFinalResult>, tokio_postgres::Error> {
+    let sql = [
+        "SELECT tfr.* FROM tournament_final_result tfr",
+        " WHERE 1 = 1",
+        " AND tfr.tournament_id = $1",
+        " ORDER BY tfr.rank, tfr.user_id NULLS FIRST, tfr.tournament_team_id NULLS FIRST, tfr.tournament_bot_id",
+    ]
+    .join("\n");
+
+    let stmnt = con.prepare(&sql).await?;
+
+    let results = con
+        .query(&stmnt, &[&tournament_id])
+        .await?
+        .into_iter()
+        .map(|row| row.into())
+        .collect();
+
+    Ok(results)
+}
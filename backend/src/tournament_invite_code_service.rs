@@ -0,0 +1,69 @@
+use super::db_types::*;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for TournamentInviteCode {
+    // select * from tournament_invite_code order only, otherwise it will fail
+    fn from(row: tokio_postgres::Row) -> TournamentInviteCode {
+        TournamentInviteCode {
+            tournament_invite_code_id: row.get("tournament_invite_code_id"),
+            creation_time: row.get("creation_time"),
+            creator_user_id: row.get("creator_user_id"),
+            tournament_id: row.get("tournament_id"),
+            invite_code: row.get("invite_code"),
+        }
+    }
+}
+
+#[tracing::instrument(level = "debug", skip(con, invite_code))]
+pub async fn add(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    tournament_id: i64,
+    invite_code: String,
+) -> Result<TournamentInviteCode, tokio_postgres::Error> {
+    let row = con
+        .query_one(
+            "INSERT INTO
+             tournament_invite_code(
+                 creator_user_id,
+                 tournament_id,
+                 invite_code
+             )
+             VALUES ($1, $2, $3)
+             RETURNING tournament_invite_code_id, creation_time
+            ",
+            &[&creator_user_id, &tournament_id, &invite_code],
+        )
+        .await?;
+
+    // return tournament_invite_code
+    Ok(TournamentInviteCode {
+        tournament_invite_code_id: row.get(0),
+        creation_time: row.get(1),
+        creator_user_id,
+        tournament_id,
+        invite_code,
+    })
+}
+
+#[tracing::instrument(level = "debug", skip(con))]
+pub async fn get_recent_by_tournament_id(
+    con: &mut impl GenericClient,
+    tournament_id: i64,
+) -> Result<Option<TournamentInviteCode>, tokio_postgres::Error> {
+    let sql = [
+        "SELECT tic.* FROM recent_tournament_invite_code tic",
+        "WHERE 1 = 1",
+        "AND tic.tournament_id = $1",
+    ]
+    .join("\n");
+
+    let stmnt = con.prepare(&sql).await?;
+
+    let results = con
+        .query_opt(&stmnt, &[&tournament_id])
+        .await?
+        .map(|row| row.into());
+
+    Ok(results)
+}
@@ -1,5 +1,5 @@
 use super::db_types::*;
-use std::convert::From;
+use std::convert::{From, TryFrom};
 use tokio_postgres::GenericClient;
 
 impl From<tokio_postgres::row::Row> for TournamentMembership {
@@ -11,16 +11,32 @@ impl From<tokio_postgres::row::Row> for TournamentMembership {
             creator_user_id: row.get("creator_user_id"),
             tournament_id: row.get("tournament_id"),
             active: row.get("active"),
+            observer: row.get("observer"),
+            approval: ApprovalKind::try_from(row.get::<_, i64>("approval")).unwrap(),
+            removed: row.get("removed"),
+            banned: row.get("banned"),
+            actor_user_id: row.get("actor_user_id"),
+            reason: row.get("reason"),
+            tournament_team_id: row.get("tournament_team_id"),
         }
     }
 }
 
 // TODO we need to figure out a way to make scheduled and unscheduled articles work better
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(level = "debug", skip(con, reason))]
 pub async fn add(
     con: &mut impl GenericClient,
     creator_user_id: i64,
     tournament_id: i64,
     active: bool,
+    observer: bool,
+    approval: ApprovalKind,
+    removed: bool,
+    banned: bool,
+    actor_user_id: i64,
+    reason: Option<String>,
+    tournament_team_id: Option<i64>,
 ) -> Result<TournamentMembership, tokio_postgres::Error> {
     let row = con
         .query_one(
@@ -28,15 +44,29 @@ pub async fn add(
              tournament_membership(
                  creator_user_id,
                  tournament_id,
-                 active
+                 active,
+                 observer,
+                 approval,
+                 removed,
+                 banned,
+                 actor_user_id,
+                 reason,
+                 tournament_team_id
              )
-             VALUES ($1, $2, $3)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
              RETURNING tournament_membership_id, creation_time
             ",
             &[
                 &creator_user_id,
                 &tournament_id,
-                &active
+                &active,
+                &observer,
+                &(approval as i64),
+                &removed,
+                &banned,
+                &actor_user_id,
+                &reason,
+                &tournament_team_id,
             ],
         )
         .await?;
@@ -47,9 +77,17 @@ pub async fn add(
         creator_user_id,
         tournament_id,
         active,
+        observer,
+        approval,
+        removed,
+        banned,
+        actor_user_id,
+        reason,
+        tournament_team_id,
     })
 }
 
+#[tracing::instrument(level = "debug", skip(con))]
 pub async fn get_recent_by_tournament(
     con: &mut impl GenericClient,
     tournament_id: i64,
@@ -74,6 +112,7 @@ pub async fn get_recent_by_tournament(
     Ok(results)
 }
 
+#[tracing::instrument(level = "debug", skip(con))]
 pub async fn get_recent_by_tournament_user(
     con: &mut impl GenericClient,
     tournament_id: i64,
@@ -99,6 +138,7 @@ pub async fn get_recent_by_tournament_user(
 }
 
 
+#[tracing::instrument(level = "debug", skip(con, props))]
 pub async fn query(
     con: &mut impl GenericClient,
     props: super::request::TournamentMembershipViewProps,
@@ -116,6 +156,7 @@ pub async fn query(
         " AND ($4::bigint[] IS NULL OR ts.creator_user_id = ANY($4))",
         " AND ($5::bigint[] IS NULL OR ts.tournament_id = ANY($5))",
         " AND ($6::bool     IS NULL OR ts.active = $6)",
+        " AND ($7::bigint   IS NULL OR ts.approval = $7)",
         " ORDER BY ts.tournament_membership_id",
     ]
     .join("\n");
@@ -132,6 +173,7 @@ pub async fn query(
                 &props.creator_user_id,
                 &props.tournament_id,
                 &props.active,
+                &props.approval.map(|a| a as i64),
             ],
         )
         .await?
@@ -0,0 +1,88 @@
+use super::db_types::*;
+use super::request;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for TournamentPause {
+    // select * from tournament_pause order only, otherwise it will fail
+    fn from(row: tokio_postgres::Row) -> TournamentPause {
+        TournamentPause {
+            tournament_pause_id: row.get("tournament_pause_id"),
+            creation_time: row.get("creation_time"),
+            creator_user_id: row.get("creator_user_id"),
+            tournament_id: row.get("tournament_id"),
+            start_time: row.get("start_time"),
+            end_time: row.get("end_time"),
+        }
+    }
+}
+
+#[tracing::instrument(level = "debug", skip(con))]
+pub async fn add(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    tournament_id: i64,
+    start_time: i64,
+    end_time: i64,
+) -> Result<TournamentPause, tokio_postgres::Error> {
+    let row = con
+        .query_one(
+            "INSERT INTO
+             tournament_pause(
+                 creator_user_id,
+                 tournament_id,
+                 start_time,
+                 end_time
+             )
+             VALUES ($1, $2, $3, $4)
+             RETURNING tournament_pause_id, creation_time
+            ",
+            &[&creator_user_id, &tournament_id, &start_time, &end_time],
+        )
+        .await?;
+
+    // return tournament_pause
+    Ok(TournamentPause {
+        tournament_pause_id: row.get(0),
+        creation_time: row.get(1),
+        creator_user_id,
+        tournament_id,
+        start_time,
+        end_time,
+    })
+}
+
+#[tracing::instrument(level = "debug", skip(con, props))]
+pub async fn query(
+    con: &mut impl GenericClient,
+    props: request::TournamentPauseViewProps,
+) -> Result<Vec<TournamentPause>, tokio_postgres::Error> {
+    let sql = [
+        "SELECT tp.* FROM tournament_pause tp",
+        " WHERE 1 = 1",
+        " AND ($1::bigint[]  IS NULL OR tp.tournament_pause_id = ANY($1))",
+        " AND ($2::bigint    IS NULL OR tp.creation_time >= $2)",
+        " AND ($3::bigint    IS NULL OR tp.creation_time <= $3)",
+        " AND ($4::bigint[]  IS NULL OR tp.tournament_id = ANY($4))",
+        " ORDER BY tp.tournament_pause_id",
+    ]
+    .join("\n");
+
+    let stmnt = con.prepare(&sql).await?;
+
+    let results = con
+        .query(
+            &stmnt,
+            &[
+                &props.tournament_pause_id,
+                &props.min_creation_time,
+                &props.max_creation_time,
+                &props.tournament_id,
+            ],
+        )
+        .await?
+        .into_iter()
+        .map(|row| row.into())
+        .collect();
+
+    Ok(results)
+}
@@ -0,0 +1,102 @@
+use super::db_types::*;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for TournamentPseudonym {
+    // select * from tournament_pseudonym order only, otherwise it will fail
+    fn from(row: tokio_postgres::Row) -> TournamentPseudonym {
+        TournamentPseudonym {
+            tournament_pseudonym_id: row.get("tournament_pseudonym_id"),
+            creation_time: row.get("creation_time"),
+            creator_user_id: row.get("creator_user_id"),
+            tournament_id: row.get("tournament_id"),
+            user_id: row.get("user_id"),
+            pseudonym: row.get("pseudonym"),
+        }
+    }
+}
+
+#[tracing::instrument(level = "debug", skip(con))]
+pub async fn add(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    tournament_id: i64,
+    user_id: i64,
+    pseudonym: String,
+) -> Result<Option<TournamentPseudonym>, tokio_postgres::Error> {
+    // waits for anyone else giving the same user a pseudonym, and backs off if they did
+    let row = con
+        .query_opt(
+            "INSERT INTO
+             tournament_pseudonym(
+                 creator_user_id,
+                 tournament_id,
+                 user_id,
+                 pseudonym
+             )
+             VALUES ($1, $2, $3, $4)
+             ON CONFLICT (tournament_id, user_id) DO NOTHING
+             RETURNING tournament_pseudonym_id, creation_time
+            ",
+            &[&creator_user_id, &tournament_id, &user_id, &pseudonym],
+        )
+        .await?;
+
+    // return tournament_pseudonym
+    Ok(row.map(|row| TournamentPseudonym {
+        tournament_pseudonym_id: row.get(0),
+        creation_time: row.get(1),
+        creator_user_id,
+        tournament_id,
+        user_id,
+        pseudonym,
+    }))
+}
+
+#[tracing::instrument(level = "debug", skip(con))]
+pub async fn get_by_tournament_user(
+    con: &mut impl GenericClient,
+    tournament_id: i64,
+    user_id: i64,
+) -> Result<Option<TournamentPseudonym>, tokio_postgres::Error> {
+    let sql = [
+        "SELECT tp.* FROM tournament_pseudonym tp",
+        "WHERE 1 = 1",
+        "AND tp.tournament_id = $1",
+        "AND tp.user_id = $2",
+    ]
+    .join("\n");
+
+    let stmnt = con.prepare(&sql).await?;
+
+    let results = con
+        .query_opt(&stmnt, &[&tournament_id, &user_id])
+        .await?
+        .map(|row| row.into());
+
+    Ok(results)
+}
+
+#[tracing::instrument(level = "debug", skip(con))]
+pub async fn get_by_tournament(
+    con: &mut impl GenericClient,
+    tournament_id: i64,
+) -> Result<Vec<TournamentPseudonym>, tokio_postgres::Error> {
+    let sql = [
+        "SELECT tp.* FROM tournament_pseudonym tp",
+        "WHERE 1 = 1",
+        "AND tp.tournament_id = $1",
+        "ORDER BY tp.tournament_pseudonym_id",
+    ]
+    .join("\n");
+
+    let stmnt = con.prepare(&sql).await?;
+
+    let results = con
+        .query(&stmnt, &[&tournament_id])
+        .await?
+        .into_iter()
+        .map(|row| row.into())
+        .collect();
+
+    Ok(results)
+}
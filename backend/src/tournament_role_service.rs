@@ -0,0 +1,131 @@
+use super::db_types::*;
+use super::request;
+use std::convert::TryFrom;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for TournamentRole {
+    // select * from tournament_role order only, otherwise it will fail
+    fn from(row: tokio_postgres::Row) -> TournamentRole {
+        TournamentRole {
+            tournament_role_id: row.get("tournament_role_id"),
+            creation_time: row.get("creation_time"),
+            creator_user_id: row.get("creator_user_id"),
+            tournament_id: row.get("tournament_id"),
+            user_id: row.get("user_id"),
+            role: TournamentRoleKind::try_from(row.get::<_, i64>("role")).unwrap(),
+            active: row.get("active"),
+        }
+    }
+}
+
+#[tracing::instrument(level = "debug", skip(con))]
+pub async fn add(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    tournament_id: i64,
+    user_id: i64,
+    role: TournamentRoleKind,
+    active: bool,
+) -> Result<TournamentRole, tokio_postgres::Error> {
+    let row = con
+        .query_one(
+            "INSERT INTO
+             tournament_role(
+                 creator_user_id,
+                 tournament_id,
+                 user_id,
+                 role,
+                 active
+             )
+             VALUES ($1, $2, $3, $4, $5)
+             RETURNING tournament_role_id, creation_time
+            ",
+            &[
+                &creator_user_id,
+                &tournament_id,
+                &user_id,
+                &(role as i64),
+                &active,
+            ],
+        )
+        .await?;
+
+    // return tournament_role
+    Ok(TournamentRole {
+        tournament_role_id: row.get(0),
+        creation_time: row.get(1),
+        creator_user_id,
+        tournament_id,
+        user_id,
+        role,
+        active,
+    })
+}
+
+#[tracing::instrument(level = "debug", skip(con))]
+pub async fn get_recent_by_tournament_user(
+    con: &mut impl GenericClient,
+    tournament_id: i64,
+    user_id: i64,
+) -> Result<Option<TournamentRole>, tokio_postgres::Error> {
+    let sql = [
+        "SELECT tr.* FROM recent_tournament_role tr",
+        "WHERE 1 = 1",
+        "AND tr.tournament_id = $1",
+        "AND tr.user_id = $2",
+    ]
+    .join("\n");
+
+    let stmnt = con.prepare(&sql).await?;
+
+    let results = con
+        .query_opt(&stmnt, &[&tournament_id, &user_id])
+        .await?
+        .map(|row| row.into());
+
+    Ok(results)
+}
+
+#[tracing::instrument(level = "debug", skip(con, props))]
+pub async fn query(
+    con: &mut impl GenericClient,
+    props: request::TournamentRoleViewProps,
+) -> Result<Vec<TournamentRole>, tokio_postgres::Error> {
+    let sql = [
+        if props.only_recent {
+            "SELECT tr.* FROM recent_tournament_role tr"
+        } else {
+            "SELECT tr.* FROM tournament_role tr"
+        },
+        "WHERE 1 = 1",
+        "AND ($1::bigint[]  IS NULL OR tr.tournament_role_id = ANY($1))",
+        "AND ($2::bigint    IS NULL OR tr.creation_time >= $2)",
+        "AND ($3::bigint    IS NULL OR tr.creation_time <= $3)",
+        "AND ($4::bigint[]  IS NULL OR tr.tournament_id = ANY($4))",
+        "AND ($5::bigint[]  IS NULL OR tr.user_id = ANY($5))",
+        "AND ($6::bool      IS NULL OR tr.active = $6)",
+        "ORDER BY tr.tournament_role_id",
+    ]
+    .join("\n");
+
+    let stmnt = con.prepare(&sql).await?;
+
+    let results = con
+        .query(
+            &stmnt,
+            &[
+                &props.tournament_role_id,
+                &props.min_creation_time,
+                &props.max_creation_time,
+                &props.tournament_id,
+                &props.user_id,
+                &props.active,
+            ],
+        )
+        .await?
+        .into_iter()
+        .map(|row| row.into())
+        .collect();
+
+    Ok(results)
+}
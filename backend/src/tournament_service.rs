@@ -1,4 +1,5 @@
 use super::db_types::*;
+use std::convert::TryFrom;
 use tokio_postgres::GenericClient;
 
 impl From<tokio_postgres::row::Row> for Tournament {
@@ -14,10 +15,20 @@ impl From<tokio_postgres::row::Row> for Tournament {
             incentive_multiplier: row.get("incentive_multiplier"),
             incentive_start_year: row.get("incentive_start_year"),
             max_years: row.get("max_years"),
+            year_duration_ms: row.get("year_duration_ms"),
+            late_join_policy: LateJoinPolicyKind::try_from(row.get::<_, i64>("late_join_policy"))
+                .unwrap(),
+            exit_policy: ExitPolicyKind::try_from(row.get::<_, i64>("exit_policy")).unwrap(),
+            visibility: VisibilityKind::try_from(row.get::<_, i64>("visibility")).unwrap(),
+            min_players: row.get("min_players"),
+            max_players: row.get("max_players"),
+            auto_start: row.get("auto_start"),
+            practice: row.get("practice"),
         }
     }
 }
 
+#[tracing::instrument(level = "debug", skip(con))]
 pub async fn add(
     con: &mut impl GenericClient,
     creator_user_id: i64,
@@ -27,6 +38,14 @@ pub async fn add(
     incentive_multiplier: i64,
     incentive_start_year: i64,
     max_years: i64,
+    year_duration_ms: Option<i64>,
+    late_join_policy: LateJoinPolicyKind,
+    exit_policy: ExitPolicyKind,
+    visibility: VisibilityKind,
+    min_players: Option<i64>,
+    max_players: Option<i64>,
+    auto_start: bool,
+    practice: bool,
 ) -> Result<Tournament, tokio_postgres::Error> {
     let row = con
         .query_one(
@@ -38,9 +57,17 @@ pub async fn add(
                demand_yintercept,
                incentive_multiplier,
                incentive_start_year,
-               max_years
+               max_years,
+               year_duration_ms,
+               late_join_policy,
+               exit_policy,
+               visibility,
+               min_players,
+               max_players,
+               auto_start,
+               practice
              )
-             VALUES($1, $2, $3, $4, $5, $6, $7)
+             VALUES($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
              RETURNING tournament_id, creation_time
             ",
             &[
@@ -51,6 +78,14 @@ pub async fn add(
                 &incentive_multiplier,
                 &incentive_start_year,
                 &max_years,
+                &year_duration_ms,
+                &(late_join_policy as i64),
+                &(exit_policy as i64),
+                &(visibility as i64),
+                &min_players,
+                &max_players,
+                &auto_start,
+                &practice,
             ],
         )
         .await?;
@@ -66,9 +101,18 @@ pub async fn add(
         incentive_multiplier,
         incentive_start_year,
         max_years,
+        year_duration_ms,
+        late_join_policy,
+        exit_policy,
+        visibility,
+        min_players,
+        max_players,
+        auto_start,
+        practice,
     })
 }
 
+#[tracing::instrument(level = "debug", skip(con))]
 pub async fn get_by_tournament_id(
     con: &mut impl GenericClient,
     tournament_id: i64,
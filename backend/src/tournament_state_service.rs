@@ -0,0 +1,71 @@
+use super::db_types::*;
+use std::convert::TryFrom;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for TournamentState {
+    // select * from tournament_state order only, otherwise it will fail
+    fn from(row: tokio_postgres::Row) -> TournamentState {
+        TournamentState {
+            tournament_state_id: row.get("tournament_state_id"),
+            creation_time: row.get("creation_time"),
+            creator_user_id: row.get("creator_user_id"),
+            tournament_id: row.get("tournament_id"),
+            state: TournamentStateKind::try_from(row.get::<_, i64>("state")).unwrap(),
+        }
+    }
+}
+
+#[tracing::instrument(level = "debug", skip(con))]
+pub async fn add(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    tournament_id: i64,
+    state: TournamentStateKind,
+) -> Result<TournamentState, tokio_postgres::Error> {
+    let row = con
+        .query_one(
+            "INSERT INTO
+             tournament_state(
+                 creator_user_id,
+                 tournament_id,
+                 state
+             )
+             VALUES ($1, $2, $3)
+             RETURNING tournament_state_id, creation_time
+            ",
+            &[&creator_user_id, &tournament_id, &(state as i64)],
+        )
+        .await?;
+
+    // return tournament_state
+    Ok(TournamentState {
+        tournament_state_id: row.get(0),
+        creation_time: row.get(1),
+        creator_user_id,
+        tournament_id,
+        state,
+    })
+}
+
+#[tracing::instrument(level = "debug", skip(con))]
+pub async fn get_recent_by_tournament_id(
+    con: &mut impl GenericClient,
+    tournament_id: i64,
+) -> Result<Option<TournamentState>, tokio_postgres::Error> {
+    let sql = [
+        "SELECT ts.* FROM recent_tournament_state ts",
+        " WHERE 1 = 1",
+        " AND ts.tournament_id = $1",
+        " ORDER BY ts.tournament_state_id",
+    ]
+    .join("\n");
+
+    let stmnt = con.prepare(&sql).await?;
+
+    let results = con
+        .query_opt(&stmnt, &[&tournament_id])
+        .await?
+        .map(|row| row.into());
+
+    Ok(results)
+}
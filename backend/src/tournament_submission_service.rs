@@ -9,6 +9,8 @@ impl From<tokio_postgres::row::Row> for TournamentSubmission {
             tournament_submission_id: row.get("tournament_submission_id"),
             creation_time: row.get("creation_time"),
             creator_user_id: row.get("creator_user_id"),
+            tournament_team_id: row.get("tournament_team_id"),
+            tournament_bot_id: row.get("tournament_bot_id"),
             tournament_id: row.get("tournament_id"),
             amount: row.get("amount"),
             year: row.get("year"),
@@ -18,29 +20,37 @@ impl From<tokio_postgres::row::Row> for TournamentSubmission {
 }
 
 // TODO we need to figure out a way to make scheduled and unscheduled articles work better
+#[tracing::instrument(level = "debug", skip(con))]
 pub async fn add(
     con: &mut impl GenericClient,
     creator_user_id: i64,
+    firm: Firm,
     tournament_id: i64,
     year: i64,
     amount: i64,
     autogenerated: bool,
 ) -> Result<TournamentSubmission, tokio_postgres::Error> {
+    let tournament_team_id = firm.tournament_team_id();
+    let tournament_bot_id = firm.tournament_bot_id();
     let row = con
         .query_one(
             "INSERT INTO
              tournament_submission(
                  creator_user_id,
+                 tournament_team_id,
+                 tournament_bot_id,
                  tournament_id,
                  year,
                  amount,
                  autogenerated
              )
-             VALUES ($1, $2, $3, $4, $5)
+             VALUES ($1, $2, $3, $4, $5, $6, $7)
              RETURNING tournament_submission_id, creation_time
             ",
             &[
                 &creator_user_id,
+                &tournament_team_id,
+                &tournament_bot_id,
                 &tournament_id,
                 &year,
                 &amount,
@@ -53,6 +63,8 @@ pub async fn add(
         tournament_submission_id: row.get(0),
         creation_time: row.get(1),
         creator_user_id,
+        tournament_team_id,
+        tournament_bot_id,
         tournament_id,
         year,
         amount,
@@ -60,6 +72,7 @@ pub async fn add(
     })
 }
 
+#[tracing::instrument(level = "debug", skip(con))]
 pub async fn get_recent_by_tournament(
     con: &mut impl GenericClient,
     tournament_id: i64,
@@ -84,18 +97,22 @@ pub async fn get_recent_by_tournament(
     Ok(results)
 }
 
+#[tracing::instrument(level = "debug", skip(con, props))]
 pub async fn query(
     con: &mut impl GenericClient,
     props: super::request::TournamentSubmissionViewProps,
 ) -> Result<Vec<TournamentSubmission>, tokio_postgres::Error> {
     let sql = [
         "SELECT ts.* FROM tournament_submission ts",
+        " INNER JOIN tournament t ON t.tournament_id = ts.tournament_id",
         " WHERE 1 = 1",
         " AND ($1::bigint[] IS NULL OR ts.tournament_submission_id = ANY($1))",
         " AND ($2::bigint   IS NULL OR ts.creation_time >= $2)",
         " AND ($3::bigint   IS NULL OR ts.creation_time <= $3)",
         " AND ($4::bigint[] IS NULL OR ts.creator_user_id = ANY($4))",
         " AND ($5::bigint[] IS NULL OR ts.tournament_id = ANY($5))",
+        " AND ($6::bigint[] IS NULL OR ts.tournament_team_id = ANY($6))",
+        " AND ($7::bool      IS NULL OR t.practice = $7)",
         " ORDER BY ts.tournament_submission_id",
     ]
     .join("\n");
@@ -111,6 +128,8 @@ pub async fn query(
                 &props.max_creation_time,
                 &props.creator_user_id,
                 &props.tournament_id,
+                &props.tournament_team_id,
+                &props.practice,
             ],
         )
         .await?
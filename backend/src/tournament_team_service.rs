@@ -0,0 +1,99 @@
+use super::db_types::*;
+use super::request;
+use tokio_postgres::GenericClient;
+
+impl From<tokio_postgres::row::Row> for TournamentTeam {
+    // select * from tournament_team order only, otherwise it will fail
+    fn from(row: tokio_postgres::Row) -> TournamentTeam {
+        TournamentTeam {
+            tournament_team_id: row.get("tournament_team_id"),
+            creation_time: row.get("creation_time"),
+            creator_user_id: row.get("creator_user_id"),
+            tournament_id: row.get("tournament_id"),
+            name: row.get("name"),
+        }
+    }
+}
+
+#[tracing::instrument(level = "debug", skip(con))]
+pub async fn add(
+    con: &mut impl GenericClient,
+    creator_user_id: i64,
+    tournament_id: i64,
+    name: String,
+) -> Result<TournamentTeam, tokio_postgres::Error> {
+    let row = con
+        .query_one(
+            "INSERT INTO
+             tournament_team(
+                 creator_user_id,
+                 tournament_id,
+                 name
+             )
+             VALUES ($1, $2, $3)
+             RETURNING tournament_team_id, creation_time
+            ",
+            &[&creator_user_id, &tournament_id, &name],
+        )
+        .await?;
+
+    // return tournament_team
+    Ok(TournamentTeam {
+        tournament_team_id: row.get(0),
+        creation_time: row.get(1),
+        creator_user_id,
+        tournament_id,
+        name,
+    })
+}
+
+#[tracing::instrument(level = "debug", skip(con))]
+pub async fn get_by_tournament_team_id(
+    con: &mut impl GenericClient,
+    tournament_team_id: i64,
+) -> Result<Option<TournamentTeam>, tokio_postgres::Error> {
+    let result = con
+        .query_opt(
+            "SELECT * FROM tournament_team WHERE tournament_team_id=$1",
+            &[&tournament_team_id],
+        )
+        .await?
+        .map(|x| x.into());
+    Ok(result)
+}
+
+#[tracing::instrument(level = "debug", skip(con, props))]
+pub async fn query(
+    con: &mut impl GenericClient,
+    props: request::TournamentTeamViewProps,
+) -> Result<Vec<TournamentTeam>, tokio_postgres::Error> {
+    let sql = [
+        "SELECT tt.* FROM tournament_team tt",
+        " WHERE 1 = 1",
+        " AND ($1::bigint[] IS NULL OR tt.tournament_team_id = ANY($1))",
+        " AND ($2::bigint   IS NULL OR tt.creation_time >= $2)",
+        " AND ($3::bigint   IS NULL OR tt.creation_time <= $3)",
+        " AND ($4::bigint[] IS NULL OR tt.tournament_id = ANY($4))",
+        " ORDER BY tt.tournament_team_id",
+    ]
+    .join("\n");
+
+    let stmnt = con.prepare(&sql).await?;
+
+    let results = con
+        .query(
+            &stmnt,
+            &[
+                &props.tournament_team_id,
+                &props.min_creation_time,
+                &props.max_creation_time,
+                &props.tournament_id,
+            ],
+        )
+        .await?
+        .into_iter()
+        .map(|row| row.into())
+        .collect();
+
+    Ok(results)
+}
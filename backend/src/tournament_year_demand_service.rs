@@ -10,32 +10,47 @@ impl From<tokio_postgres::row::Row> for TournamentYearDemand {
             creation_time: row.get("creation_time"),
             tournament_id: row.get("tournament_id"),
             user_id: row.get("user_id"),
+            tournament_team_id: row.get("tournament_team_id"),
+            tournament_bot_id: row.get("tournament_bot_id"),
             year: row.get("year"),
             demand: row.get("demand"),
         }
     }
 }
 
+#[tracing::instrument(level = "debug", skip(con))]
 pub async fn add(
     con: &mut impl GenericClient,
-    user_id: i64,
+    firm: Firm,
     tournament_id: i64,
     year: i64,
     demand: i64,
 ) -> Result<TournamentYearDemand, tokio_postgres::Error> {
+    let user_id = firm.user_id();
+    let tournament_team_id = firm.tournament_team_id();
+    let tournament_bot_id = firm.tournament_bot_id();
     let row = con
         .query_one(
             "INSERT INTO
              tournament_year_demand(
                  user_id,
+                 tournament_team_id,
+                 tournament_bot_id,
                  tournament_id,
                  year,
                  demand
              )
-             VALUES ($1, $2, $3, $4)
+             VALUES ($1, $2, $3, $4, $5, $6)
              RETURNING tournament_year_demand_id, creation_time
             ",
-            &[&user_id, &tournament_id, &year, &demand],
+            &[
+                &user_id,
+                &tournament_team_id,
+                &tournament_bot_id,
+                &tournament_id,
+                &year,
+                &demand,
+            ],
         )
         .await?;
 
@@ -44,18 +59,21 @@ pub async fn add(
         tournament_year_demand_id: row.get(0),
         creation_time: row.get(1),
         user_id,
+        tournament_team_id,
+        tournament_bot_id,
         tournament_id,
         year,
         demand,
     })
 }
 
-pub async fn get_recent_by_tournament_id(
+#[tracing::instrument(level = "debug", skip(con))]
+pub async fn get_by_tournament_id(
     con: &mut impl GenericClient,
     tournament_id: i64,
 ) -> Result<Vec<TournamentYearDemand>, tokio_postgres::Error> {
     let sql = [
-        "SELECT td.* FROM recent_tournament_year_demand td",
+        "SELECT td.* FROM tournament_year_demand td",
         " WHERE 1 = 1",
         " AND td.tournament_id = $1",
         " ORDER BY td.tournament_year_demand_id",
@@ -74,18 +92,22 @@ pub async fn get_recent_by_tournament_id(
     Ok(results)
 }
 
+#[tracing::instrument(level = "debug", skip(con, props))]
 pub async fn query(
     con: &mut impl GenericClient,
     props: request::TournamentYearDemandViewProps,
 ) -> Result<Vec<TournamentYearDemand>, tokio_postgres::Error> {
     let sql = [
         "SELECT td.* FROM tournament_year_demand td",
+        " INNER JOIN tournament t ON t.tournament_id = td.tournament_id",
         " WHERE 1 = 1",
         " AND ($1::bigint[]  IS NULL OR td.tournament_year_demand_id = ANY($1))",
         " AND ($2::bigint    IS NULL OR td.creation_time >= $2)",
         " AND ($3::bigint    IS NULL OR td.creation_time <= $3)",
         " AND ($4::bigint[]  IS NULL OR td.user_id = ANY($4))",
         " AND ($5::bigint[]  IS NULL OR td.tournament_id = ANY($5))",
+        " AND ($6::bigint[]  IS NULL OR td.tournament_team_id = ANY($6))",
+        " AND ($7::bool      IS NULL OR t.practice = $7)",
         " ORDER BY td.tournament_year_demand_id",
     ]
     .join("\n");
@@ -101,6 +123,8 @@ pub async fn query(
                 &props.max_creation_time,
                 &props.user_id,
                 &props.tournament_id,
+                &props.tournament_team_id,
+                &props.practice,
             ],
         )
         .await?
@@ -11,15 +11,18 @@ impl From<tokio_postgres::row::Row> for TournamentYear {
             creator_user_id: row.get("creator_user_id"),
             tournament_id: row.get("tournament_id"),
             current_year: row.get("current_year"),
+            deadline: row.get("deadline"),
         }
     }
 }
 
+#[tracing::instrument(level = "debug", skip(con))]
 pub async fn add(
     con: &mut impl GenericClient,
     creator_user_id: i64,
     tournament_id: i64,
     current_year: i64,
+    deadline: Option<i64>,
 ) -> Result<TournamentYear, tokio_postgres::Error> {
     let row = con
         .query_one(
@@ -27,16 +30,13 @@ pub async fn add(
              tournament_year(
                  creator_user_id,
                  tournament_id,
-                 current_year
+                 current_year,
+                 deadline
              )
-             VALUES ($1, $2, $3)
+             VALUES ($1, $2, $3, $4)
              RETURNING tournament_year_id, creation_time
             ",
-            &[
-                &creator_user_id,
-                &tournament_id,
-                &current_year,
-            ],
+            &[&creator_user_id, &tournament_id, &current_year, &deadline],
         )
         .await?;
 
@@ -47,9 +47,11 @@ pub async fn add(
         creator_user_id,
         tournament_id,
         current_year,
+        deadline,
     })
 }
 
+#[tracing::instrument(level = "debug", skip(con))]
 pub async fn get_recent_by_tournament_id(
     con: &mut impl GenericClient,
     tournament_id: i64,
@@ -72,6 +74,38 @@ pub async fn get_recent_by_tournament_id(
     Ok(results)
 }
 
+// the most recent years of running tournaments whose deadline is at or before now
+#[tracing::instrument(level = "debug", skip(con))]
+pub async fn get_due(
+    con: &mut impl GenericClient,
+    now: i64,
+) -> Result<Vec<TournamentYear>, tokio_postgres::Error> {
+    let sql = [
+        "SELECT td.* FROM recent_tournament_year td",
+        "INNER JOIN recent_tournament_state ts ON ts.tournament_id = td.tournament_id",
+        "INNER JOIN tournament t ON t.tournament_id = td.tournament_id",
+        "WHERE 1 = 1",
+        "AND td.deadline <= $1",
+        "AND ts.state = $2",
+        // practice games only move on when the student submits
+        "AND NOT t.practice",
+        "ORDER BY td.deadline",
+    ]
+    .join("\n");
+
+    let stmnt = con.prepare(&sql).await?;
+
+    let results = con
+        .query(&stmnt, &[&now, &(TournamentStateKind::Running as i64)])
+        .await?
+        .into_iter()
+        .map(|row| row.into())
+        .collect();
+
+    Ok(results)
+}
+
+#[tracing::instrument(level = "debug", skip(con, props))]
 pub async fn query(
     con: &mut impl GenericClient,
     props: request::TournamentYearViewProps,
@@ -82,12 +116,14 @@ pub async fn query(
         } else {
             "SELECT td.* FROM tournament_year td"
         },
+        "INNER JOIN tournament t ON t.tournament_id = td.tournament_id",
         "WHERE 1 = 1",
         "AND ($1::bigint[]  IS NULL OR td.tournament_year_id = ANY($1))",
         "AND ($2::bigint    IS NULL OR td.creation_time >= $2)",
         "AND ($3::bigint    IS NULL OR td.creation_time <= $3)",
         "AND ($4::bigint[]  IS NULL OR td.creator_user_id = ANY($4))",
         "AND ($5::bigint[]  IS NULL OR td.tournament_id = ANY($5))",
+        "AND ($6::bool      IS NULL OR t.practice = $6)",
         "ORDER BY td.tournament_year_id",
     ]
     .join("\n");
@@ -103,6 +139,7 @@ pub async fn query(
                 &props.max_creation_time,
                 &props.creator_user_id,
                 &props.tournament_id,
+                &props.practice,
             ],
         )
         .await?
@@ -47,7 +47,24 @@ where
     M: serde::ser::Serialize,
     S: serde::ser::Serialize,
 {
-    println!("{}", serde_json::to_string(&e).unwrap());
+    // emitted as a tracing event so that it picks up the current request span
+    let msg = to_text(&e.msg);
+    let source = to_text(&e.source);
+    match e.severity {
+        SeverityKind::Info => tracing::info!(source = %source, "{}", msg),
+        SeverityKind::Warning => tracing::warn!(source = %source, "{}", msg),
+        SeverityKind::Error => tracing::error!(source = %source, "{}", msg),
+        SeverityKind::Fatal => tracing::error!(source = %source, fatal = true, "{}", msg),
+    }
+}
+
+// renders strings without the surrounding quotes, and everything else as json
+fn to_text<T: serde::ser::Serialize>(t: &T) -> String {
+    match serde_json::to_value(t) {
+        Ok(serde_json::Value::String(s)) => s,
+        Ok(v) => v.to_string(),
+        Err(_) => String::new(),
+    }
 }
 
 // creates a random string 16 characters long
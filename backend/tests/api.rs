@@ -0,0 +1,1368 @@
+mod common;
+
+use common::{api_key, config, unreachable_auth, TestEnv, POOL_SIZE};
+use production_minigame_service::scheduler;
+use serde_json::{json, Value};
+use std::time::Duration;
+use warp::http::StatusCode;
+
+fn new_tournament(user_id: i64) -> Value {
+    json!({
+        "apiKey": api_key(user_id),
+        "title": "test",
+        "costPerUnit": 10,
+        "demandXintercept": 100,
+        "demandYintercept": 100,
+        "incentiveMultiplier": 5,
+        "incentiveStartYear": 2,
+        "maxYears": 3,
+    })
+}
+
+async fn create_tournament(env: &TestEnv, user_id: i64) -> i64 {
+    let data = env
+        .ok("/public/tournament/new", new_tournament(user_id))
+        .await;
+    data["tournament"]["tournamentId"].as_i64().unwrap()
+}
+
+async fn join(env: &TestEnv, user_id: i64, tournament_id: i64) {
+    env.ok(
+        "/public/tournament_membership/new",
+        json!({"tournamentId": tournament_id, "active": true, "apiKey": api_key(user_id)}),
+    )
+    .await;
+}
+
+async fn set_state(env: &TestEnv, user_id: i64, tournament_id: i64, state: &str) -> Value {
+    env.ok(
+        "/public/tournament_state/new",
+        json!({"tournamentId": tournament_id, "state": state, "apiKey": api_key(user_id)}),
+    )
+    .await
+}
+
+async fn submit(env: &TestEnv, user_id: i64, tournament_id: i64, amount: i64) -> Value {
+    env.ok(
+        "/public/tournament_submission/new",
+        json!({"tournamentId": tournament_id, "amount": amount, "apiKey": api_key(user_id)}),
+    )
+    .await
+}
+
+async fn advance(env: &TestEnv, user_id: i64, tournament_id: i64) -> i64 {
+    let year = env
+        .ok(
+            "/public/tournament_year/new",
+            json!({"tournamentId": tournament_id, "apiKey": api_key(user_id)}),
+        )
+        .await;
+    year["currentYear"].as_i64().unwrap()
+}
+
+async fn finalize(env: &TestEnv, user_id: i64, tournament_id: i64) -> Vec<Value> {
+    let results = env
+        .ok(
+            "/public/tournament_final_result/new",
+            json!({"tournamentId": tournament_id, "apiKey": api_key(user_id)}),
+        )
+        .await;
+    results.as_array().unwrap().clone()
+}
+
+async fn archive(env: &TestEnv, user_id: i64, tournament_id: i64) {
+    env.ok(
+        "/public/tournament_data/new",
+        json!({"tournamentId": tournament_id, "title": "test", "active": false, "apiKey": api_key(user_id)}),
+    )
+    .await;
+}
+
+async fn view(env: &TestEnv, kind: &str, tournament_id: i64) -> Vec<Value> {
+    let mut props = json!({"tournamentId": [tournament_id], "apiKey": api_key(1)});
+    if !matches!(
+        kind,
+        "tournament_submission" | "tournament_year_demand" | "tournament_team" | "tournament_bot"
+    ) {
+        props["onlyRecent"] = json!(false);
+    }
+    let resp = env.ok(&format!("/public/{}/view", kind), props).await;
+    resp.as_array().unwrap().clone()
+}
+
+#[tokio::test]
+async fn full_game() {
+    let env = match TestEnv::new().await {
+        Some(env) => env,
+        None => return,
+    };
+
+    let tournament_id = create_tournament(&env, 1).await;
+    join(&env, 2, tournament_id).await;
+    join(&env, 3, tournament_id).await;
+    let state = set_state(&env, 1, tournament_id, "RUNNING").await;
+    assert_eq!(state["tournament"]["state"], "RUNNING");
+
+    for year in 0..3 {
+        // player 2 changes their mind, player 3 never submits
+        submit(&env, 2, tournament_id, 5).await;
+        let submission = submit(&env, 2, tournament_id, 20).await;
+        assert_eq!(submission["year"], year);
+        assert_eq!(advance(&env, 1, tournament_id).await, year + 1);
+    }
+
+    let years = view(&env, "tournament_year", tournament_id).await;
+    assert_eq!(years.len(), 4);
+
+    // player 3's submissions were filled in with zeros
+    let submissions = view(&env, "tournament_submission", tournament_id).await;
+    let autogenerated: Vec<_> = submissions
+        .iter()
+        .filter(|s| s["autogenerated"] == true)
+        .collect();
+    assert_eq!(autogenerated.len(), 3);
+    assert!(autogenerated
+        .iter()
+        .all(|s| s["creatorUserId"] == 3 && s["amount"] == 0));
+    assert_eq!(submissions.len(), 9);
+
+    // every player gets a demand when they join, and one for every year after
+    let demands = view(&env, "tournament_year_demand", tournament_id).await;
+    assert_eq!(demands.len(), 8);
+    for demand in &demands {
+        let year = demand["year"].as_i64().unwrap();
+        let incentive = demand["demand"].as_i64().unwrap();
+        if year < 2 {
+            assert_eq!(incentive, 0);
+        } else {
+            assert!(incentive.abs() <= 5);
+        }
+    }
+
+    let members = view(&env, "tournament_membership", tournament_id).await;
+    assert_eq!(members.len(), 2);
+
+    // player 2's last year is still open, finalizing closes it
+    submit(&env, 2, tournament_id, 20).await;
+    let results = finalize(&env, 1, tournament_id).await;
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0]["userId"], 2);
+    assert_eq!(results[0]["rank"], 1);
+    assert_eq!(results[1]["userId"], 3);
+    assert_eq!(results[1]["balance"], 0.0);
+    assert_eq!(results[0]["tournament"]["state"], "FINISHED");
+
+    // members see the same results, and so does everyone else since the tournament is public
+    let seen = env
+        .ok(
+            "/public/tournament_final_result/view",
+            json!({"tournamentId": tournament_id, "apiKey": api_key(3)}),
+        )
+        .await;
+    assert_eq!(seen.as_array().unwrap().len(), 2);
+    let seen = env
+        .ok(
+            "/public/tournament_final_result/view",
+            json!({"tournamentId": tournament_id, "apiKey": api_key(4)}),
+        )
+        .await;
+    assert_eq!(seen.as_array().unwrap().len(), 2);
+    assert_eq!(seen[0]["userId"], Value::Null);
+    env.err(
+        "/public/tournament_submission/new",
+        json!({"tournamentId": tournament_id, "amount": 1, "apiKey": api_key(2)}),
+        StatusCode::BAD_REQUEST,
+        "TOURNAMENT_FINISHED",
+    )
+    .await;
+
+    archive(&env, 1, tournament_id).await;
+    let data = env
+        .ok(
+            "/public/tournament_data/view",
+            json!({"tournamentId": [tournament_id], "onlyRecent": true, "apiKey": api_key(1)}),
+        )
+        .await;
+    assert_eq!(data[0]["active"], false);
+
+    env.teardown().await;
+}
+
+#[tokio::test]
+async fn tournament_errors() {
+    let env = match TestEnv::new().await {
+        Some(env) => env,
+        None => return,
+    };
+
+    let mut props = new_tournament(1);
+    props["maxYears"] = json!(1);
+    env.err(
+        "/public/tournament/new",
+        props,
+        StatusCode::BAD_REQUEST,
+        "TOURNAMENT_MAX_YEARS_INVALID",
+    )
+    .await;
+
+    let mut props = new_tournament(1);
+    props["incentiveStartYear"] = json!(1);
+    env.err(
+        "/public/tournament/new",
+        props,
+        StatusCode::BAD_REQUEST,
+        "TOURNAMENT_INCENTIVE_START_YEAR_INVALID",
+    )
+    .await;
+
+    let mut props = new_tournament(1);
+    props["apiKey"] = json!("bogus");
+    env.err(
+        "/public/tournament/new",
+        props,
+        StatusCode::BAD_REQUEST,
+        "UNAUTHORIZED",
+    )
+    .await;
+
+    // postgres refuses to store text containing a NUL
+    let mut props = new_tournament(1);
+    props["title"] = json!("a\u{0}b");
+    env.err(
+        "/public/tournament/new",
+        props,
+        StatusCode::BAD_REQUEST,
+        "INTERNAL_SERVER_ERROR",
+    )
+    .await;
+
+    let tournament_id = create_tournament(&env, 1).await;
+
+    // tournaments that aren't yours look like they don't exist
+    env.err(
+        "/public/tournament_data/new",
+        json!({"tournamentId": tournament_id, "title": "mine", "active": true, "apiKey": api_key(2)}),
+        StatusCode::BAD_REQUEST,
+        "TOURNAMENT_NONEXISTENT",
+    )
+    .await;
+    env.err(
+        "/public/tournament_year/new",
+        json!({"tournamentId": tournament_id, "apiKey": api_key(2)}),
+        StatusCode::BAD_REQUEST,
+        "TOURNAMENT_NONEXISTENT",
+    )
+    .await;
+    env.err(
+        "/public/tournament_membership/new",
+        json!({"tournamentId": tournament_id + 1000, "active": true, "apiKey": api_key(2)}),
+        StatusCode::BAD_REQUEST,
+        "TOURNAMENT_NONEXISTENT",
+    )
+    .await;
+
+    env.teardown().await;
+}
+
+#[tokio::test]
+async fn membership_and_submission_errors() {
+    let env = match TestEnv::new().await {
+        Some(env) => env,
+        None => return,
+    };
+
+    let tournament_id = create_tournament(&env, 1).await;
+
+    env.err(
+        "/public/tournament_membership/new",
+        json!({"tournamentId": tournament_id, "active": true, "apiKey": api_key(1)}),
+        StatusCode::BAD_REQUEST,
+        "TOURNAMENT_MEMBERSHIP_INVALID",
+    )
+    .await;
+
+    // only members may submit
+    env.err(
+        "/public/tournament_submission/new",
+        json!({"tournamentId": tournament_id, "amount": 1, "apiKey": api_key(2)}),
+        StatusCode::BAD_REQUEST,
+        "UNAUTHORIZED",
+    )
+    .await;
+
+    env.err(
+        "/public/tournament_membership/new",
+        json!({"tournamentId": tournament_id, "active": false, "apiKey": api_key(2)}),
+        StatusCode::BAD_REQUEST,
+        "TOURNAMENT_MEMBERSHIP_INVALID",
+    )
+    .await;
+
+    // joining twice is the same as joining once
+    join(&env, 2, tournament_id).await;
+    join(&env, 2, tournament_id).await;
+    assert_eq!(
+        view(&env, "tournament_membership", tournament_id)
+            .await
+            .len(),
+        1
+    );
+    assert_eq!(
+        view(&env, "tournament_year_demand", tournament_id)
+            .await
+            .len(),
+        1
+    );
+
+    set_state(&env, 1, tournament_id, "RUNNING").await;
+    advance(&env, 1, tournament_id).await;
+
+    env.err(
+        "/public/tournament_membership/new",
+        json!({"tournamentId": tournament_id, "active": true, "apiKey": api_key(3)}),
+        StatusCode::BAD_REQUEST,
+        "TOURNAMENT_STARTED",
+    )
+    .await;
+
+    advance(&env, 1, tournament_id).await;
+    advance(&env, 1, tournament_id).await;
+    env.err(
+        "/public/tournament_year/new",
+        json!({"tournamentId": tournament_id, "apiKey": api_key(1)}),
+        StatusCode::BAD_REQUEST,
+        "TOURNAMENT_MAX_YEARS_ACHIEVED",
+    )
+    .await;
+
+    archive(&env, 1, tournament_id).await;
+    env.err(
+        "/public/tournament_submission/new",
+        json!({"tournamentId": tournament_id, "amount": 1, "apiKey": api_key(2)}),
+        StatusCode::BAD_REQUEST,
+        "TOURNAMENT_ARCHIVED",
+    )
+    .await;
+    env.err(
+        "/public/tournament_year/new",
+        json!({"tournamentId": tournament_id, "apiKey": api_key(1)}),
+        StatusCode::BAD_REQUEST,
+        "TOURNAMENT_ARCHIVED",
+    )
+    .await;
+
+    env.teardown().await;
+}
+
+#[tokio::test]
+async fn lifecycle_errors() {
+    let env = match TestEnv::new().await {
+        Some(env) => env,
+        None => return,
+    };
+
+    let tournament_id = create_tournament(&env, 1).await;
+    join(&env, 2, tournament_id).await;
+
+    env.err(
+        "/public/tournament_submission/new",
+        json!({"tournamentId": tournament_id, "amount": 1, "apiKey": api_key(2)}),
+        StatusCode::BAD_REQUEST,
+        "TOURNAMENT_NOT_RUNNING",
+    )
+    .await;
+    env.err(
+        "/public/tournament_state/new",
+        json!({"tournamentId": tournament_id, "state": "FINISHED", "apiKey": api_key(1)}),
+        StatusCode::BAD_REQUEST,
+        "TOURNAMENT_STATE_TRANSITION_INVALID",
+    )
+    .await;
+    env.err(
+        "/public/tournament_state/new",
+        json!({"tournamentId": tournament_id, "state": "RUNNING", "apiKey": api_key(2)}),
+        StatusCode::BAD_REQUEST,
+        "TOURNAMENT_NONEXISTENT",
+    )
+    .await;
+
+    set_state(&env, 1, tournament_id, "RUNNING").await;
+    set_state(&env, 1, tournament_id, "PAUSED").await;
+    env.err(
+        "/public/tournament_year/new",
+        json!({"tournamentId": tournament_id, "apiKey": api_key(1)}),
+        StatusCode::BAD_REQUEST,
+        "TOURNAMENT_NOT_RUNNING",
+    )
+    .await;
+
+    finalize(&env, 1, tournament_id).await;
+    env.err(
+        "/public/tournament_submission/new",
+        json!({"tournamentId": tournament_id, "amount": 1, "apiKey": api_key(2)}),
+        StatusCode::BAD_REQUEST,
+        "TOURNAMENT_FINISHED",
+    )
+    .await;
+
+    set_state(&env, 1, tournament_id, "ARCHIVED").await;
+    env.err(
+        "/public/tournament_state/new",
+        json!({"tournamentId": tournament_id, "state": "RUNNING", "apiKey": api_key(1)}),
+        StatusCode::BAD_REQUEST,
+        "TOURNAMENT_ARCHIVED",
+    )
+    .await;
+
+    // archiving through the state hides the tournament too
+    let data = env
+        .ok(
+            "/public/tournament_data/view",
+            json!({"tournamentId": [tournament_id], "onlyRecent": true, "apiKey": api_key(1)}),
+        )
+        .await;
+    assert_eq!(data[0]["active"], false);
+
+    env.teardown().await;
+}
+
+#[tokio::test]
+async fn scheduled_years() {
+    let env = match TestEnv::new().await {
+        Some(env) => env,
+        None => return,
+    };
+
+    let mut props = new_tournament(1);
+    props["yearDurationMs"] = json!(60_000);
+    let tournament_id = env.ok("/public/tournament/new", props).await["tournament"]["tournamentId"]
+        .as_i64()
+        .unwrap();
+    // a tournament without deadlines, which is never advanced on its own
+    let manual_id = create_tournament(&env, 1).await;
+    for id in [tournament_id, manual_id] {
+        join(&env, 2, id).await;
+        set_state(&env, 1, id, "RUNNING").await;
+    }
+
+    let years = view(&env, "tournament_year", tournament_id).await;
+    let deadline = years.last().unwrap()["deadline"].as_i64().unwrap();
+    assert_eq!(scheduler::tick(&env.db(), deadline - 1).await.unwrap(), 0);
+    assert_eq!(scheduler::tick(&env.db(), deadline).await.unwrap(), 1);
+
+    set_state(&env, 1, tournament_id, "PAUSED").await;
+    assert_eq!(scheduler::tick(&env.db(), i64::MAX).await.unwrap(), 0);
+    set_state(&env, 1, tournament_id, "RUNNING").await;
+
+    let pauses = env
+        .ok(
+            "/public/tournament_pause/view",
+            json!({"tournamentId": [tournament_id], "apiKey": api_key(1)}),
+        )
+        .await;
+    assert_eq!(pauses.as_array().unwrap().len(), 1);
+
+    let years = view(&env, "tournament_year", tournament_id).await;
+    let year = years.last().unwrap();
+    assert_eq!(year["currentYear"], 1);
+    assert!(year["deadline"].as_i64().unwrap() > deadline);
+
+    let years = view(&env, "tournament_year", manual_id).await;
+    assert_eq!(years.len(), 1);
+    assert_eq!(years[0]["deadline"], Value::Null);
+
+    env.teardown().await;
+}
+
+#[tokio::test]
+async fn late_join_and_exit_policies() {
+    let env = match TestEnv::new().await {
+        Some(env) => env,
+        None => return,
+    };
+
+    let mut props = new_tournament(1);
+    props["lateJoinPolicy"] = json!("CATCH_UP");
+    props["exitPolicy"] = json!("FORFEIT");
+    let data = env.ok("/public/tournament/new", props).await;
+    assert_eq!(data["tournament"]["lateJoinPolicy"], "CATCH_UP");
+    assert_eq!(data["tournament"]["exitPolicy"], "FORFEIT");
+    let tournament_id = data["tournament"]["tournamentId"].as_i64().unwrap();
+
+    join(&env, 2, tournament_id).await;
+    join(&env, 3, tournament_id).await;
+    set_state(&env, 1, tournament_id, "RUNNING").await;
+    submit(&env, 2, tournament_id, 20).await;
+    submit(&env, 3, tournament_id, 20).await;
+    advance(&env, 1, tournament_id).await;
+
+    // user 4 catches up, user 3 leaves and forfeits
+    join(&env, 4, tournament_id).await;
+    let membership = env
+        .ok(
+            "/public/tournament_membership/new",
+            json!({"tournamentId": tournament_id, "active": false, "apiKey": api_key(3)}),
+        )
+        .await;
+    assert_eq!(membership["active"], false);
+    env.err(
+        "/public/tournament_submission/new",
+        json!({"tournamentId": tournament_id, "amount": 5, "apiKey": api_key(3)}),
+        StatusCode::BAD_REQUEST,
+        "UNAUTHORIZED",
+    )
+    .await;
+    submit(&env, 4, tournament_id, 10).await;
+
+    let results = finalize(&env, 1, tournament_id).await;
+    let mut users: Vec<i64> = results
+        .iter()
+        .map(|r| r["userId"].as_i64().unwrap())
+        .collect();
+    users.sort();
+    assert_eq!(users, vec![2, 4]);
+
+    env.teardown().await;
+}
+
+#[tokio::test]
+async fn idempotent_retries() {
+    let env = match TestEnv::new().await {
+        Some(env) => env,
+        None => return,
+    };
+
+    let tournament_id = create_tournament(&env, 1).await;
+    join(&env, 2, tournament_id).await;
+    set_state(&env, 1, tournament_id, "RUNNING").await;
+
+    let props = json!({"tournamentId": tournament_id, "apiKey": api_key(1), "idempotencyKey": "k"});
+    let first = env.ok("/public/tournament_year/new", props.clone()).await;
+    let second = env.ok("/public/tournament_year/new", props).await;
+    assert_eq!(first, second);
+    assert_eq!(view(&env, "tournament_year", tournament_id).await.len(), 2);
+
+    // the same key can't be used for a different request
+    let submission = |amount: i64| {
+        json!({"tournamentId": tournament_id, "amount": amount, "apiKey": api_key(2), "idempotencyKey": "k"})
+    };
+    env.ok("/public/tournament_submission/new", submission(10)).await;
+    env.err(
+        "/public/tournament_submission/new",
+        submission(20),
+        StatusCode::BAD_REQUEST,
+        "IDEMPOTENCY_KEY_REUSED",
+    )
+    .await;
+
+    // until it's been pruned
+    let pruned = scheduler::prune(&env.db(), &config(), i64::MAX).await.unwrap();
+    assert_eq!(pruned, 2);
+    assert_eq!(
+        env.ok("/public/tournament_submission/new", submission(20)).await["amount"],
+        20
+    );
+
+    env.teardown().await;
+}
+
+#[tokio::test]
+async fn tournament_roles() {
+    let env = match TestEnv::new().await {
+        Some(env) => env,
+        None => return,
+    };
+
+    let tournament_id = create_tournament(&env, 1).await;
+    join(&env, 2, tournament_id).await;
+
+    let grant = |user_id: i64, active: bool| {
+        json!({
+            "tournamentId": tournament_id,
+            "userId": user_id,
+            "role": "ORGANIZER",
+            "active": active,
+            "apiKey": api_key(1),
+        })
+    };
+    let role = env.ok("/public/tournament_role/new", grant(3, true)).await;
+    assert_eq!(role["role"], "ORGANIZER");
+
+    // the organizer runs the session
+    set_state(&env, 3, tournament_id, "RUNNING").await;
+    assert_eq!(advance(&env, 3, tournament_id).await, 1);
+
+    env.err(
+        "/public/tournament_role/new",
+        grant(2, true),
+        StatusCode::BAD_REQUEST,
+        "TOURNAMENT_ROLE_INVALID",
+    )
+    .await;
+
+    env.ok("/public/tournament_role/new", grant(3, false)).await;
+    env.err(
+        "/public/tournament_year/new",
+        json!({"tournamentId": tournament_id, "apiKey": api_key(3)}),
+        StatusCode::BAD_REQUEST,
+        "TOURNAMENT_NONEXISTENT",
+    )
+    .await;
+
+    // the creator is the first owner
+    let roles = view(&env, "tournament_role", tournament_id).await;
+    assert_eq!(roles.len(), 3);
+    assert_eq!(roles[0]["userId"], 1);
+    assert_eq!(roles[0]["role"], "OWNER");
+
+    env.teardown().await;
+}
+
+#[tokio::test]
+async fn private_tournaments() {
+    let env = match TestEnv::new().await {
+        Some(env) => env,
+        None => return,
+    };
+
+    let mut props = new_tournament(1);
+    props["visibility"] = json!("INVITE_CODE");
+    let invite_only_id = env.ok("/public/tournament/new", props).await["tournament"]
+        ["tournamentId"]
+        .as_i64()
+        .unwrap();
+    let codes = env
+        .ok(
+            "/public/tournament_invite_code/view",
+            json!({"tournamentId": invite_only_id, "apiKey": api_key(1)}),
+        )
+        .await;
+    let invite_code = codes[0]["inviteCode"].as_str().unwrap().to_owned();
+    env.err(
+        "/public/tournament_membership/new",
+        json!({"tournamentId": invite_only_id, "active": true, "apiKey": api_key(2)}),
+        StatusCode::BAD_REQUEST,
+        "TOURNAMENT_INVITE_CODE_INVALID",
+    )
+    .await;
+    env.ok(
+        "/public/tournament_membership/new",
+        json!({"tournamentId": invite_only_id, "active": true, "inviteCode": invite_code, "apiKey": api_key(2)}),
+    )
+    .await;
+
+    let mut props = new_tournament(1);
+    props["visibility"] = json!("APPROVAL_REQUIRED");
+    let approval_id = env.ok("/public/tournament/new", props).await["tournament"]["tournamentId"]
+        .as_i64()
+        .unwrap();
+    join(&env, 2, approval_id).await;
+    join(&env, 3, approval_id).await;
+
+    let queue = |approval: &str| {
+        json!({
+            "tournamentId": [approval_id],
+            "approval": approval,
+            "onlyRecent": true,
+            "apiKey": api_key(1),
+        })
+    };
+    let pending = env
+        .ok("/public/tournament_membership/view", queue("PENDING"))
+        .await;
+    assert_eq!(pending.as_array().unwrap().len(), 2);
+
+    env.ok(
+        "/public/tournament_membership_approval/new",
+        json!({"tournamentId": approval_id, "userId": 2, "approved": true, "apiKey": api_key(1)}),
+    )
+    .await;
+    let pending = env
+        .ok("/public/tournament_membership/view", queue("PENDING"))
+        .await;
+    assert_eq!(pending.as_array().unwrap().len(), 1);
+    assert_eq!(pending[0]["creatorUserId"], 3);
+    assert_eq!(
+        view(&env, "tournament_year_demand", approval_id)
+            .await
+            .len(),
+        1
+    );
+
+    // organizers can take players out again, and say why
+    let membership = env
+        .ok(
+            "/public/tournament_membership_action/new",
+            json!({"tournamentId": approval_id, "userId": 2, "action": "BAN", "reason": "spam", "apiKey": api_key(1)}),
+        )
+        .await;
+    assert_eq!(membership["banned"], true);
+    assert_eq!(membership["actorUserId"], 1);
+    env.err(
+        "/public/tournament_membership/new",
+        json!({"tournamentId": approval_id, "active": true, "apiKey": api_key(2)}),
+        StatusCode::BAD_REQUEST,
+        "TOURNAMENT_MEMBERSHIP_BANNED",
+    )
+    .await;
+    let history = view(&env, "tournament_membership", approval_id).await;
+    assert_eq!(history.last().unwrap()["reason"], "spam");
+
+    env.teardown().await;
+}
+
+#[tokio::test]
+async fn player_limits_and_auto_start() {
+    let env = match TestEnv::new().await {
+        Some(env) => env,
+        None => return,
+    };
+
+    let mut props = new_tournament(1);
+    props["minPlayers"] = json!(3);
+    props["maxPlayers"] = json!(2);
+    env.err(
+        "/public/tournament/new",
+        props.clone(),
+        StatusCode::BAD_REQUEST,
+        "TOURNAMENT_PLAYER_COUNT_INVALID",
+    )
+    .await;
+
+    props["minPlayers"] = json!(2);
+    props["maxPlayers"] = json!(2);
+    props["autoStart"] = json!(true);
+    props["lateJoinPolicy"] = json!("CATCH_UP");
+    let data = env.ok("/public/tournament/new", props).await;
+    assert_eq!(data["tournament"]["minPlayers"], 2);
+    assert_eq!(data["tournament"]["maxPlayers"], 2);
+    assert_eq!(data["tournament"]["autoStart"], true);
+    let tournament_id = data["tournament"]["tournamentId"].as_i64().unwrap();
+
+    // the second player starts the game, the third doesn't fit
+    join(&env, 2, tournament_id).await;
+    let membership = env
+        .ok(
+            "/public/tournament_membership/new",
+            json!({"tournamentId": tournament_id, "active": true, "apiKey": api_key(3)}),
+        )
+        .await;
+    assert_eq!(membership["tournament"]["state"], "RUNNING");
+    env.err(
+        "/public/tournament_membership/new",
+        json!({"tournamentId": tournament_id, "active": true, "apiKey": api_key(4)}),
+        StatusCode::BAD_REQUEST,
+        "TOURNAMENT_FULL",
+    )
+    .await;
+    submit(&env, 2, tournament_id, 10).await;
+    assert_eq!(advance(&env, 1, tournament_id).await, 1);
+
+    // without auto start, the organizer can't start early
+    let mut props = new_tournament(1);
+    props["minPlayers"] = json!(2);
+    let data = env.ok("/public/tournament/new", props).await;
+    let tournament_id = data["tournament"]["tournamentId"].as_i64().unwrap();
+    join(&env, 2, tournament_id).await;
+    env.err(
+        "/public/tournament_state/new",
+        json!({"tournamentId": tournament_id, "state": "RUNNING", "apiKey": api_key(1)}),
+        StatusCode::BAD_REQUEST,
+        "TOURNAMENT_NOT_ENOUGH_PLAYERS",
+    )
+    .await;
+    join(&env, 3, tournament_id).await;
+    set_state(&env, 1, tournament_id, "RUNNING").await;
+
+    env.teardown().await;
+}
+
+#[tokio::test]
+async fn team_firms() {
+    let env = match TestEnv::new().await {
+        Some(env) => env,
+        None => return,
+    };
+
+    let tournament_id = create_tournament(&env, 1).await;
+    let team = env
+        .ok(
+            "/public/tournament_team/new",
+            json!({"tournamentId": tournament_id, "name": "red", "apiKey": api_key(1)}),
+        )
+        .await;
+    assert_eq!(team["name"], "red");
+    let team_id = team["tournamentTeamId"].as_i64().unwrap();
+    assert_eq!(view(&env, "tournament_team", tournament_id).await.len(), 1);
+
+    for user_id in [2, 3] {
+        let membership = env
+            .ok(
+                "/public/tournament_membership/new",
+                json!({"tournamentId": tournament_id, "active": true, "tournamentTeamId": team_id, "apiKey": api_key(user_id)}),
+            )
+            .await;
+        assert_eq!(membership["tournamentTeamId"], team_id);
+    }
+    env.err(
+        "/public/tournament_membership/new",
+        json!({"tournamentId": tournament_id, "active": true, "tournamentTeamId": team_id + 1000, "apiKey": api_key(4)}),
+        StatusCode::BAD_REQUEST,
+        "TOURNAMENT_TEAM_INVALID",
+    )
+    .await;
+    join(&env, 4, tournament_id).await;
+    assert_eq!(
+        view(&env, "tournament_year_demand", tournament_id)
+            .await
+            .len(),
+        2
+    );
+
+    // either teammate submits for the firm, the last one counts
+    set_state(&env, 1, tournament_id, "RUNNING").await;
+    let submission = submit(&env, 2, tournament_id, 10).await;
+    assert_eq!(submission["tournamentTeamId"], team_id);
+    submit(&env, 3, tournament_id, 20).await;
+    submit(&env, 4, tournament_id, 20).await;
+    advance(&env, 1, tournament_id).await;
+
+    let results = finalize(&env, 1, tournament_id).await;
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0]["userId"], Value::Null);
+    assert_eq!(results[0]["tournamentTeamId"], team_id);
+    assert_eq!(results[1]["userId"], 4);
+    assert_eq!(results[0]["balance"], results[1]["balance"]);
+
+    env.teardown().await;
+}
+
+#[tokio::test]
+async fn pseudonyms() {
+    let env = match TestEnv::new().await {
+        Some(env) => env,
+        None => return,
+    };
+
+    let tournament_id = create_tournament(&env, 1).await;
+    let membership = env
+        .ok(
+            "/public/tournament_membership/new",
+            json!({"tournamentId": tournament_id, "active": true, "apiKey": api_key(2)}),
+        )
+        .await;
+    let pseudonym = membership["pseudonym"].as_str().unwrap().to_owned();
+    assert!(pseudonym.starts_with("Firm "));
+
+    // organizers can map pseudonyms back to players, nobody else can
+    let pseudonyms = env
+        .ok(
+            "/public/tournament_pseudonym/view",
+            json!({"tournamentId": tournament_id, "apiKey": api_key(1)}),
+        )
+        .await;
+    assert_eq!(pseudonyms[0]["userId"], 2);
+    assert_eq!(pseudonyms[0]["pseudonym"], pseudonym.as_str());
+    env.err(
+        "/public/tournament_pseudonym/view",
+        json!({"tournamentId": tournament_id, "apiKey": api_key(2)}),
+        StatusCode::BAD_REQUEST,
+        "TOURNAMENT_NONEXISTENT",
+    )
+    .await;
+
+    set_state(&env, 1, tournament_id, "RUNNING").await;
+    let submission = submit(&env, 2, tournament_id, 10).await;
+    assert_eq!(submission["pseudonym"], pseudonym.as_str());
+
+    env.teardown().await;
+}
+
+#[tokio::test]
+async fn bots() {
+    let env = match TestEnv::new().await {
+        Some(env) => env,
+        None => return,
+    };
+
+    let tournament_id = create_tournament(&env, 1).await;
+    let bot = env
+        .ok(
+            "/public/tournament_bot/new",
+            json!({"tournamentId": tournament_id, "strategy": "FIXED_QUANTITY", "quantity": 12, "apiKey": api_key(1)}),
+        )
+        .await;
+    assert_eq!(bot["strategy"], "FIXED_QUANTITY");
+    let bot_id = bot["tournamentBotId"].as_i64().unwrap();
+    env.err(
+        "/public/tournament_bot/new",
+        json!({"tournamentId": tournament_id, "strategy": "NASH", "apiKey": api_key(2)}),
+        StatusCode::BAD_REQUEST,
+        "TOURNAMENT_NONEXISTENT",
+    )
+    .await;
+    assert_eq!(view(&env, "tournament_bot", tournament_id).await.len(), 1);
+
+    join(&env, 2, tournament_id).await;
+    set_state(&env, 1, tournament_id, "RUNNING").await;
+    submit(&env, 2, tournament_id, 20).await;
+    advance(&env, 1, tournament_id).await;
+
+    let submissions = view(&env, "tournament_submission", tournament_id).await;
+    let bot_submission = submissions
+        .iter()
+        .find(|s| s["tournamentBotId"] == bot_id)
+        .unwrap();
+    assert_eq!(bot_submission["amount"], 12);
+    assert_eq!(bot_submission["pseudonym"], bot["name"]);
+
+    let results = finalize(&env, 1, tournament_id).await;
+    assert_eq!(results.len(), 2);
+    assert!(results.iter().any(|r| r["tournamentBotId"] == bot_id));
+
+    env.teardown().await;
+}
+
+#[tokio::test]
+async fn practice() {
+    let env = match TestEnv::new().await {
+        Some(env) => env,
+        None => return,
+    };
+
+    let mut tournament = new_tournament(2);
+    tournament["practice"] = json!(true);
+    let data = env.ok("/public/tournament/new", tournament).await;
+    assert_eq!(data["tournament"]["practice"], true);
+    assert_eq!(data["tournament"]["state"], "RUNNING");
+    let tournament_id = data["tournament"]["tournamentId"].as_i64().unwrap();
+
+    env.err(
+        "/public/tournament_membership/new",
+        json!({"tournamentId": tournament_id, "active": true, "apiKey": api_key(3)}),
+        StatusCode::BAD_REQUEST,
+        "TOURNAMENT_PRACTICE",
+    )
+    .await;
+
+    for year in 0..=3 {
+        let submission = submit(&env, 2, tournament_id, 20).await;
+        assert_eq!(submission["year"], year);
+    }
+    let results = env
+        .ok(
+            "/public/tournament_final_result/view",
+            json!({"tournamentId": tournament_id, "apiKey": api_key(2)}),
+        )
+        .await;
+    assert_eq!(results.as_array().unwrap().len(), 4);
+    env.err(
+        "/public/tournament_final_result/view",
+        json!({"tournamentId": tournament_id, "apiKey": api_key(1)}),
+        StatusCode::BAD_REQUEST,
+        "UNAUTHORIZED",
+    )
+    .await;
+
+    // the student finds practice games alongside the rest, nobody else sees them at all
+    let listed = |user_id: i64, practice: Value| json!({"tournamentId": [tournament_id], "practice": practice, "onlyRecent": true, "apiKey": api_key(user_id)});
+    for kind in [
+        "tournament_data",
+        "tournament_year",
+        "tournament_submission",
+        "tournament_year_demand",
+    ] {
+        let path = format!("/public/{}/view", kind);
+        for practice in [Value::Null, json!(true)] {
+            let rows = env.ok(&path, listed(2, practice.clone())).await;
+            assert!(!rows.as_array().unwrap().is_empty(), "{}", kind);
+            let rows = env.ok(&path, listed(1, practice)).await;
+            assert_eq!(rows.as_array().unwrap().len(), 0, "{}", kind);
+        }
+        let rows = env.ok(&path, listed(2, json!(false))).await;
+        assert_eq!(rows.as_array().unwrap().len(), 0, "{}", kind);
+    }
+
+    // and don't count as active
+    let resp = env.raw("GET", "/metrics", "", &[]).await;
+    let body = String::from_utf8(resp.body().to_vec()).unwrap();
+    assert!(body.contains("active_tournaments 0\n"));
+
+    env.teardown().await;
+}
+
+#[tokio::test]
+async fn benchmarks() {
+    let env = match TestEnv::new().await {
+        Some(env) => env,
+        None => return,
+    };
+
+    let tournament_id = create_tournament(&env, 1).await;
+    join(&env, 2, tournament_id).await;
+    join(&env, 3, tournament_id).await;
+    set_state(&env, 1, tournament_id, "RUNNING").await;
+    advance(&env, 1, tournament_id).await;
+
+    // price is 100 - Q and costs are 10, incentives start in year 2
+    let benchmarks = env
+        .ok(
+            "/public/tournament_benchmarks/view",
+            json!({"tournamentId": tournament_id, "apiKey": api_key(1)}),
+        )
+        .await;
+    let benchmarks = benchmarks.as_array().unwrap();
+    assert_eq!(benchmarks.len(), 2);
+    assert_eq!(benchmarks[1]["year"], 1);
+    assert_eq!(benchmarks[1]["firms"], 2);
+    assert_eq!(benchmarks[1]["nash"]["quantity"], 30.0);
+    assert_eq!(benchmarks[1]["nash"]["price"], 40.0);
+    assert_eq!(benchmarks[1]["monopoly"]["totalQuantity"], 45.0);
+    assert_eq!(benchmarks[1]["competitive"]["price"], 10.0);
+
+    env.err(
+        "/public/tournament_benchmarks/view",
+        json!({"tournamentId": tournament_id, "apiKey": "nope"}),
+        StatusCode::BAD_REQUEST,
+        "UNAUTHORIZED",
+    )
+    .await;
+
+    env.teardown().await;
+}
+
+#[tokio::test]
+async fn analytics() {
+    let env = match TestEnv::new().await {
+        Some(env) => env,
+        None => return,
+    };
+
+    let tournament_id = create_tournament(&env, 1).await;
+    join(&env, 2, tournament_id).await;
+    join(&env, 3, tournament_id).await;
+    set_state(&env, 1, tournament_id, "RUNNING").await;
+    submit(&env, 2, tournament_id, 30).await;
+    submit(&env, 3, tournament_id, 10).await;
+    advance(&env, 1, tournament_id).await;
+    submit(&env, 2, tournament_id, 50).await;
+
+    env.err(
+        "/public/tournament_analytics/view",
+        json!({"tournamentId": tournament_id, "apiKey": api_key(2)}),
+        StatusCode::BAD_REQUEST,
+        "TOURNAMENT_NONEXISTENT",
+    )
+    .await;
+    let analytics = env
+        .ok(
+            "/public/tournament_analytics/view",
+            json!({"tournamentId": tournament_id, "apiKey": api_key(1)}),
+        )
+        .await;
+
+    // the year still being played is left out
+    let years = analytics["years"].as_array().unwrap();
+    assert_eq!(years.len(), 1);
+    assert_eq!(years[0]["hhi"], 75.0 * 75.0 + 25.0 * 25.0);
+    assert_eq!(years[0]["nashPriceGap"], 20.0);
+    assert_eq!(years[0]["firms"][0]["userId"], 2);
+    assert_eq!(years[0]["firms"][0]["share"], 0.75);
+    assert_eq!(years[0]["firms"][0]["deviation"], -10.0);
+    assert_eq!(analytics["firms"].as_array().unwrap().len(), 2);
+
+    env.teardown().await;
+}
+
+#[tokio::test]
+async fn collusion() {
+    let env = match TestEnv::new().await {
+        Some(env) => env,
+        None => return,
+    };
+
+    let tournament_id = create_tournament(&env, 1).await;
+    join(&env, 2, tournament_id).await;
+    join(&env, 3, tournament_id).await;
+    set_state(&env, 1, tournament_id, "RUNNING").await;
+    // both keep to half the monopoly output for two years
+    for _ in 0..2 {
+        submit(&env, 2, tournament_id, 22).await;
+        submit(&env, 3, tournament_id, 22).await;
+        advance(&env, 1, tournament_id).await;
+    }
+
+    env.err(
+        "/public/tournament_collusion/view",
+        json!({"tournamentId": tournament_id, "apiKey": api_key(3)}),
+        StatusCode::BAD_REQUEST,
+        "TOURNAMENT_NONEXISTENT",
+    )
+    .await;
+    let collusion = env
+        .ok(
+            "/public/tournament_collusion/view",
+            json!({"tournamentId": tournament_id, "apiKey": api_key(1)}),
+        )
+        .await;
+
+    let years = collusion["years"].as_array().unwrap();
+    assert_eq!(years.len(), 2);
+    assert_eq!(years[0]["nearMonopoly"], true);
+    assert_eq!(collusion["longestNearMonopoly"], 2);
+    assert_eq!(collusion["sustainedNearMonopoly"], false);
+
+    let pairs = collusion["pairs"].as_array().unwrap();
+    assert_eq!(pairs.len(), 1);
+    assert_eq!(pairs[0]["firms"][0]["userId"], 2);
+    assert_eq!(pairs[0]["firms"][1]["userId"], 3);
+    assert_eq!(pairs[0]["longestCooperation"], 2);
+    assert_eq!(pairs[0]["defections"], 0);
+
+    env.teardown().await;
+}
+
+#[tokio::test]
+async fn request_errors() {
+    let env = match TestEnv::new().await {
+        Some(env) => env,
+        None => return,
+    };
+
+    let (status, value) = env.request("POST", "/public/nonexistent", "{}").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(value["error"], json!("NOT_FOUND"));
+
+    let (status, value) = env
+        .request("POST", "/public/tournament/new", "{not json")
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(value["error"], json!("DECODE_ERROR"));
+
+    // well formed, but missing fields
+    let (status, value) = env
+        .request("POST", "/public/tournament/new", r#"{"apiKey": "key-1"}"#)
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(value["error"], json!("DECODE_ERROR"));
+
+    env.teardown().await;
+}
+
+#[tokio::test]
+async fn request_ids() {
+    let env = match TestEnv::new().await {
+        Some(env) => env,
+        None => return,
+    };
+
+    // requests that never reach a handler get an id too, in the header and the body
+    for (path, body) in [
+        ("/public/tournament/new", "{not json"),
+        ("/public/nonexistent", "{}"),
+    ] {
+        let resp = env.raw("POST", path, body, &[]).await;
+        let id = resp.headers()["x-request-id"].to_str().unwrap().to_owned();
+        assert!(!id.is_empty());
+        let value: Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(value["requestId"], json!(id), "{}", path);
+    }
+
+    // the caller's own id is kept, whether the request worked or not
+    let headers = [("x-request-id", "trace-123")];
+    let resp = env
+        .raw("POST", "/public/tournament/new", "{not json", &headers)
+        .await;
+    assert_eq!(resp.headers()["x-request-id"], "trace-123");
+    let value: Value = serde_json::from_slice(resp.body()).unwrap();
+    assert_eq!(value["requestId"], json!("trace-123"));
+    let resp = env.raw("GET", "/health/live", "", &headers).await;
+    assert_eq!(resp.headers()["x-request-id"], "trace-123");
+
+    // but not if it's garbage
+    let resp = env
+        .raw("GET", "/health/live", "", &[("x-request-id", "a b")])
+        .await;
+    assert_ne!(resp.headers()["x-request-id"], "a b");
+
+    env.teardown().await;
+}
+
+#[tokio::test]
+async fn health_and_metrics() {
+    let env = match TestEnv::new().await {
+        Some(env) => env,
+        None => return,
+    };
+
+    let (status, _) = env.request("GET", "/health/live", "").await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, value) = env.request("GET", "/health/ready", "").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(value, json!({"database": true, "authService": true}));
+
+    create_tournament(&env, 1).await;
+    env.request("POST", "/public/tournament/new", "{not json")
+        .await;
+    env.request("GET", "/wp-login.php", "").await;
+
+    let resp = env.raw("GET", "/metrics", "", &[]).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()["content-type"], "text/plain; version=0.0.4");
+    let body = String::from_utf8(resp.body().to_vec()).unwrap();
+    for line in [
+        "# TYPE http_requests_total counter",
+        "http_requests_total{route=\"/public/tournament/new\",status=\"200\"}",
+        "# TYPE http_request_duration_seconds histogram",
+        "http_request_duration_seconds_bucket{route=\"/public/tournament/new\",le=\"+Inf\"}",
+        "http_request_duration_seconds_count{route=\"/public/tournament/new\"}",
+        "# TYPE app_errors_total counter",
+        "app_errors_total{error=\"DecodeError\"}",
+        "# TYPE active_tournaments gauge",
+        "active_tournaments 1\n",
+        "# TYPE tournament_year_advance_duration_seconds histogram",
+        "# TYPE db_pool_wait_seconds histogram",
+    ] {
+        assert!(body.contains(line), "missing {} in\n{}", line, body);
+    }
+    // every sample is a name, optional labels and a number
+    for sample in body.lines().filter(|l| !l.starts_with('#')) {
+        let (_, value) = sample.rsplit_once(' ').unwrap();
+        assert!(value.parse::<f64>().is_ok(), "{}", sample);
+    }
+    // unmatched routes aren't errors on our side
+    assert!(!body.contains("NotFound"));
+
+    env.teardown().await;
+}
+
+#[tokio::test]
+async fn dependency_errors() {
+    let mut env = match TestEnv::with_auth(unreachable_auth().await).await {
+        Some(env) => env,
+        None => return,
+    };
+
+    env.err(
+        "/public/tournament/new",
+        new_tournament(1),
+        StatusCode::SERVICE_UNAVAILABLE,
+        "AUTH_SERVICE_UNAVAILABLE",
+    )
+    .await;
+
+    // views check the api key too, since what they show depends on who's asking
+    env.err(
+        "/public/tournament_data/view",
+        json!({"onlyRecent": true, "apiKey": api_key(1)}),
+        StatusCode::SERVICE_UNAVAILABLE,
+        "AUTH_SERVICE_UNAVAILABLE",
+    )
+    .await;
+
+    let (status, value) = env.request("GET", "/health/ready", "").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(value, json!({"database": true, "authService": false}));
+
+    env.disconnect_database().await;
+    let (status, value) = env.request("GET", "/health/ready", "").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(value, json!({"database": false, "authService": false}));
+
+    env.teardown().await;
+}
+
+#[tokio::test]
+async fn concurrent_first_joins() {
+    let env = match TestEnv::new().await {
+        Some(env) => env,
+        None => return,
+    };
+
+    let tournament_id = create_tournament(&env, 1).await;
+
+    // the same join sent twice at once, like a double click, only lets them in once
+    for user_id in 2..=5 {
+        let body = json!({"tournamentId": tournament_id, "active": true, "apiKey": api_key(user_id)});
+        let path = "/public/tournament_membership/new";
+        let ((status_a, a), (status_b, b)) =
+            tokio::join!(env.post(path, body.clone()), env.post(path, body));
+        assert_eq!(status_a, StatusCode::OK, "{}", a);
+        assert_eq!(status_b, StatusCode::OK, "{}", b);
+        assert_eq!(a["tournamentMembershipId"], b["tournamentMembershipId"]);
+    }
+
+    env.teardown().await;
+}
+
+#[tokio::test]
+async fn transactions_run_side_by_side() {
+    let env = match TestEnv::new().await {
+        Some(env) => env,
+        None => return,
+    };
+    let db = env.db();
+
+    // every transaction gets a connection of its own, instead of waiting for the others
+    let mut open = vec![];
+    for _ in 0..POOL_SIZE {
+        let sp = tokio::time::timeout(Duration::from_secs(1), db.begin())
+            .await
+            .expect("waited for a free connection")
+            .unwrap();
+        open.push(sp);
+    }
+
+    // once they're all taken, the next one waits its turn
+    assert!(tokio::time::timeout(Duration::from_millis(100), db.begin())
+        .await
+        .is_err());
+    open.pop();
+    let sp = tokio::time::timeout(Duration::from_secs(1), db.begin())
+        .await
+        .expect("connection was never given back")
+        .unwrap();
+    sp.commit().await.unwrap();
+
+    drop(open);
+    env.teardown().await;
+}
+
+#[tokio::test]
+async fn database_reconnects() {
+    let env = match TestEnv::new().await {
+        Some(env) => env,
+        None => return,
+    };
+
+    create_tournament(&env, 1).await;
+    env.kill_database_connections().await;
+
+    // until the connection is back, requests fail cleanly instead of hanging or panicking
+    let mut recovered = false;
+    for _ in 0..50 {
+        let (status, value) = env
+            .post(
+                "/public/tournament_data/view",
+                json!({"onlyRecent": true, "apiKey": api_key(1)}),
+            )
+            .await;
+        if status == StatusCode::OK {
+            assert_eq!(value.as_array().unwrap().len(), 1);
+            recovered = true;
+            break;
+        }
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE, "{}", value);
+        assert_eq!(value["error"], json!("DATABASE_UNAVAILABLE"));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(recovered, "database connection was never reestablished");
+
+    // and writes work again too
+    create_tournament(&env, 1).await;
+
+    env.teardown().await;
+}
+
+// NO_CAPABILITY, TOURNAMENT_SUBMISSION_TESTCASE_*, BAD_REQUEST, NETWORK and UNKNOWN are never
+// returned by any handler, and METHOD_NOT_ALLOWED can't happen since no route filters on method.
@@ -0,0 +1,213 @@
+// Shared harness for the integration tests.
+//
+// Each test gets its own postgres schema, loaded from sql/1-production_minigame.sql.
+// Api keys from api_key() are checked with DevAuth, so no auth service is needed.
+// Set TEST_DATABASE_URL to a database the tests may create schemas in,
+// e.g. postgres://postgres@localhost/postgres. Without it the tests are skipped.
+
+#![allow(dead_code)]
+
+use auth_service_api::client::AuthService;
+use production_minigame_service::api;
+use production_minigame_service::auth_client::{AuthClient, AuthClientConfig};
+use production_minigame_service::db;
+use production_minigame_service::dev_auth::DevAuth;
+use production_minigame_service::{Auth, Config, Db};
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio_postgres::NoTls;
+use warp::http::{Response, StatusCode};
+use warp::hyper::body::Bytes;
+
+static SCHEMA_SQL: &str = include_str!("../../sql/1-production_minigame.sql");
+
+// small, so tests run into it
+pub const POOL_SIZE: usize = 2;
+
+pub fn api_key(user_id: i64) -> String {
+    format!("key-{}", user_id)
+}
+
+// authenticates "key-<n>" as user n, without needing an auth service
+pub fn dev_auth() -> Auth {
+    Arc::new(DevAuth::new((1..=20).map(|id| (api_key(id), id)).collect()))
+}
+
+// what the api under test runs with
+pub fn config() -> Config {
+    Config {
+        site_external_url: "http://localhost:3000".to_owned(),
+        idempotency_window_ms: 60_000,
+    }
+}
+
+// a client for an auth service that isn't there, for testing what happens when it's down
+pub async fn unreachable_auth() -> Auth {
+    // a port with nothing listening on it
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    drop(listener);
+
+    Arc::new(AuthClient::new(
+        AuthService::new(&format!("http://{}", addr)).await,
+        AuthClientConfig {
+            timeout: Duration::from_millis(500),
+            max_retries: 1,
+            retry_backoff: Duration::from_millis(10),
+            failure_threshold: 100,
+            reset_timeout: Duration::from_millis(100),
+        },
+    ))
+}
+
+pub struct TestEnv {
+    database_url: String,
+    schema: String,
+    db: Db,
+    auth_service: Auth,
+    shutdown_tx: watch::Sender<bool>,
+    db_supervisor: Option<JoinHandle<()>>,
+}
+
+impl TestEnv {
+    // returns None if no test database was configured
+    pub async fn new() -> Option<TestEnv> {
+        TestEnv::with_auth(dev_auth()).await
+    }
+
+    pub async fn with_auth(auth_service: Auth) -> Option<TestEnv> {
+        let database_url = match std::env::var("TEST_DATABASE_URL") {
+            Ok(v) => v,
+            Err(_) => {
+                eprintln!("TEST_DATABASE_URL not set, skipping");
+                return None;
+            }
+        };
+
+        let schema = format!("test_{:016x}", rand::random::<u64>());
+        let (client, connection) = tokio_postgres::connect(&database_url, NoTls)
+            .await
+            .expect("couldn't connect to TEST_DATABASE_URL");
+        tokio::spawn(connection);
+
+        // the schema file creates and switches to its own database, which we don't want here
+        let sql = SCHEMA_SQL
+            .lines()
+            .filter(|l| !l.starts_with("CREATE DATABASE") && !l.starts_with("\\c"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        client
+            .batch_execute(&format!(
+                "CREATE SCHEMA {0}; SET search_path TO {0}; {1}",
+                schema, sql
+            ))
+            .await
+            .expect("couldn't load schema");
+
+        let separator = if database_url.contains('?') { '&' } else { '?' };
+        // the application name lets us find our own connections again
+        let schema_url = format!(
+            "{}{}options=-c%20search_path%3D{}&application_name={}",
+            database_url, separator, schema, schema
+        );
+
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let (database, db_supervisor) =
+            db::Database::connect(schema_url, POOL_SIZE, shutdown_rx).await;
+
+        Some(TestEnv {
+            database_url,
+            schema,
+            db: database,
+            auth_service,
+            shutdown_tx,
+            db_supervisor: Some(db_supervisor),
+        })
+    }
+
+    pub fn db(&self) -> Db {
+        self.db.clone()
+    }
+
+    pub async fn request(&self, method: &str, path: &str, body: &str) -> (StatusCode, Value) {
+        let resp = self.raw(method, path, body, &[]).await;
+        let value = serde_json::from_slice(resp.body()).unwrap_or(Value::Null);
+        (resp.status(), value)
+    }
+
+    // the whole response, for when the headers matter or the body isn't json
+    pub async fn raw(
+        &self,
+        method: &str,
+        path: &str,
+        body: &str,
+        headers: &[(&str, &str)],
+    ) -> Response<Bytes> {
+        let filter = api::api(config(), self.db.clone(), self.auth_service.clone());
+        let mut request = warp::test::request()
+            .method(method)
+            .path(path)
+            .header("content-type", "application/json");
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        request.body(body).reply(&filter).await
+    }
+
+    pub async fn post(&self, path: &str, body: Value) -> (StatusCode, Value) {
+        self.request("POST", path, &body.to_string()).await
+    }
+
+    // posts, and panics unless the request succeeded
+    pub async fn ok(&self, path: &str, body: Value) -> Value {
+        let (status, value) = self.post(path, body).await;
+        assert_eq!(status, StatusCode::OK, "{} failed: {}", path, value);
+        value
+    }
+
+    // posts, and checks that the request failed with the given error
+    pub async fn err(&self, path: &str, body: Value, status: StatusCode, error: &str) {
+        let (actual_status, value) = self.post(path, body).await;
+        assert_eq!(value["error"], Value::String(error.to_owned()), "{}", path);
+        assert!(value["requestId"].is_string(), "{}", path);
+        assert_eq!(actual_status, status, "{}", path);
+    }
+
+    // stops the database supervisor, so the store reports itself unavailable
+    pub async fn disconnect_database(&mut self) {
+        self.shutdown_tx.send(true).ok();
+        if let Some(db_supervisor) = self.db_supervisor.take() {
+            db_supervisor.await.ok();
+        }
+    }
+
+    // terminates our connections from the postgres side, like a database restart would
+    pub async fn kill_database_connections(&self) {
+        let (client, connection) = tokio_postgres::connect(&self.database_url, NoTls)
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+        client
+            .execute(
+                "SELECT pg_terminate_backend(pid) FROM pg_stat_activity WHERE application_name = $1",
+                &[&self.schema],
+            )
+            .await
+            .unwrap();
+    }
+
+    pub async fn teardown(mut self) {
+        self.disconnect_database().await;
+        let (client, connection) = tokio_postgres::connect(&self.database_url, NoTls)
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+        client
+            .batch_execute(&format!("DROP SCHEMA {} CASCADE", self.schema))
+            .await
+            .unwrap();
+    }
+}
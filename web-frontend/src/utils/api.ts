@@ -87,7 +87,7 @@ async function fetchApiOrNetworkError<T>(url: string, props: object): Promise<Re
     if (code >= 200 && code < 300) {
       return { Ok: resp }
     } else {
-      return { Err: resp }
+      return { Err: resp.error }
     }
   } catch (_) {
     return { Err: "NETWORK" };